@@ -0,0 +1,379 @@
+use std::collections::BTreeMap;
+use std::io::{Cursor, ErrorKind};
+
+use mw_common::game::MwEv;
+use mw_common::plid::PlayerId;
+use mw_dataformat::header::{ISHeader, MwFileHeader};
+use mw_dataformat::msg::bin::MsgBinRead;
+use mw_dataformat::msg::MsgReader;
+use mw_dataformat::read::{FrameKind, MwFileReader, MwFrameDataReader, MwReaderError};
+
+use crate::prelude::*;
+use crate::{CommonArgs, AnalyzeArgs};
+
+#[derive(Default)]
+struct OpStats {
+    /// How many times the opcode was encoded
+    n_ops: u64,
+    /// How many messages were decoded from it
+    n_msgs: u64,
+    /// Total encoded size
+    n_bytes: u64,
+}
+
+/// Stats for the opcodes that can pack runs of multiple messages
+struct RunStats {
+    name: &'static str,
+    /// Size of each message if they were encoded individually
+    bytes_single: u64,
+    stats: OpStats,
+    /// Histogram: how many ops packed N messages
+    hist: Vec<u64>,
+}
+
+#[derive(Default)]
+struct FrameStats {
+    n_frames: u64,
+    n_bytes: u64,
+    /// Bytes spent on headers/masks/lengths, rather than payload
+    n_bytes_overhead: u64,
+}
+
+#[derive(Default)]
+struct PlidStats {
+    n_frames: u64,
+    n_bytes: u64,
+    n_msgs: u64,
+}
+
+#[derive(Default)]
+struct Analysis {
+    ops: BTreeMap<&'static str, OpStats>,
+    runs: Vec<RunStats>,
+    frames_homo: FrameStats,
+    frames_hetero: FrameStats,
+    frames_keepalive: FrameStats,
+    plids: Vec<PlidStats>,
+    duration_ms: u64,
+}
+
+pub fn main(common: &CommonArgs, args: &AnalyzeArgs) -> AnyResult<()> {
+    let data = if let Some(in_path) = &common.input {
+        std::fs::read(in_path)
+            .context("Cannot read input file!")?
+    } else {
+        bail!("Input filename must be specified!");
+    };
+    let mut buf = Vec::new();
+
+    let mut mfr = MwFileReader::new(Cursor::new(data.as_slice()), &mut buf)
+        .context("Failed to load input file as a MineWars format file!")?;
+
+    if !args.ignore_checksums {
+        mfr.verify_checksums()
+            .context("Checksum verification failed!")?;
+    }
+
+    let len_file = data.len();
+    let len_is = mfr.is_header().len_total_is();
+    let len_framedata = mfr.file_header().len_framedata_compressed();
+    eprintln!("File Layout:");
+    eprintln!("Total file size: {}", len_file);
+    eprintln!("File Header:     {}", MwFileHeader::serialized_len());
+    eprintln!("IS:              {}", len_is);
+    eprintln!("  IS Header:     {}", ISHeader::serialized_len());
+    eprintln!("  MapData:       {}", mfr.is_header().len_mapdata_compressed());
+    eprintln!("  Cits Pos:      {}", mfr.is_header().len_citdata_pos());
+    eprintln!("  Cits Names:    {}", mfr.is_header().len_citdata_names());
+    eprintln!("  RulesData:     {}", mfr.is_header().len_rules());
+    eprintln!("  PlayersData:   {}", mfr.is_header().len_players());
+    eprintln!("FrameData:       {}", len_framedata);
+
+    eprintln!();
+    eprintln!("LZ4 Compression:");
+    let is_header = mfr.is_header();
+    let off_mapdata = MwFileHeader::serialized_len()
+        + ISHeader::serialized_len()
+        + is_header.offset_mapdata();
+    let mapdata = data.get(off_mapdata..(off_mapdata + is_header.len_mapdata_compressed()))
+        .context("MapData is out of bounds!")?;
+    print_lz4_ratio(
+        "MapData",
+        is_header.is_mapdata_compressed(),
+        is_header.len_mapdata_raw(),
+        mapdata,
+    );
+    let framedata = mfr.get_uncompressed_framedata()
+        .context("Cannot read FrameData")?;
+    if mfr.is_framedata_compressed() {
+        let off_framedata = mfr.offset_framedata();
+        print_lz4_ratio("FrameData", true, framedata.len(), &data[off_framedata..(off_framedata + len_framedata)]);
+    } else {
+        print_lz4_ratio("FrameData", false, framedata.len(), &framedata);
+    }
+
+    let max_plid = mfr.is_header().max_plid();
+    let analysis = analyze_frames(&framedata, max_plid)
+        .context("Cannot decode FrameData")?;
+
+    eprintln!();
+    analysis.print();
+
+    Ok(())
+}
+
+/// Report the compression ratio of some data
+///
+/// If the data was stored uncompressed, compress it to show what
+/// the ratio would be.
+fn print_lz4_ratio(name: &str, compressed: bool, len_raw: usize, data: &[u8]) {
+    let len_compressed = if compressed {
+        data.len()
+    } else {
+        lz4_flex::block::compress(data).len()
+    };
+    eprintln!(
+        "{}: {} -> {} bytes ({:.1}%){}",
+        name, len_raw, len_compressed,
+        percent(len_compressed as u64, len_raw as u64),
+        if compressed { "" } else { " (not compressed in file; estimated)" },
+    );
+}
+
+fn analyze_frames(framedata: &[u8], max_plid: u8) -> AnyResult<Analysis> {
+    let mut analysis = Analysis {
+        runs: vec![
+            RunStats::new("OWNER", 3, 8),
+            RunStats::new("EXPLODE", 3, 16),
+            RunStats::new("DIGITS", 3, 8),
+        ],
+        plids: (0..=max_plid).map(|_| Default::default()).collect(),
+        ..Default::default()
+    };
+    let len_plidsmask = max_plid as u64 / 8 + 1;
+    let mut buf = Vec::new();
+    let mut msgs = Vec::new();
+    let mut reader = MwFrameDataReader::new(Cursor::new(framedata), &mut buf, max_plid)?;
+    loop {
+        match reader.advance_next_frame() {
+            Ok(()) => {}
+            Err(MwReaderError::Io(e)) if e.kind() == ErrorKind::UnexpectedEof => break,
+            Err(e) => return Err(e.into()),
+        }
+        analysis.duration_ms = reader.current_time_ms();
+        let kind = reader.frame_kind();
+        if kind == FrameKind::Keepalive {
+            analysis.frames_keepalive.n_frames += 1;
+            analysis.frames_keepalive.n_bytes += 2;
+            analysis.frames_keepalive.n_bytes_overhead += 2;
+            continue;
+        }
+        let views: Vec<bool> = (0..=max_plid)
+            .map(|i| reader.contains_view(PlayerId::from(i)))
+            .collect();
+        let mut len_payload = 0;
+        let mut n_views = 0;
+        for (i, stream) in reader.iter_streams().enumerate() {
+            let stream = stream?;
+            if !views[i] {
+                continue;
+            }
+            n_views += 1;
+            let plid = &mut analysis.plids[i];
+            plid.n_frames += 1;
+            plid.n_bytes += stream.len() as u64;
+            // Homogenous frames share one copy of the payload,
+            // so only count its messages once
+            let is_first = kind != FrameKind::Homogenous || n_views == 1;
+            if is_first {
+                len_payload += stream.len() as u64;
+            }
+            msgs.clear();
+            let n_msgs = analyze_msgs(
+                stream, &mut msgs,
+                if is_first { Some((&mut analysis.ops, &mut analysis.runs)) } else { None },
+            )?;
+            analysis.plids[i].n_msgs += n_msgs;
+        }
+        let (stats, overhead) = match kind {
+            FrameKind::Homogenous => (&mut analysis.frames_homo, 3 + len_plidsmask),
+            _ => (&mut analysis.frames_hetero, 2 + len_plidsmask + n_views),
+        };
+        stats.n_frames += 1;
+        stats.n_bytes += overhead + len_payload;
+        stats.n_bytes_overhead += overhead;
+    }
+    Ok(analysis)
+}
+
+/// Decode all the messages in a stream, optionally collecting opcode stats
+///
+/// Returns the number of messages.
+fn analyze_msgs(
+    mut stream: &[u8],
+    msgs: &mut Vec<MwEv>,
+    mut stats: Option<(&mut BTreeMap<&'static str, OpStats>, &mut [RunStats])>,
+) -> AnyResult<u64> {
+    let mut r_bin = MsgBinRead::new();
+    let mut total = 0;
+    while !stream.is_empty() {
+        let len_before = stream.len();
+        msgs.clear();
+        r_bin.read(&mut stream, msgs)
+            .context("Failed to decode binary messages")?;
+        let Some(first) = msgs.first() else {
+            break;
+        };
+        let n_msgs = msgs.len() as u64;
+        let n_bytes = (len_before - stream.len()) as u64;
+        total += n_msgs;
+        let Some((ops, runs)) = &mut stats else {
+            continue;
+        };
+        let name = op_name(first, msgs.len());
+        let op = ops.entry(name).or_default();
+        op.n_ops += 1;
+        op.n_msgs += n_msgs;
+        op.n_bytes += n_bytes;
+        let run = match first {
+            MwEv::TileOwner { .. } => &mut runs[0],
+            MwEv::Explode { .. } => &mut runs[1],
+            MwEv::DigitCapture { .. } => &mut runs[2],
+            _ => continue,
+        };
+        run.stats.n_ops += 1;
+        run.stats.n_msgs += n_msgs;
+        run.stats.n_bytes += n_bytes;
+        if let Some(x) = run.hist.get_mut(msgs.len() - 1) {
+            *x += 1;
+        }
+    }
+    Ok(total)
+}
+
+/// The name of the binary encoding that was used for a message
+///
+/// Uses the same mnemonics as the ASM format.
+fn op_name(first: &MwEv, n_msgs: usize) -> &'static str {
+    match first {
+        MwEv::Nop => "NOP",
+        MwEv::Debug(..) => "DEBUG",
+        MwEv::Player { .. } => "PLAYER",
+        MwEv::Tremor => "SHAKE",
+        MwEv::Smoke { .. } => "SMOKE",
+        MwEv::Unsmoke { .. } => "UNSMOKE",
+        MwEv::CitMoney { .. } => "CITMONEY",
+        MwEv::CitIncome { .. } => "CITINCOME",
+        MwEv::CitMoneyTransact { .. } => "CITTRANS",
+        MwEv::CitRes { .. } => "CITRES",
+        MwEv::CitTradeInfo { .. } => "CITTRADE",
+        MwEv::Flag { .. } => "FLAG",
+        MwEv::StructureGone { .. } => "NOSTRUCT",
+        MwEv::StructureHp { .. } => "STRUCTHP",
+        MwEv::Explode { .. } => "EXPLODE",
+        MwEv::BuildNew { .. } => "BUILDNEW",
+        MwEv::Construction { .. } => "BUILD",
+        MwEv::RevealStructure { .. } => "STRUCT",
+        MwEv::DigitCapture { .. } if n_msgs > 1 => "DIGITS (multi)",
+        MwEv::DigitCapture { .. } => "DIGITS (single)",
+        MwEv::RevealItem { .. } => "ITEM",
+        MwEv::TileKind { .. } => "TILE",
+        MwEv::TileOwner { .. } => "OWNER",
+    }
+}
+
+impl RunStats {
+    fn new(name: &'static str, bytes_single: u64, max_run: usize) -> Self {
+        Self {
+            name,
+            bytes_single,
+            stats: Default::default(),
+            hist: vec![0; max_run],
+        }
+    }
+}
+
+impl Analysis {
+    fn print(&self) {
+        eprintln!("Frames (duration: {} ms):", self.duration_ms);
+        let total_frames = self.frames_homo.n_frames
+            + self.frames_hetero.n_frames
+            + self.frames_keepalive.n_frames;
+        let total_bytes = self.frames_homo.n_bytes
+            + self.frames_hetero.n_bytes
+            + self.frames_keepalive.n_bytes;
+        eprintln!("{:<14} {:>8} {:>7} {:>10} {:>7} {:>10}", "Kind", "Frames", "%", "Bytes", "%", "Overhead");
+        for (name, stats) in [
+            ("Homogenous", &self.frames_homo),
+            ("Heterogenous", &self.frames_hetero),
+            ("Keepalive", &self.frames_keepalive),
+        ] {
+            eprintln!(
+                "{:<14} {:>8} {:>6.1}% {:>10} {:>6.1}% {:>10}",
+                name,
+                stats.n_frames, percent(stats.n_frames, total_frames),
+                stats.n_bytes, percent(stats.n_bytes, total_bytes),
+                stats.n_bytes_overhead,
+            );
+        }
+        eprintln!("{:<14} {:>8} {:>7} {:>10}", "Total", total_frames, "", total_bytes);
+
+        eprintln!();
+        eprintln!("Opcodes (each payload counted once):");
+        let total_bytes: u64 = self.ops.values().map(|op| op.n_bytes).sum();
+        let mut ops: Vec<_> = self.ops.iter().collect();
+        ops.sort_by(|a, b| b.1.n_bytes.cmp(&a.1.n_bytes));
+        eprintln!("{:<16} {:>8} {:>8} {:>10} {:>7}", "Opcode", "Count", "Msgs", "Bytes", "%");
+        for (name, op) in ops {
+            eprintln!(
+                "{:<16} {:>8} {:>8} {:>10} {:>6.1}%",
+                name, op.n_ops, op.n_msgs, op.n_bytes, percent(op.n_bytes, total_bytes),
+            );
+        }
+
+        eprintln!();
+        eprintln!("Run Packing:");
+        for run in &self.runs {
+            let s = &run.stats;
+            if s.n_ops == 0 {
+                eprintln!("{}: (none)", run.name);
+                continue;
+            }
+            let bytes_unpacked = s.n_msgs * run.bytes_single;
+            eprintln!(
+                "{}: {} msgs in {} ops ({:.2} msgs/op), {} bytes ({:.2} bytes/msg, {:.1}% of unpacked {} bytes)",
+                run.name, s.n_msgs, s.n_ops,
+                s.n_msgs as f64 / s.n_ops as f64,
+                s.n_bytes,
+                s.n_bytes as f64 / s.n_msgs as f64,
+                percent(s.n_bytes, bytes_unpacked),
+                bytes_unpacked,
+            );
+            let hist: Vec<String> = run.hist.iter().enumerate()
+                .filter(|(_, n)| **n != 0)
+                .map(|(i, n)| format!("{}:{}", i + 1, n))
+                .collect();
+            eprintln!("  run lengths: {}", hist.join(" "));
+        }
+
+        eprintln!();
+        eprintln!("Per-PlayerId Streams:");
+        eprintln!("{:<10} {:>8} {:>8} {:>10}", "PlayerId", "Frames", "Msgs", "Bytes");
+        for (i, plid) in self.plids.iter().enumerate() {
+            let name = if i == 0 {
+                "spectator".to_owned()
+            } else {
+                i.to_string()
+            };
+            eprintln!("{:<10} {:>8} {:>8} {:>10}", name, plid.n_frames, plid.n_msgs, plid.n_bytes);
+        }
+    }
+}
+
+fn percent(x: u64, total: u64) -> f64 {
+    if total == 0 {
+        0.0
+    } else {
+        x as f64 * 100.0 / total as f64
+    }
+}
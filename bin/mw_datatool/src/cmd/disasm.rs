@@ -1,14 +1,50 @@
-use std::io::{BufReader, BufWriter, BufRead, Write};
+use std::io::{BufReader, BufWriter, BufRead, ErrorKind, Read, Seek, Write};
+use std::path::Path;
 
+use mw_common::game::MwEv;
+use mw_dataformat::index::MwFrameIndex;
 use mw_dataformat::msg::asm::MsgAsmWrite;
 use mw_dataformat::msg::bin::MsgBinRead;
 use mw_dataformat::msg::{MsgReader, MsgWriter};
+use mw_dataformat::read::{FrameKind, MwFileReader, MwFrameDataReader, MwFrameReader, MwReaderError, MwStreamDecoder, MwStreamItem};
 
 use crate::prelude::*;
 use crate::{CommonArgs, DisasmArgs};
 
 pub fn main(common: &CommonArgs, args: &DisasmArgs) -> AnyResult<()> {
+    // jump straight to the start time, if the file has an index
+    if let (Some(in_path), Some(_), false, false) = (&common.input, args.start_time, args.unframed, args.no_file_header) {
+        let done = if let Some(out_path) = &common.output {
+            let out_file = std::fs::OpenOptions::new()
+                .write(true)
+                .truncate(true)
+                .create(true)
+                .open(out_path)
+                .context("Cannot open output file!")?;
+            disasm_frames_indexed(in_path, BufWriter::new(out_file), args)?
+        } else {
+            disasm_frames_indexed(in_path, std::io::stdout(), args)?
+        };
+        if done {
+            return Ok(());
+        }
+    }
     match (&common.input, &common.output) {
+        (None, None) => {
+            let bufr = BufReader::new(std::io::stdin());
+            disasm(bufr, std::io::stdout(), args)?;
+        }
+        (None, Some(out_path)) => {
+            let out_file = std::fs::OpenOptions::new()
+                .write(true)
+                .truncate(true)
+                .create(true)
+                .open(out_path)
+                .context("Cannot open output file!")?;
+            let bufr = BufReader::new(std::io::stdin());
+            let bufw = BufWriter::new(out_file);
+            disasm(bufr, bufw, args)?;
+        }
         (Some(in_path), None) => {
             let in_file = std::fs::OpenOptions::new()
                 .read(true)
@@ -32,9 +68,6 @@ pub fn main(common: &CommonArgs, args: &DisasmArgs) -> AnyResult<()> {
             let bufw = BufWriter::new(out_file);
             disasm(bufr, bufw, args)?;
         }
-        (None, _) => {
-            bail!("Input filename must be specified!");
-        }
     }
     Ok(())
 }
@@ -55,8 +88,159 @@ fn disasm<R: BufRead, W: Write>(mut reader: R, mut writer: W, args: &DisasmArgs)
                 .context("Failed to encode ASM messages")?;
         }
     } else {
-        todo!()
+        disasm_frames(reader, writer, args)?;
+    }
+    Ok(())
+}
+
+/// Disassemble frames, decoding the input incrementally as it is read
+///
+/// Works with pipes and other non-seekable input.
+fn disasm_frames<R: BufRead, W: Write>(mut reader: R, mut writer: W, args: &DisasmArgs) -> AnyResult<()> {
+    let mut decoder = if args.no_file_header {
+        MwStreamDecoder::new()
+    } else {
+        MwStreamDecoder::new_file()
+    };
+    let mut chunk = vec![0; 4096];
+    let mut output = FrameDisasm::new(args);
+    'read: loop {
+        let len = reader.read(&mut chunk)
+            .context("Failed to read input")?;
+        if len == 0 {
+            if decoder.len_pending() != 0 {
+                bail!("Input ends in the middle of {}!", if decoder.is_in_frames() {
+                    "a frame"
+                } else {
+                    "the initialization sequence"
+                });
+            }
+            break;
+        }
+        decoder.push(&chunk[..len]);
+        while let Some(item) = decoder.decode_next().context("Failed to decode input")? {
+            let MwStreamItem::Frame(frame) = item else {
+                continue;
+            };
+            let more = output.frame(
+                &mut writer, frame.current_time_ms(), frame.frame_kind(),
+                frame.iter_streams().map(Ok),
+            )?;
+            if !more {
+                break 'read;
+            }
+        }
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+/// Disassemble frames from a file with a seek index, jumping to `start_time`
+///
+/// Returns `false` (without output) if the file has no index.
+fn disasm_frames_indexed<W: Write>(in_path: &Path, mut writer: W, args: &DisasmArgs) -> AnyResult<bool> {
+    let in_file = std::fs::OpenOptions::new()
+        .read(true)
+        .open(in_path)
+        .context("Cannot open input file!")?;
+    let mut buf = Vec::new();
+    let mut scratch = Vec::new();
+    let mut mfr = MwFileReader::new(BufReader::new(in_file), &mut buf)
+        .context("Failed to load input file as a MineWars format file!")?;
+    let Some(index) = mfr.read_frame_index().context("Failed to read frame index")? else {
+        return Ok(false);
+    };
+    match mfr.read_frames(Some(&mut scratch)).context("Failed to read frames")? {
+        MwFrameReader::Uncompressed(mut frames) => {
+            disasm_frames_seek(&mut frames, &index, &mut writer, args)?;
+        }
+        MwFrameReader::Compressed(mut frames) => {
+            disasm_frames_seek(&mut frames, &index, &mut writer, args)?;
+        }
+    }
+    writer.flush()?;
+    Ok(true)
+}
+
+fn disasm_frames_seek<R: Read + Seek, W: Write>(
+    frames: &mut MwFrameDataReader<'_, R>,
+    index: &MwFrameIndex,
+    writer: &mut W,
+    args: &DisasmArgs,
+) -> AnyResult<()> {
+    frames.seek_time(index, args.start_time.unwrap_or(0));
+    let mut output = FrameDisasm::new(args);
+    loop {
+        match frames.advance_next_frame() {
+            Ok(()) => {}
+            Err(MwReaderError::Io(e)) if e.kind() == ErrorKind::UnexpectedEof => break,
+            Err(e) => return Err(e).context("Cannot decode frames"),
+        }
+        let (time_ms, kind) = (frames.current_time_ms(), frames.frame_kind());
+        let more = output.frame(
+            writer, time_ms, kind,
+            frames.iter_streams().map(|r| r.context("Failed to read frame data")),
+        )?;
+        if !more {
+            break;
+        }
     }
     Ok(())
 }
 
+/// Selects which frames to output (as per the args) and disassembles them
+struct FrameDisasm<'a> {
+    args: &'a DisasmArgs,
+    n_frames: u64,
+    msgs: Vec<MwEv>,
+    r_bin: MsgBinRead,
+    w_asm: MsgAsmWrite,
+}
+
+impl<'a> FrameDisasm<'a> {
+    fn new(args: &'a DisasmArgs) -> Self {
+        Self {
+            args,
+            n_frames: 0,
+            msgs: vec![],
+            r_bin: MsgBinRead::new(),
+            w_asm: MsgAsmWrite::new(),
+        }
+    }
+    /// Returns `false` if no more frames should be output
+    fn frame<'s, W: Write>(
+        &mut self,
+        writer: &mut W,
+        time_ms: u64,
+        kind: FrameKind,
+        streams: impl Iterator<Item = AnyResult<&'s [u8]>>,
+    ) -> AnyResult<bool> {
+        if self.args.end_time.map(|end| time_ms > end).unwrap_or(false) {
+            return Ok(false);
+        }
+        if self.args.n_frames.map(|n| self.n_frames >= n).unwrap_or(false) {
+            return Ok(false);
+        }
+        if self.args.start_time.map(|start| time_ms < start).unwrap_or(false) {
+            return Ok(true);
+        }
+        if kind == FrameKind::Keepalive {
+            return Ok(true);
+        }
+        self.n_frames += 1;
+        writeln!(writer, "; FRAME {} {:?}", time_ms, kind)?;
+        for (plid, stream) in streams.enumerate() {
+            let mut stream = stream?;
+            if stream.is_empty() {
+                continue;
+            }
+            self.msgs.clear();
+            self.r_bin.read_all(&mut stream, &mut self.msgs)
+                .context("Failed to decode binary messages")?;
+            writeln!(writer, "; VIEW {}", plid)?;
+            self.w_asm.write_all(writer, &self.msgs)
+                .context("Failed to encode ASM messages")?;
+        }
+        Ok(true)
+    }
+}
@@ -0,0 +1,93 @@
+use std::fs::File;
+use std::io::{BufReader, Seek, SeekFrom, Write};
+
+use mw_dataformat::index::{MwFrameIndex, INDEX_MAGIC};
+use mw_dataformat::read::{MwFileReader, MwFrameReader};
+use mw_dataformat::trailer::replace_section;
+
+use crate::prelude::*;
+use crate::{CommonArgs, FrameIndexArgs};
+
+pub fn main(common: &CommonArgs, args: &FrameIndexArgs) -> AnyResult<()> {
+    if !args.remove && args.interval_ms == 0 {
+        bail!("Index interval must not be zero!");
+    }
+    replace_trailer_section(common, INDEX_MAGIC, |mfr, out| {
+        if args.remove {
+            return Ok(());
+        }
+        let mut scratch = Vec::new();
+        let index = match mfr.read_frames(Some(&mut scratch)).context("Failed to read frames")? {
+            MwFrameReader::Uncompressed(mut frames) => {
+                frames.build_index(args.interval_ms).context("Cannot decode frames")?
+            }
+            MwFrameReader::Compressed(mut frames) => {
+                frames.build_index(args.interval_ms).context("Cannot decode frames")?
+            }
+        };
+        print_summary(&index);
+        index.serialize(out);
+        Ok(())
+    })
+}
+
+/// Replace a trailing section of a file, keeping any others as they are
+///
+/// Modifies the input file in-place, or a copy of it, if an output file is given.
+/// `gen` should encode the new section into the provided buffer, or leave it
+/// empty, to remove the section.
+pub fn replace_trailer_section(
+    common: &CommonArgs,
+    magic: [u8; 4],
+    gen: impl FnOnce(MwFileReader<'_, BufReader<&mut File>>, &mut Vec<u8>) -> AnyResult<()>,
+) -> AnyResult<()> {
+    let Some(in_path) = &common.input else {
+        bail!("Input filename must be specified!");
+    };
+    let path = if let Some(out_path) = &common.output {
+        std::fs::copy(in_path, out_path)
+            .context("Failed to copy data from input to output file!")?;
+        out_path
+    } else {
+        in_path
+    };
+    let mut file = std::fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .truncate(false)
+        .create(false)
+        .open(path)
+        .context("Cannot open file!")?;
+
+    let mut buf = Vec::new();
+    let mut mfr = MwFileReader::new(BufReader::new(&mut file), &mut buf)
+        .context("Failed to load input file as a MineWars format file!")?;
+    let end = mfr.offset_framedata_end() as u64;
+    let mut old_trailer = mfr.read_trailer()
+        .context("Cannot read trailing sections")?
+        .to_owned();
+    // a bare index from an older file: convert it to a trailing section
+    if let Ok(index) = MwFrameIndex::deserialize_legacy(&old_trailer) {
+        old_trailer.clear();
+        index.serialize(&mut old_trailer);
+    }
+    let mut section = vec![];
+    gen(mfr, &mut section)?;
+
+    let mut trailer = vec![];
+    replace_section(&old_trailer, magic, &section, &mut trailer)
+        .context("Existing trailing sections are corrupted")?;
+    file.set_len(end)?;
+    file.seek(SeekFrom::Start(end))?;
+    file.write_all(&trailer)?;
+    Ok(())
+}
+
+fn print_summary(index: &MwFrameIndex) {
+    let entries = index.entries();
+    eprintln!("Index Entries: {}", entries.len());
+    if let (Some(first), Some(last)) = (entries.first(), entries.last()) {
+        eprintln!("Time Range: {} .. {} ms", first.time_ms, last.time_ms);
+    }
+    eprintln!("Index Size: {} bytes", index.serialized_len());
+}
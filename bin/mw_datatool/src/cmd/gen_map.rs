@@ -1,7 +1,11 @@
 use std::io::BufWriter;
 
-use mw_common::{game::{MapGenTileData, TileKind}, grid::*, phoneme::Ph};
+use mw_common::{game::{ItemKind, MapGenTileData}, grid::*};
+use mw_common::mapgen::{default_res_tile, gen_cit_names, gen_cits, gen_map, gen_regions, MapGenSettings};
+use mw_common::prelude::{rand, rand_pcg::Pcg64Mcg, SeedableRng};
+use mw_game_minesweeper::minegen::{gen_mines, MineGenSettings};
 
+use crate::cmd::map_ascii::tile_ascii;
 use crate::prelude::*;
 use crate::{CommonArgs, GenMapArgs};
 
@@ -16,22 +20,73 @@ pub fn main(common: &CommonArgs, args: &GenMapArgs) -> AnyResult<()> {
     } else {
         bail!("Output filename must be specified!");
     };
+
+    let mut settings = MapGenSettings::default();
+    if let Some(land_bias) = args.land_bias {
+        settings.land_bias = land_bias.try_into()
+            .ok().context("Land bias must be in range 0-255!")?;
+    }
+    let seed = args.seed.unwrap_or_else(rand::random);
+    eprintln!("Generating map with seed {}.", seed);
+    let mut rng = Pcg64Mcg::seed_from_u64(seed);
+
+    if args.sq {
+        gen_and_write::<Sq>(file, &settings, args, &mut rng)
+    } else {
+        gen_and_write::<Hex>(file, &settings, args, &mut rng)
+    }
+}
+
+fn gen_and_write<C: Coord>(
+    file: std::fs::File,
+    settings: &MapGenSettings,
+    args: &GenMapArgs,
+    rng: &mut Pcg64Mcg,
+) -> AnyResult<()> {
     let mut buf = Vec::new();
     let mut scratch = Vec::new();
     let mut tile = MapGenTileData::default();
-    tile.set_kind(TileKind::Regular);
     tile.set_region(0xFF);
-    let map: MapDataC<Hex, _> = MapData::new(args.size, tile);
+    let mut map: MapDataC<C, _> = MapData::new(args.size, tile);
+
+    gen_map(settings, &mut map, rng, |d, kind| d.set_kind(kind));
+
+    let cits = gen_cits(args.cits, &map, rng, |d| d.kind());
+    if cits.len() < args.cits as usize {
+        eprintln!("Map only has room for {} cities!", cits.len());
+    }
+    let names = gen_cit_names(cits.len(), rng);
+    gen_regions(&cits, &default_res_tile(), &mut map, |d| d.kind(), |d, region| d.set_region(region));
+
+    if let Some(mines) = args.mines {
+        let minegen = MineGenSettings {
+            mine_density: mines,
+            prob_decoy: args.decoys.unwrap_or(0),
+            ..Default::default()
+        };
+        gen_mines(&minegen, &mut map, rng, |d| d.kind(), |d, item| d.set_item(item));
+        for cit in cits.iter() {
+            map[*cit].set_item(ItemKind::Safe);
+        }
+    } else if args.decoys.is_some() {
+        bail!("Decoys can only be generated together with mines!");
+    }
+
+    let cits_pos: Vec<Pos> = cits.iter().map(|c| c.as_pos()).collect();
+    if args.ascii {
+        map.ascii_art(&mut std::io::stdout().lock(), |c, d| match d.item() {
+            ItemKind::Mine => b'*',
+            ItemKind::Decoy => b'?',
+            _ => tile_ascii(&cits_pos, c.into(), d.kind()),
+        })?;
+    }
 
     let bufwriter = BufWriter::new(file);
     let (b_file, b_is) = mw_dataformat::write::MwFileBuilder::new(bufwriter, &mut buf)?
         .start_is()?;
     let is = b_is
-        .with_map_lz4compressed(&map, true, &mut scratch)?
-        .with_cits([
-            (Pos(12, 17), [Ph::A, Ph::B, Ph::E, Ph::Z].as_slice()),
-            (Pos(7, 3), [Ph::I, Ph::D, Ph::A].as_slice()),
-        ])?
+        .with_map_lz4compressed(&map, args.mines.is_some(), &mut scratch)?
+        .with_cits(cits_pos.iter().copied().zip(names.iter().map(|n| n.as_slice())))?
         .finish()?;
     let b_file = b_file.with_is(is)?;
     b_file.finish()?;
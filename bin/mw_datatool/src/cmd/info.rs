@@ -1,5 +1,7 @@
 use mw_common::phoneme::{lang, render_str};
+use mw_dataformat::index::MwFrameIndex;
 use mw_dataformat::read::MwFileReader;
+use mw_dataformat::trailer::iter_sections;
 
 use crate::prelude::*;
 use crate::{CommonArgs, InfoArgs};
@@ -33,6 +35,16 @@ pub fn main(common: &CommonArgs, args: &InfoArgs) -> AnyResult<()> {
     eprintln!("FrameData is compressed?: {}", mfr.is_framedata_compressed());
     eprintln!("FrameData length (compressed): {}", mfr.file_header().len_framedata_compressed());
     eprintln!("FrameData length (raw):        {}", mfr.file_header().len_framedata_raw());
+    let trailer = mfr.read_trailer()
+        .context("Cannot read trailing sections")?;
+    if let Ok(index) = MwFrameIndex::deserialize_legacy(trailer) {
+        eprintln!("Legacy frame index entries: {}", index.entries().len());
+    } else {
+        for section in iter_sections(trailer) {
+            let section = section.context("Trailing section is corrupted")?;
+            eprintln!("Trailing section {:?} length: {}", String::from_utf8_lossy(&section.magic), section.data.len());
+        }
+    }
 
     let (_, mut isr) = mfr.read_is()
         .context("Cannot read IS")?;
@@ -48,6 +60,7 @@ pub fn main(common: &CommonArgs, args: &InfoArgs) -> AnyResult<()> {
     eprintln!("Maximum PlayerId: {}", isr.max_plid());
     eprintln!("Maximum PlayerSubId: {}", isr.max_sub_plid());
     eprintln!("RulesData length: {}", isr.header().len_rules());
+    eprintln!("PlayersData length: {}", isr.header().len_players());
 
     eprintln!();
     eprintln!("Cits:");
@@ -57,5 +70,19 @@ pub fn main(common: &CommonArgs, args: &InfoArgs) -> AnyResult<()> {
         eprintln!("{}: Y:{},X:{} {:?}", i, pos.y(), pos.x(), render_str::<lang::EN>(name));
     }
 
+    eprintln!();
+    eprintln!("Players:");
+    for (plid, info) in isr.read_players()?.iter().enumerate() {
+        if plid == 0 {
+            eprintln!("Spectator:");
+        } else {
+            let [r, g, b] = info.color;
+            eprintln!("{}: #{:02x}{:02x}{:02x}", plid, r, g, b);
+        }
+        for (subplid, sub) in info.subplids.iter().enumerate() {
+            eprintln!("  {}: {:?}", subplid, sub.display_name);
+        }
+    }
+
     Ok(())
 }
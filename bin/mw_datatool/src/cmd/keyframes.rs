@@ -0,0 +1,61 @@
+use mw_common::game::{MapGenTileData, ViewTileData};
+use mw_common::grid::*;
+use mw_dataformat::keyframe::{MwKeyframes, KEYFRAMES_MAGIC};
+use mw_dataformat::read::MwFrameReader;
+
+use crate::cmd::frame_index::replace_trailer_section;
+use crate::prelude::*;
+use crate::{CommonArgs, KeyframesArgs};
+
+pub fn main(common: &CommonArgs, args: &KeyframesArgs) -> AnyResult<()> {
+    if !args.remove && args.interval_ms == 0 {
+        bail!("Keyframe interval must not be zero!");
+    }
+    replace_trailer_section(common, KEYFRAMES_MAGIC, |mfr, out| {
+        if args.remove {
+            return Ok(());
+        }
+        let mut scratch = Vec::new();
+        let (mfr, mut isr) = mfr.read_is()
+            .context("Cannot read IS")?;
+        let map: MapDataPos<MapGenTileData> = match isr.map_topology() {
+            Topology::Hex => {
+                let map: MapDataC<Hex, MapGenTileData> =
+                    isr.read_map(Some(&mut scratch), true)?;
+                map.rekey()
+            }
+            Topology::Sq => {
+                let map: MapDataC<Sq, MapGenTileData> =
+                    isr.read_map(Some(&mut scratch), true)?;
+                map.rekey()
+            }
+        };
+        let initial = map.convert(|_, d| {
+            let mut t = ViewTileData::from_kind_item(d.kind(), d.item());
+            t.set_region(d.region());
+            t
+        });
+        let mfr = mfr.finish_is(isr)?;
+        let keyframes = match mfr.read_frames(Some(&mut scratch)).context("Failed to read frames")? {
+            MwFrameReader::Uncompressed(mut frames) => {
+                frames.build_keyframes(&initial, args.interval_ms).context("Cannot decode frames")?
+            }
+            MwFrameReader::Compressed(mut frames) => {
+                frames.build_keyframes(&initial, args.interval_ms).context("Cannot decode frames")?
+            }
+        };
+        let mut scratch = Vec::new();
+        keyframes.serialize(out, &mut scratch);
+        print_summary(&keyframes, out.len());
+        Ok(())
+    })
+}
+
+fn print_summary(keyframes: &MwKeyframes, len: usize) {
+    let keyframes = keyframes.keyframes();
+    eprintln!("Keyframes: {}", keyframes.len());
+    if let (Some(first), Some(last)) = (keyframes.first(), keyframes.last()) {
+        eprintln!("Time Range: {} .. {} ms", first.entry.time_ms, last.entry.time_ms);
+    }
+    eprintln!("Keyframes Size: {} bytes", len);
+}
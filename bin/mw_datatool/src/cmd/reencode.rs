@@ -103,8 +103,18 @@ fn reencode<R: Read + Seek, W: Write + Seek>(reader: R, writer: W, args: &Reenco
     let iter_cit_names = isr.read_cits_names()?;
     let b_is = b_is.with_cits(cit_pos.iter().cloned().zip(iter_cit_names))?;
 
-    // TODO: rules
-    let b_is = b_is.with_rules()?;
+    let b_is = if let Some(rules) = isr.read_rules()? {
+        b_is.with_rules(&rules)?
+    } else {
+        b_is.without_rules()?
+    };
+
+    let b_is = if isr.header().len_players() == 0 {
+        b_is.without_players()?
+    } else {
+        let players = isr.read_players()?;
+        b_is.with_players(&players)?
+    };
 
     let b_file = b_file.with_is(b_is.finish()?)?;
 
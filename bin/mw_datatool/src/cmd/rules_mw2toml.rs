@@ -0,0 +1,91 @@
+use std::fmt::Debug;
+use std::io::Write;
+
+use enum_map::{EnumArray, EnumMap};
+use mw_common::data::{MwDur, MwRatio};
+use mw_common::game::MwRules;
+use mw_dataformat::read::MwFileReader;
+use toml::{Table, Value};
+
+use crate::prelude::*;
+use crate::{CommonArgs, RulesMw2tomlArgs};
+
+pub fn main(common: &CommonArgs, _args: &RulesMw2tomlArgs) -> AnyResult<()> {
+    let file = if let Some(in_path) = &common.input {
+        std::fs::OpenOptions::new()
+            .read(true)
+            .open(in_path)
+            .context("Cannot open input file!")?
+    } else {
+        bail!("Input filename must be specified!");
+    };
+    let mut buf = Vec::new();
+
+    let mut mfr = MwFileReader::new(file, &mut buf)
+        .context("Failed to load input file as a MineWars format file!")?;
+
+    mfr.verify_checksum_isdata()
+        .context("Checksum verification failed!")?;
+
+    let (_, mut isr) = mfr.read_is()?;
+    let Some(rules) = isr.read_rules().context("Cannot decode rules!")? else {
+        bail!("File does not contain game rules!");
+    };
+
+    let toml = rules_to_toml(&rules).to_string();
+
+    if let Some(out_path) = &common.output {
+        std::fs::write(out_path, toml)
+            .context("Cannot write output file!")?;
+    } else {
+        std::io::stdout().lock().write_all(toml.as_bytes())?;
+    }
+
+    Ok(())
+}
+
+/// Convert the rules into a TOML table
+///
+/// Durations are represented in milliseconds and ratios as
+/// floating-point numbers. Per-kind tables are keyed by variant name.
+pub fn rules_to_toml(rules: &MwRules) -> Table {
+    let mut t = Table::new();
+    t.insert("res_base".into(), int(rules.res_base));
+    t.insert("res_tile".into(), table(&rules.res_tile, |x| int(*x)));
+    t.insert("res_tile_harvest".into(), table(&rules.res_tile_harvest, |x| int(*x)));
+    t.insert("cit_starting_money_spawn".into(), int(rules.cit_starting_money_spawn));
+    t.insert("cit_starting_money_other".into(), int(rules.cit_starting_money_other));
+    t.insert("mult_cost_foreign_region".into(), ratio(rules.mult_cost_foreign_region));
+    t.insert("mult_costsharing_local_contribution".into(), ratio(rules.mult_costsharing_local_contribution));
+    t.insert("vis_radius".into(), int(rules.vis_radius));
+    t.insert("vis_radius_watchtower".into(), int(rules.vis_radius_watchtower));
+    t.insert("structure_return_bulldoze".into(), table(&rules.structure_return_bulldoze, |x| int(*x)));
+    t.insert("structure_return_cancel".into(), table(&rules.structure_return_cancel, |x| int(*x)));
+    t.insert("structure_cost_initial".into(), table(&rules.structure_cost_initial, |x| int(*x)));
+    t.insert("structure_cost_construction".into(), table(&rules.structure_cost_construction, |x| int(*x)));
+    t.insert("action_cost".into(), table(&rules.action_cost, |x| int(*x)));
+    t.insert("action_cooldown".into(), table(&rules.action_cooldown, |x| dur(*x)));
+    t.insert("action_delay".into(), table(&rules.action_delay, |x| dur(*x)));
+    t.insert("item_cost".into(), table(&rules.item_cost, |x| int(*x)));
+    t.insert("item_return".into(), table(&rules.item_return, |x| int(*x)));
+    t.insert("cost_strike_tile".into(), int(rules.cost_strike_tile));
+    t.insert("dur_land_protect".into(), dur(rules.dur_land_protect));
+    t.insert("dur_capture_city".into(), dur(rules.dur_capture_city));
+    t
+}
+
+fn int(x: impl Into<i64>) -> Value {
+    Value::Integer(x.into())
+}
+
+fn dur(x: MwDur) -> Value {
+    int(x.as_millis())
+}
+
+fn ratio(x: MwRatio) -> Value {
+    Value::Float(x.as_f64())
+}
+
+fn table<K: EnumArray<V> + Debug, V>(map: &EnumMap<K, V>, f: impl Fn(&V) -> Value) -> Value {
+    Value::Table(map.iter().map(|(k, v)| (format!("{:?}", k), f(v))).collect())
+}
@@ -0,0 +1,302 @@
+use std::fmt::Debug;
+use std::io::{BufReader, BufWriter, Read};
+
+use enum_map::{EnumArray, EnumMap};
+use mw_common::data::{MwDur, MwRatio};
+use mw_common::game::MwRules;
+use toml::{Table, Value};
+
+use crate::cmd::strip::{rewrite, StripWhat};
+use crate::prelude::*;
+use crate::{CommonArgs, RulesToml2mwArgs};
+
+pub fn main(common: &CommonArgs, args: &RulesToml2mwArgs) -> AnyResult<()> {
+    let toml = if let Some(toml_path) = &args.toml {
+        std::fs::read_to_string(toml_path)
+            .context("Cannot read TOML file!")?
+    } else {
+        let mut s = String::new();
+        std::io::stdin().read_to_string(&mut s)
+            .context("Cannot read TOML from stdin!")?;
+        s
+    };
+    let table: Table = toml.parse()
+        .context("Cannot parse TOML!")?;
+    let rules = rules_from_toml(&table, args.lossy)?;
+
+    let what = StripWhat {
+        frames: false,
+        frames_after_ms: None,
+        map: false,
+        cits: false,
+        rules: false,
+        players: false,
+    };
+
+    match (&common.input, &common.output) {
+        (Some(in_path), None) => {
+            let file_in_mem = std::fs::read(in_path)
+                .context("Cannot read input file!")?;
+            let file = std::fs::OpenOptions::new()
+                .write(true)
+                .truncate(true)
+                .create(false)
+                .open(in_path)
+                .context("Cannot open file for writing!")?;
+            let bufw = BufWriter::new(file);
+            rewrite(std::io::Cursor::new(file_in_mem), bufw, &what, Some(&rules))?;
+        }
+        (Some(in_path), Some(out_path)) => {
+            let in_file = std::fs::OpenOptions::new()
+                .read(true)
+                .open(in_path)
+                .context("Cannot open input file!")?;
+            let out_file = std::fs::OpenOptions::new()
+                .write(true)
+                .truncate(true)
+                .create(true)
+                .open(out_path)
+                .context("Cannot open output file!")?;
+            let bufr = BufReader::new(in_file);
+            let bufw = BufWriter::new(out_file);
+            rewrite(bufr, bufw, &what, Some(&rules))?;
+        }
+        (None, _) => {
+            bail!("Input filename must be specified!");
+        }
+    }
+
+    Ok(())
+}
+
+/// Parse rules from a TOML table, as produced by `rules-mw2toml`
+///
+/// All fields must be present and no unknown fields are allowed.
+/// If `lossy` is false, values that cannot be represented exactly
+/// in the binary encoding are an error; otherwise they are approximated.
+pub fn rules_from_toml(t: &Table, lossy: bool) -> AnyResult<MwRules> {
+    let p = Parser { lossy };
+    let rules = MwRules {
+        res_base: p.int(t, "res_base")?,
+        res_tile: p.table(t, "res_tile", Parser::int)?,
+        res_tile_harvest: p.table(t, "res_tile_harvest", Parser::int)?,
+        cit_starting_money_spawn: p.int(t, "cit_starting_money_spawn")?,
+        cit_starting_money_other: p.int(t, "cit_starting_money_other")?,
+        mult_cost_foreign_region: p.ratio(t, "mult_cost_foreign_region")?,
+        mult_costsharing_local_contribution: p.ratio(t, "mult_costsharing_local_contribution")?,
+        vis_radius: p.int(t, "vis_radius")?,
+        vis_radius_watchtower: p.int(t, "vis_radius_watchtower")?,
+        structure_return_bulldoze: p.table(t, "structure_return_bulldoze", Parser::int)?,
+        structure_return_cancel: p.table(t, "structure_return_cancel", Parser::int)?,
+        structure_cost_initial: p.table(t, "structure_cost_initial", Parser::int)?,
+        structure_cost_construction: p.table(t, "structure_cost_construction", Parser::int)?,
+        action_cost: p.table(t, "action_cost", Parser::int)?,
+        action_cooldown: p.table(t, "action_cooldown", Parser::dur)?,
+        action_delay: p.table(t, "action_delay", Parser::dur)?,
+        item_cost: p.table(t, "item_cost", Parser::int)?,
+        item_return: p.table(t, "item_return", Parser::int)?,
+        cost_strike_tile: p.int(t, "cost_strike_tile")?,
+        dur_land_protect: p.dur(t, "dur_land_protect")?,
+        dur_capture_city: p.dur(t, "dur_capture_city")?,
+    };
+    if let Some(k) = t.keys().find(|k| !KNOWN_KEYS.contains(&k.as_str())) {
+        bail!("Unknown rules field {:?}!", k);
+    }
+    Ok(rules)
+}
+
+const KNOWN_KEYS: &[&str] = &[
+    "res_base",
+    "res_tile",
+    "res_tile_harvest",
+    "cit_starting_money_spawn",
+    "cit_starting_money_other",
+    "mult_cost_foreign_region",
+    "mult_costsharing_local_contribution",
+    "vis_radius",
+    "vis_radius_watchtower",
+    "structure_return_bulldoze",
+    "structure_return_cancel",
+    "structure_cost_initial",
+    "structure_cost_construction",
+    "action_cost",
+    "action_cooldown",
+    "action_delay",
+    "item_cost",
+    "item_return",
+    "cost_strike_tile",
+    "dur_land_protect",
+    "dur_capture_city",
+];
+
+struct Parser {
+    lossy: bool,
+}
+
+impl Parser {
+    fn get<'t>(&self, t: &'t Table, key: &str) -> AnyResult<&'t Value> {
+        t.get(key).with_context(|| format!("Missing rules field {:?}!", key))
+    }
+
+    fn int<T: TryFrom<i64>>(&self, t: &Table, key: &str) -> AnyResult<T> {
+        let Some(x) = self.get(t, key)?.as_integer() else {
+            bail!("Rules field {:?} must be an integer!", key);
+        };
+        T::try_from(x)
+            .map_err(|_| anyhow::anyhow!("Rules field {:?} value {} is out of range!", key, x))
+    }
+
+    /// Durations are in milliseconds
+    fn dur(&self, t: &Table, key: &str) -> AnyResult<MwDur> {
+        let ms: u16 = self.int(t, key)?;
+        let dur = MwDur::from_millis_lossy(ms);
+        if dur.as_millis() != ms {
+            if !self.lossy {
+                bail!("Rules field {:?} duration {}ms cannot be represented exactly (closest: {}ms)!", key, ms, dur.as_millis());
+            }
+            eprintln!("Rules field {:?}: approximating duration {}ms as {}ms.", key, ms, dur.as_millis());
+        }
+        Ok(dur)
+    }
+
+    /// Ratios are floating-point numbers (integers are also accepted)
+    fn ratio(&self, t: &Table, key: &str) -> AnyResult<MwRatio> {
+        let x = match self.get(t, key)? {
+            Value::Float(x) => *x,
+            Value::Integer(x) => *x as f64,
+            _ => bail!("Rules field {:?} must be a number!", key),
+        };
+        if !(0.0..=15.0).contains(&x) {
+            bail!("Rules field {:?} ratio {} is out of range (0.0-15.0)!", key, x);
+        }
+        let ratio = (0..=0xFF).map(MwRatio)
+            .min_by(|a, b| (a.as_f64() - x).abs().total_cmp(&(b.as_f64() - x).abs()))
+            .unwrap();
+        if (ratio.as_f64() - x).abs() > 1e-9 {
+            if !self.lossy {
+                bail!("Rules field {:?} ratio {} cannot be represented exactly (closest: {}/{})!", key, x, ratio.num(), ratio.denum());
+            }
+            eprintln!("Rules field {:?}: approximating ratio {} as {}/{}.", key, x, ratio.num(), ratio.denum());
+        }
+        Ok(ratio)
+    }
+
+    /// Per-kind tables are keyed by variant name
+    fn table<K: EnumArray<V> + EnumArray<Option<V>> + Debug, V>(
+        &self,
+        t: &Table,
+        key: &str,
+        f: impl Fn(&Self, &Table, &str) -> AnyResult<V>,
+    ) -> AnyResult<EnumMap<K, V>> {
+        let Some(inner) = self.get(t, key)?.as_table() else {
+            bail!("Rules field {:?} must be a table!", key);
+        };
+        let mut r = Ok(());
+        let mut n_found = 0;
+        let map = EnumMap::from_fn(|k: K| {
+            let name = format!("{:?}", k);
+            if r.is_err() {
+                return None;
+            }
+            n_found += 1;
+            match f(self, inner, &name).with_context(|| format!("In rules table {:?}:", key)) {
+                Ok(x) => Some(x),
+                Err(e) => {
+                    r = Err(e);
+                    None
+                }
+            }
+        });
+        r?;
+        if inner.len() != n_found {
+            bail!("Rules table {:?} contains unknown entries!", key);
+        }
+        Ok(map.map(|_, x| x.unwrap()))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use enum_map::{enum_map, Enum};
+
+    use crate::cmd::rules_mw2toml::rules_to_toml;
+
+    use super::*;
+
+    fn test_rules() -> MwRules {
+        MwRules {
+            res_base: 3,
+            res_tile: EnumMap::from_fn(|k| Enum::into_usize(k) as u8),
+            res_tile_harvest: EnumMap::from_fn(|k| 1000 + Enum::into_usize(k) as u16),
+            cit_starting_money_spawn: 5000,
+            cit_starting_money_other: 2500,
+            mult_cost_foreign_region: MwRatio::new_lossy(3, 2),
+            mult_costsharing_local_contribution: MwRatio::new_lossy(1, 4),
+            vis_radius: 5,
+            vis_radius_watchtower: 9,
+            structure_return_bulldoze: EnumMap::from_fn(|k| 10 * Enum::into_usize(k) as u16),
+            structure_return_cancel: EnumMap::from_fn(|k| 20 * Enum::into_usize(k) as u16),
+            structure_cost_initial: EnumMap::from_fn(|k| 300 + Enum::into_usize(k) as u16),
+            structure_cost_construction: EnumMap::from_fn(|k| 400 + Enum::into_usize(k) as u16),
+            action_cost: EnumMap::from_fn(|k| 0x1234 + Enum::into_usize(k) as u16),
+            action_cooldown: EnumMap::from_fn(|k| MwDur::from_millis_lossy(100 * Enum::into_usize(k) as u16)),
+            action_delay: EnumMap::from_fn(|k| MwDur(Enum::into_usize(k) as u8)),
+            item_cost: enum_map! { _ => 50 },
+            item_return: enum_map! { _ => 25 },
+            cost_strike_tile: 75,
+            dur_land_protect: MwDur::from_millis_lossy(5000),
+            dur_capture_city: MwDur::from_millis_lossy(2000),
+        }
+    }
+
+    /// Go through the TOML text, like `rules-mw2toml | rules-toml2mw` would
+    fn reparse(t: &Table) -> Table {
+        t.to_string().parse().unwrap()
+    }
+
+    #[test]
+    fn rules_toml_roundtrip() {
+        let rules = test_rules();
+        let t = reparse(&rules_to_toml(&rules));
+        assert_eq!(rules_from_toml(&t, false).unwrap(), rules);
+        assert_eq!(rules_from_toml(&t, true).unwrap(), rules);
+    }
+
+    #[test]
+    fn rules_toml_lossy_dur() {
+        let rules = test_rules();
+        let mut t = reparse(&rules_to_toml(&rules));
+        t.insert("dur_land_protect".into(), Value::Integer(1234));
+        assert!(rules_from_toml(&t, false).is_err());
+        let lossy = rules_from_toml(&t, true).unwrap();
+        assert_eq!(lossy.dur_land_protect.as_millis(), 1200);
+        assert_eq!(lossy, MwRules {
+            dur_land_protect: MwDur::from_millis_lossy(1234),
+            ..rules
+        });
+    }
+
+    #[test]
+    fn rules_toml_lossy_ratio() {
+        let rules = test_rules();
+        let mut t = reparse(&rules_to_toml(&rules));
+        t.insert("mult_cost_foreign_region".into(), Value::Float(0.31));
+        assert!(rules_from_toml(&t, false).is_err());
+        let lossy = rules_from_toml(&t, true).unwrap();
+        assert!((lossy.mult_cost_foreign_region.as_f64() - 0.31).abs() < 0.005);
+        // the approximated rules must survive another round trip exactly
+        let t = reparse(&rules_to_toml(&lossy));
+        assert_eq!(rules_from_toml(&t, false).unwrap(), lossy);
+    }
+
+    #[test]
+    fn rules_toml_unknown_or_missing() {
+        let rules = test_rules();
+        let mut t = reparse(&rules_to_toml(&rules));
+        t.insert("not_a_rule".into(), Value::Integer(1));
+        assert!(rules_from_toml(&t, true).is_err());
+        let mut t = reparse(&rules_to_toml(&rules));
+        t.remove("vis_radius");
+        assert!(rules_from_toml(&t, true).is_err());
+    }
+}
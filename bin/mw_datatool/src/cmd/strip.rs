@@ -0,0 +1,222 @@
+use std::io::{BufReader, BufWriter, ErrorKind, Read, Seek, Write};
+
+use mw_common::game::{MapGenTileData, MwRules};
+use mw_common::grid::*;
+use mw_dataformat::read::{MwFileReader, MwFrameDataReader, MwFrameReader, MwReaderError};
+use mw_dataformat::write::{MwFileBuilder, MwFrameBuilder, MwISBuilder, MwISBuilderWithMap};
+
+use crate::prelude::*;
+use crate::{CommonArgs, StripArgs};
+
+/// Which sections to remove, after resolving all the shorthand options
+pub struct StripWhat {
+    pub frames: bool,
+    pub frames_after_ms: Option<u64>,
+    pub map: bool,
+    pub cits: bool,
+    pub rules: bool,
+    pub players: bool,
+}
+
+impl StripWhat {
+    fn from_args(args: &StripArgs) -> Self {
+        let map = args.map || args.rulesonly;
+        let cits = args.cits || args.maponly || args.rulesonly;
+        let rules = args.rules || args.mapcitonly || args.maponly;
+        let players = args.players || args.mapcitonly || args.maponly || args.rulesonly;
+        let frames = args.frames || map || cits
+            || args.mapcitonly || args.maponly || args.rulesonly;
+        Self {
+            frames,
+            frames_after_ms: args.frames_after_ms,
+            map,
+            cits,
+            rules,
+            players,
+        }
+    }
+}
+
+pub fn main(common: &CommonArgs, args: &StripArgs) -> AnyResult<()> {
+    match (&common.input, &common.output) {
+        (Some(in_path), None) => {
+            let file_in_mem = std::fs::read(in_path)
+                .context("Cannot read input file!")?;
+            let file = std::fs::OpenOptions::new()
+                .write(true)
+                .truncate(true)
+                .create(false)
+                .open(in_path)
+                .context("Cannot open file for writing!")?;
+            let bufw = BufWriter::new(file);
+            strip(std::io::Cursor::new(file_in_mem), bufw, args)?;
+        }
+        (Some(in_path), Some(out_path)) => {
+            let in_file = std::fs::OpenOptions::new()
+                .read(true)
+                .open(in_path)
+                .context("Cannot open input file!")?;
+            let out_file = std::fs::OpenOptions::new()
+                .write(true)
+                .truncate(true)
+                .create(true)
+                .open(out_path)
+                .context("Cannot open output file!")?;
+            let bufr = BufReader::new(in_file);
+            let bufw = BufWriter::new(out_file);
+            strip(bufr, bufw, args)?;
+        }
+        (None, _) => {
+            bail!("Input filename must be specified!");
+        }
+    }
+
+    Ok(())
+}
+
+fn strip<R: Read + Seek, W: Write + Seek>(reader: R, writer: W, args: &StripArgs) -> AnyResult<()> {
+    rewrite(reader, writer, &StripWhat::from_args(args), None)
+}
+
+/// Copy a MineWars file, leaving out the parts specified in `what`
+///
+/// If `new_rules` is provided, it replaces the file's rules (unless stripped).
+pub fn rewrite<R: Read + Seek, W: Write + Seek>(
+    reader: R,
+    writer: W,
+    what: &StripWhat,
+    new_rules: Option<&MwRules>,
+) -> AnyResult<()> {
+    let mut buf_r = Vec::new();
+    let mut buf_w = Vec::new();
+    let mut scratch_r = Vec::new();
+    let mut scratch_w = Vec::new();
+
+    let mut mfr = MwFileReader::new(reader, &mut buf_r)
+        .context("Failed to load input file as a MineWars format file!")?;
+
+    mfr.verify_checksums()
+        .context("Checksum verification failed!")?;
+
+    let (b_file, b_is) = MwFileBuilder::new(writer, &mut buf_w)?
+        .start_is()?;
+
+    let (mfr, mut isr) = mfr.read_is()?;
+
+    let b_is = b_is.with_max_plid(isr.max_plid(), isr.max_sub_plid());
+
+    let b_is = if what.map {
+        b_is.without_map()
+    } else {
+        let compress = isr.is_mapdata_compressed();
+        match isr.map_topology() {
+            Topology::Hex => {
+                let mut map: MapDataC<Hex, MapGenTileData> =
+                    isr.read_map(Some(&mut scratch_r), true)?;
+                write_map(b_is, &mut map, what.cits, compress, &mut scratch_w)?
+            }
+            Topology::Sq => {
+                let mut map: MapDataC<Sq, MapGenTileData> =
+                    isr.read_map(Some(&mut scratch_r), true)?;
+                write_map(b_is, &mut map, what.cits, compress, &mut scratch_w)?
+            }
+        }
+    };
+
+    let b_is = if what.map || what.cits {
+        b_is.with_cits([])?
+    } else {
+        let cit_pos = isr.read_cits_pos()?.to_owned();
+        let iter_cit_names = isr.read_cits_names()?;
+        b_is.with_cits(cit_pos.iter().cloned().zip(iter_cit_names))?
+    };
+
+    let b_is = if what.rules {
+        b_is.without_rules()?
+    } else if let Some(rules) = new_rules {
+        b_is.with_rules(rules)?
+    } else {
+        let rules = isr.read_rules_raw()?.to_owned();
+        b_is.with_rules_raw(&rules)?
+    };
+
+    let b_is = if what.players {
+        b_is.without_players()?
+    } else {
+        let players = isr.read_players_raw()?.to_owned();
+        b_is.with_players_raw(&players)?
+    };
+
+    let is = b_is.finish()?;
+    let mfr = mfr.finish_is(isr)?;
+
+    if what.frames {
+        b_file.with_is(is)?.finish()?;
+        return Ok(());
+    }
+
+    let mut raw = Vec::new();
+    if mfr.is_framedata_compressed() {
+        let b_file = b_file.with_is_and_frame_compression(is, &mut scratch_w)?;
+        let (b_file, mut b_frames) = b_file.start_frames()?;
+        let MwFrameReader::Compressed(mut frames) = mfr.read_frames(Some(&mut scratch_r))? else {
+            bail!("Frames should be compressed!");
+        };
+        copy_frames(&mut frames, &mut b_frames, what.frames_after_ms, &mut raw)?;
+        b_file.with_frames(b_frames.finish()?)?.finish()?;
+    } else {
+        let (b_file, mut b_frames) = b_file.with_is(is)?.start_frames()?;
+        let MwFrameReader::Uncompressed(mut frames) = mfr.read_frames(None)? else {
+            bail!("Frames should not be compressed!");
+        };
+        copy_frames(&mut frames, &mut b_frames, what.frames_after_ms, &mut raw)?;
+        b_file.with_frames(b_frames.finish()?)?.finish()?;
+    }
+
+    Ok(())
+}
+
+fn write_map<'b, W: Write + Seek, C: Coord>(
+    b_is: MwISBuilder<'b, W>,
+    map: &mut MapDataC<C, MapGenTileData>,
+    strip_cits: bool,
+    compress: bool,
+    scratch: &mut Vec<u8>,
+) -> AnyResult<MwISBuilderWithMap<'b, W>> {
+    if strip_cits {
+        for (_, tile) in map.iter_mut() {
+            tile.set_region(0xFF);
+        }
+    }
+    Ok(if compress {
+        b_is.with_map_lz4compressed(map, true, scratch)?
+    } else {
+        b_is.with_map_uncompressed(map, true)?
+    })
+}
+
+/// Copy frames as-is, stopping at the first frame after `until_ms`
+///
+/// Frames are only ever dropped whole, from the end, so the remaining
+/// frame stream stays valid.
+fn copy_frames<R: Read + Seek, W: Write + Seek>(
+    frames: &mut MwFrameDataReader<R>,
+    b_frames: &mut MwFrameBuilder<W>,
+    until_ms: Option<u64>,
+    raw: &mut Vec<u8>,
+) -> AnyResult<()> {
+    loop {
+        match frames.advance_next_frame() {
+            Ok(()) => {}
+            Err(MwReaderError::Io(e)) if e.kind() == ErrorKind::UnexpectedEof => break,
+            Err(e) => return Err(e).context("Cannot decode frames"),
+        }
+        if until_ms.map(|t| frames.current_time_ms() > t).unwrap_or(false) {
+            break;
+        }
+        raw.clear();
+        frames.read_raw_frame(raw)?;
+        b_frames.append_raw_data(raw)?;
+    }
+    Ok(())
+}
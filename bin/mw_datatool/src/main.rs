@@ -12,8 +12,14 @@ mod cmd {
     pub mod info;
     pub mod gen_map;
     pub mod map_ascii;
+    pub mod analyze;
+    pub mod strip;
+    pub mod rules_mw2toml;
+    pub mod rules_toml2mw;
     pub mod checksum_verify;
     pub mod checksum_fix;
+    pub mod frame_index;
+    pub mod keyframes;
     pub mod reencode;
     pub mod disasm;
     pub mod asm;
@@ -58,6 +64,10 @@ enum CliCommand {
     ChecksumVerify(ChecksumVerifyArgs),
     /// Recompute a MineWars file's checksums
     ChecksumFix(ChecksumFixArgs),
+    /// Add a seek index to a MineWars file's frame data (or replace/remove it)
+    FrameIndex(FrameIndexArgs),
+    /// Add snapshots of every plid's view of the map to a MineWars file's frame data (or replace/remove them)
+    Keyframes(KeyframesArgs),
     /// Re-encode the file, possibly with different compression
     Reencode(ReencodeArgs),
     /// Disassemble frame data
@@ -85,6 +95,9 @@ struct GenMapArgs {
     #[arg(short, long)]
     #[arg(value_parser = clap::value_parser!(u8).range(1..=125))]
     size: u8,
+    /// Generate a map with square tiles instead of hexagons
+    #[arg(long)]
+    sq: bool,
     /// Generate this many cities/regions
     #[arg(short, long)]
     cits: u8,
@@ -105,6 +118,9 @@ struct MapAsciiArgs {
 
 #[derive(Parser, Debug)]
 struct AnalyzeArgs {
+    /// Do not verify the checksums of the input file
+    #[arg(long)]
+    ignore_checksums: bool,
 }
 
 #[derive(Parser, Debug)]
@@ -114,7 +130,7 @@ struct StripArgs {
     frames: bool,
     /// Strip frames after the given timestamp (milliseconds)
     #[arg(long)]
-    frames_after_ms: u64,
+    frames_after_ms: Option<u64>,
     /// Strip all map data; also enables `frames`
     #[arg(short, long)]
     map: bool,
@@ -124,16 +140,19 @@ struct StripArgs {
     /// Strip game rules
     #[arg(short, long)]
     rules: bool,
+    /// Strip players info (names, colors)
+    #[arg(short, long)]
+    players: bool,
     /// Strip everything except the map data and cities/regions
-    /// (enables: frames, rules)
+    /// (enables: frames, rules, players)
     #[arg(long)]
     mapcitonly: bool,
     /// Strip everything except the map data
-    /// (enables: frames, rules, cits)
+    /// (enables: frames, rules, players, cits)
     #[arg(long)]
     maponly: bool,
     /// Strip everything except the game rules
-    /// (enables: frames, map, cits)
+    /// (enables: frames, map, cits, players)
     #[arg(long)]
     rulesonly: bool,
 }
@@ -144,6 +163,9 @@ struct RulesMw2tomlArgs {
 
 #[derive(Parser, Debug)]
 struct RulesToml2mwArgs {
+    /// TOML file to read the rules from (default is stdin)
+    #[arg(short, long)]
+    toml: Option<PathBuf>,
     /// Do not error out if some values cannot be represented exactly, just approximate them
     #[arg(short, long)]
     lossy: bool,
@@ -157,6 +179,26 @@ struct ChecksumVerifyArgs {
 struct ChecksumFixArgs {
 }
 
+#[derive(Parser, Debug)]
+struct FrameIndexArgs {
+    /// Add an index entry after at least this many milliseconds since the previous one
+    #[arg(long, default_value_t = 1000)]
+    interval_ms: u64,
+    /// Remove the index instead of (re)generating it
+    #[arg(long)]
+    remove: bool,
+}
+
+#[derive(Parser, Debug)]
+struct KeyframesArgs {
+    /// Add a keyframe after at least this many milliseconds since the previous one
+    #[arg(long, default_value_t = 10000)]
+    interval_ms: u64,
+    /// Remove the keyframes instead of (re)generating them
+    #[arg(long)]
+    remove: bool,
+}
+
 #[derive(Parser, Debug)]
 struct ReencodeArgs {
     /// Do not verify the checksums of the input file
@@ -181,6 +223,9 @@ struct DisasmArgs {
     /// Unframed Format: no frame headers, only messages
     #[arg(short, long)]
     unframed: bool,
+    /// Input is a network stream (IS followed by frames), without a file header
+    #[arg(long)]
+    no_file_header: bool,
     /// Timestamp (milliseconds) to start from (default is the very beginning)
     #[arg(short, long)]
     start_time: Option<u64>,
@@ -214,12 +259,14 @@ impl Cli {
             CliCommand::Info(args) => crate::cmd::info::main(&self.common, &args),
             CliCommand::GenMap(args) => crate::cmd::gen_map::main(&self.common, &args),
             CliCommand::MapAscii(args) => crate::cmd::map_ascii::main(&self.common, &args),
-            CliCommand::Analyze(args) => todo!(),
-            CliCommand::Strip(args) => todo!(),
-            CliCommand::RulesMw2toml(args) => todo!(),
-            CliCommand::RulesToml2mw(args) => todo!(),
+            CliCommand::Analyze(args) => crate::cmd::analyze::main(&self.common, &args),
+            CliCommand::Strip(args) => crate::cmd::strip::main(&self.common, &args),
+            CliCommand::RulesMw2toml(args) => crate::cmd::rules_mw2toml::main(&self.common, &args),
+            CliCommand::RulesToml2mw(args) => crate::cmd::rules_toml2mw::main(&self.common, &args),
             CliCommand::ChecksumVerify(args) => crate::cmd::checksum_verify::main(&self.common, &args),
             CliCommand::ChecksumFix(args) => crate::cmd::checksum_fix::main(&self.common, &args),
+            CliCommand::FrameIndex(args) => crate::cmd::frame_index::main(&self.common, &args),
+            CliCommand::Keyframes(args) => crate::cmd::keyframes::main(&self.common, &args),
             CliCommand::Reencode(args) => crate::cmd::reencode::main(&self.common, &args),
             CliCommand::Disasm(args) => crate::cmd::disasm::main(&self.common, &args),
             CliCommand::Asm(args) => crate::cmd::asm::main(&self.common, &args),
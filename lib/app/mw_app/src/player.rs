@@ -1,6 +1,7 @@
 use mw_app_core::map::cit::CitOwner;
 use mw_app_core::map::tile::MwMapTile;
 use mw_app_core::map::tile::TileOwner;
+use mw_app_core::driver::GameOutEventSS;
 use mw_app_core::player::*;
 use mw_app_core::session::*;
 
@@ -14,9 +15,31 @@ pub fn plugin(app: &mut App) {
         plid_score_by_owned_pct
             .in_set(InStateSet(AppState::InGame))
             .run_if(any_filter::<(With<PlidScoreByOwnedPct>, With<SessionGovernor>)>),
+        plid_standing_from_gameevents
+            .in_set(NeedsSessionGovernorSet)
+            .in_set(SetStage::WantChanged(GameOutEventSS)),
     ));
 }
 
+fn plid_standing_from_gameevents(
+    mut commands: Commands,
+    mut evr_game: EventReader<GameEvent>,
+    q_session: Query<&PlayersIndex, With<SessionGovernor>>,
+) {
+    let players = q_session.single();
+    for ev in evr_game.read() {
+        let MwEv::Player { plid, subplid: _, ev: PlayerEv::Standing { rank, tiles, lives, elim_order, elim_secs } } = ev.ev else {
+            continue;
+        };
+        let Some(e_plid) = players.e_plid.get(plid.i()) else {
+            continue;
+        };
+        commands.entity(*e_plid).insert(PlidStanding {
+            rank, tiles, lives, elim_order, elim_secs,
+        });
+    }
+}
+
 fn plid_score_by_cits(
     mut q_plid: Query<&mut PlidScore, With<Plid>>,
     q_cit: Query<&CitOwner>,
@@ -59,11 +59,21 @@ pub struct KeyboardMouseMappings {
 impl Default for KeyboardMouseMappings {
     fn default() -> Self {
         let key_actions = hash_map! {
+            vec![KeyCode::Space] => ACTION_CHORD.into(),
+            vec![KeyCode::Tab] => ACTION_NEXT_PLID.into(),
         };
         let mouse_actions = hash_map! {
             vec![] => hash_map! {
+                vec![MouseButton::Left] => ACTION_EXPLORE.into(),
+                vec![MouseButton::Right] => ACTION_FLAG.into(),
                 vec![MouseButton::Middle] => mw_app_core::camera::input::ACTION_CENTER.into(),
             },
+            vec![KeyCode::ControlLeft] => hash_map! {
+                vec![MouseButton::Left] => ACTION_CHORD.into(),
+            },
+            vec![KeyCode::ControlRight] => hash_map! {
+                vec![MouseButton::Left] => ACTION_CHORD.into(),
+            },
         };
         let mouse_motion = hash_map! {
             vec![] => hash_map! {
@@ -1,9 +1,27 @@
 use bevy::ecs::system::SystemState;
-use mw_app_core::{driver::GameOutEventSS, map::{tile::*, MapDescriptor, MapGovernor, MapTileIndex}, session::{PlayersIndex, PlidViewing, SessionGovernor}, view::*};
+use mw_app_core::{driver::GameOutEventSS, input::*, map::{tile::*, MapDataOrig, MapDescriptor, MapGovernor, MapTileIndex}, player::{Plid, PlidPlayable, PlidState}, session::{PlayersIndex, PlidPlayingAs, PlidViewing, SessionGovernor}, view::*};
 
 use crate::prelude::*;
 
 pub fn plugin(app: &mut App) {
+    app.register_clicommand_args("view_plid", cli_view_plid);
+    app.register_clicommand_noargs("view_next_plid", cli_view_next_plid);
+    app.add_systems(Startup, (
+        setup_inputs
+            .in_set(SetStage::Provide(GameInputSS::Setup)),
+    ));
+    app.add_systems(
+        OnEnter(AppState::InGame),
+        (
+            setup_playable_views,
+            enable_inputs,
+        )
+            .run_if(rc_multiple_playable)
+    );
+    app.add_systems(
+        InputActionOnPress(ACTION_NEXT_PLID.into()),
+        input_action_next_plid,
+    );
     app.add_systems(Update, (
         view_update_from_gameevents,
     )
@@ -30,6 +48,117 @@ fn rc_viewswitch(
         .unwrap_or(false)
 }
 
+/// Marker for the input actions used to switch between playable plids
+#[derive(Component)]
+struct PlayableSwitchInput;
+
+fn setup_inputs(mut commands: Commands) {
+    commands.spawn((
+        PlayableSwitchInput,
+        InputActionBundle::from(ACTION_NEXT_PLID),
+    ));
+}
+
+fn rc_multiple_playable(
+    q_plid: Query<(), With<PlidPlayable>>,
+) -> bool {
+    q_plid.iter().count() > 1
+}
+
+fn enable_inputs(
+    mut commands: Commands,
+    q_actions: Query<Entity, (With<PlayableSwitchInput>, With<InputAction>)>,
+) {
+    for e in &q_actions {
+        commands.entity(e).insert(InputActionEnabled);
+    }
+}
+
+/// Create a fresh view for every plid we can play as,
+/// so that we can switch between them
+fn setup_playable_views(
+    mut commands: Commands,
+    q_plid: Query<(Entity, &Plid), (With<PlidPlayable>, Without<ViewMapData>)>,
+    q_map: Query<&MapDataOrig, With<MapGovernor>>,
+) {
+    let Ok(orig) = q_map.get_single() else {
+        return;
+    };
+    for (e_plid, plid) in &q_plid {
+        commands.entity(e_plid).insert(ViewBundle {
+            mapdata: ViewMapData::from_map_orig(orig, plid.0),
+        });
+    }
+}
+
+/// Play as (and view) the next plid that is still in the game
+fn input_action_next_plid(
+    mut q_session: Query<(&PlayersIndex, &mut PlidPlayingAs, &mut PlidViewing), With<SessionGovernor>>,
+    q_plid: Query<&PlidState, (With<PlidPlayable>, With<ViewMapData>)>,
+) {
+    let Ok((players, mut playing_as, mut viewing)) = q_session.get_single_mut() else {
+        return;
+    };
+    let n = players.e_plid.len();
+    let next = (1..n)
+        .map(|off| (playing_as.0.i() + off) % n)
+        .find(|i| q_plid.get(players.e_plid[*i])
+            .map(|state| !matches!(state, PlidState::Eliminated))
+            .unwrap_or(false)
+        );
+    if let Some(i) = next {
+        let plid = PlayerId::from(i as u8);
+        info!("Now playing as {:?}.", plid);
+        playing_as.0 = plid;
+        viewing.0 = plid;
+    }
+}
+
+fn cli_view_plid(
+    In(args): In<Vec<String>>,
+    mut q_session: Query<(&PlayersIndex, &mut PlidViewing), With<SessionGovernor>>,
+    q_view: Query<(), With<ViewMapData>>,
+) {
+    let Ok((players, mut viewing)) = q_session.get_single_mut() else {
+        error!("Cannot switch view: no session.");
+        return;
+    };
+    let Some(Ok(i)) = args.first().map(|s| s.parse::<u8>()) else {
+        error!("Please specify the plid to view!");
+        return;
+    };
+    let has_view = players.e_plid.get(i as usize)
+        .map(|e| q_view.contains(*e))
+        .unwrap_or(false);
+    if !has_view {
+        error!("Cannot switch view: no view for plid {}.", i);
+        return;
+    }
+    viewing.0 = PlayerId::from(i);
+}
+
+fn cli_view_next_plid(
+    mut q_session: Query<(&PlayersIndex, &mut PlidViewing), With<SessionGovernor>>,
+    q_view: Query<(), With<ViewMapData>>,
+) {
+    let Ok((players, mut viewing)) = q_session.get_single_mut() else {
+        error!("Cannot switch view: no session.");
+        return;
+    };
+    let n = players.e_plid.len();
+    let next = (1..=n)
+        .map(|off| (viewing.0.i() + off) % n)
+        .find(|i| q_view.contains(players.e_plid[*i]));
+    match next {
+        Some(i) if i != viewing.0.i() => {
+            viewing.0 = PlayerId::from(i as u8);
+        }
+        _ => {
+            info!("No other views available.");
+        }
+    }
+}
+
 fn switch_view_despawn(
     mut commands: Commands,
     q: Query<Entity, With<DespawnOnViewSwitch>>,
@@ -146,36 +275,7 @@ fn view_update_from_gameevents(
             let Ok(mut view) = q_view.get_mut(*e_plid) else {
                 continue;
             };
-            match ev.ev {
-                MwEv::TileKind { pos, kind } => {
-                    let tile = &mut view.0[pos];
-                    tile.set_kind(kind);
-                },
-                MwEv::TileOwner { pos, plid } => {
-                    let tile = &mut view.0[pos];
-                    tile.set_owner(u8::from(plid));
-                },
-                MwEv::DigitCapture { pos, digit: MwDigit { digit, asterisk } } => {
-                    let tile = &mut view.0[pos];
-                    tile.set_owner(u8::from(plid));
-                    tile.set_digit(digit);
-                    tile.set_asterisk(asterisk);
-                },
-                MwEv::RevealItem { pos, item } => {
-                    let tile = &mut view.0[pos];
-                    tile.set_item(item);
-                },
-                MwEv::Flag { pos, plid } => {
-                    let tile = &mut view.0[pos];
-                    tile.set_flag(u8::from(plid));
-                },
-                MwEv::Explode { pos } => {
-                    let tile = &mut view.0[pos];
-                    // clear any item from the tile
-                    tile.set_item(ItemKind::Safe);
-                },
-                _ => {}
-            }
+            update_view_map(&mut view.0, plid, &ev.ev);
         }
     }
 }
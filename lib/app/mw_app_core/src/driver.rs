@@ -25,6 +25,7 @@ use crate::{prelude::*, session::NeedsSessionGovernorSet};
 
 pub fn plugin(app: &mut App) {
     app.add_event::<GameEvent>();
+    app.add_event::<GameOverEvent>();
     app.configure_sets(Update, (
         NeedsDriverGovernorSet
             .run_if(any_with_component::<DriverGovernor>),
@@ -47,6 +48,10 @@ pub struct GameInEventSS;
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, SystemSet)]
 pub struct GameOutEventSS;
 
+/// Sent by the driver implementation when the game session has ended
+#[derive(Event, Debug, Clone, Copy)]
+pub struct GameOverEvent;
+
 #[derive(Bundle, Default)]
 pub struct DriverGovernorBundle {
     pub cleanup: GameFullCleanup,
@@ -7,6 +7,15 @@ pub fn plugin(app: &mut App) {
     app.register_type::<InputAnalogName>();
 }
 
+/// Explore the tile at the grid cursor
+pub const ACTION_EXPLORE: &str = "ACTION_EXPLORE";
+/// Toggle a flag on the tile at the grid cursor
+pub const ACTION_FLAG: &str = "ACTION_FLAG";
+/// Explore around the digit at the grid cursor, if it has enough flags
+pub const ACTION_CHORD: &str = "ACTION_CHORD";
+/// Switch to playing as and viewing the next playable plid
+pub const ACTION_NEXT_PLID: &str = "ACTION_NEXT_PLID";
+
 #[derive(ScheduleLabel, Clone, Debug, PartialEq, Eq, Hash)]
 pub struct InputActionOnPress(pub InputActionName);
 #[derive(ScheduleLabel, Clone, Debug, PartialEq, Eq, Hash)]
@@ -111,6 +111,18 @@ pub struct NLives {
     pub lives: u8,
 }
 
+/// Final results of a Plid, once the game is over
+#[derive(Component, Debug, Clone, Copy)]
+pub struct PlidStanding {
+    /// 1 is the winner
+    pub rank: u8,
+    pub tiles: u16,
+    pub lives: u8,
+    /// 0 if the plid survived until the end
+    pub elim_order: u8,
+    pub elim_secs: u16,
+}
+
 impl Default for SpectatorPlidBundle {
     fn default() -> Self {
         Self {
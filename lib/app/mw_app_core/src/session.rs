@@ -45,6 +45,12 @@ pub struct SessionGovernor;
 #[derive(Component)]
 pub struct PlidPlayingAs(pub PlayerId);
 
+/// The subplid that the user controls, within `PlidPlayingAs`
+/// (for when multiple users share a plid, such as in Co-op).
+/// If absent, assume `0`.
+#[derive(Component)]
+pub struct SubPlidPlayingAs(pub u8);
+
 /// The plid whose PoV is being rendered
 #[derive(Component)]
 pub struct PlidViewing(pub PlayerId);
@@ -25,9 +25,11 @@
 //!
 //! The view-related components are inserted onto Plid entities (see `player`).
 
-use mw_common::{game::{ItemKind, StructureKind, TileKind}, grid::MapDataPos, plid::PlayerId};
+use mw_common::{grid::MapDataPos, plid::PlayerId};
 
-use crate::{map::NeedsMapGovernorSet, prelude::*, session::NeedsSessionGovernorSet};
+pub use mw_common::game::ViewTileData;
+
+use crate::{map::{MapDataOrig, NeedsMapGovernorSet}, prelude::*, session::NeedsSessionGovernorSet};
 
 pub fn plugin(app: &mut App) {
     app.configure_stage_set_no_rc(
@@ -63,6 +65,25 @@ pub struct ViewBundle {
 #[derive(Component, Clone)]
 pub struct ViewMapData(pub MapDataPos<ViewTileData>);
 
+impl ViewMapData {
+    /// Create a fresh view of a map, as it is at the start of the game
+    ///
+    /// Only the spectator view (`PlayerId::Neutral`) gets the items
+    /// (where the mines are). Players must not see them.
+    pub fn from_map_orig(orig: &MapDataOrig, plid: PlayerId) -> Self {
+        let show_items = plid == PlayerId::Neutral;
+        ViewMapData(orig.map.convert(|_, d| {
+            let mut t = if show_items {
+                ViewTileData::from_kind_item(d.kind(), d.item())
+            } else {
+                ViewTileData::from_kind(d.kind())
+            };
+            t.set_region(d.region());
+            t
+        }))
+    }
+}
+
 /// Marker for entities that should be discarded on view switch.
 #[derive(Component)]
 pub struct DespawnOnViewSwitch;
@@ -70,40 +91,3 @@ pub struct DespawnOnViewSwitch;
 /// Marker for entities that should be shown in a specific view and hidden in other views.
 #[derive(Component)]
 pub struct VisibleInView(pub PlayerId);
-
-/// The per-tile data of a view.
-///
-/// This is a compact bitfield representation of the game state that needs
-/// to be cached/tracked for view switching in multi-view modes.
-///
-/// We don't need all game state. Some can be discarded on view switch,
-/// or just kept live in ECS entities and hidden (like explosion effects, etc),
-/// some can be recomputed (vis levels, roads).
-#[bitfield]
-#[derive(Clone, Copy, Default)]
-pub struct ViewTileData {
-    pub owner: B4,
-    pub digit: B3,
-    pub asterisk: bool,
-    pub kind: TileKind,
-    pub item: ItemKind,
-    pub has_structure: bool,
-    pub structure: StructureKind,
-    pub flag: B4,
-    #[skip] __: B4,
-    pub region: u8,
-}
-
-impl ViewTileData {
-    pub fn from_kind(kind: TileKind) -> Self {
-        let mut t = Self::default();
-        t.set_kind(kind);
-        t
-    }
-    pub fn from_kind_item(kind: TileKind, item: ItemKind) -> Self {
-        let mut t = Self::default();
-        t.set_kind(kind);
-        t.set_item(item);
-        t
-    }
-}
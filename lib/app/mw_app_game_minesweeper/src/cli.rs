@@ -1,5 +1,7 @@
-use mw_app_core::{driver::*, graphics::*, player::*, session::*, settings::{GraphicsStyleSettings, PlidColorSettings}, user::*};
-use mw_game_minesweeper::MinesweeperSettings;
+use mw_app_core::{driver::*, graphics::*, map::{MapDataOrig, MapGovernorBundle, MapTileDataOrig}, player::*, session::*, settings::{GraphicsStyleSettings, PlidColorSettings}, user::*};
+use mw_app_io::{mwfile::record::GameRecorder, offline_host::OfflineHost, settings::ReplaySettings};
+use mw_common::plid::{MAX_PLIDS, MAX_SUBPLIDS};
+use mw_game_minesweeper::{snapshot::read_saved_map, GameMinesweeper};
 
 use crate::{map::SimpleMapGenerator, offline::SetupOfflineGame, prelude::*, settings::{OfflineMinesweeperSettings, SimpleMapSettings}};
 
@@ -8,6 +10,22 @@ pub fn plugin(app: &mut App) {
         "start_minesweeper_singleplayer",
         start_minesweeper_singleplayer
     );
+    app.register_clicommand_args(
+        "start_minesweeper_playground",
+        start_minesweeper_playground
+    );
+    app.register_clicommand_args(
+        "start_minesweeper_coop",
+        start_minesweeper_coop
+    );
+    app.register_clicommand_args("play_subplid", cli_play_subplid);
+    app.register_clicommand_args(
+        "load_minesweeper_game",
+        load_minesweeper_game
+    );
+    app.register_clicommand_args("save_game", cli_save_game);
+    app.register_clicommand_noargs("game_pause", cli_game_pause);
+    app.register_clicommand_noargs("game_resume", cli_game_resume);
 }
 
 fn start_minesweeper_singleplayer(
@@ -17,36 +35,273 @@ fn start_minesweeper_singleplayer(
     q_user: Query<&MyUserProfile, With<UserGovernor>>,
 ) {
     let s_mapgen = settings.get::<SimpleMapSettings>().unwrap();
-    let s_colors = settings.get::<PlidColorSettings>().unwrap();
-    let s_gfx = settings.get::<GraphicsStyleSettings>().unwrap();
     let s_offline = settings.get::<OfflineMinesweeperSettings>().unwrap();
 
-    let e_subplid = commands.spawn((
-        SubPlidBundle::new(0, &q_user.single().0),
-    )).id();
-    let e_plid0 = commands.spawn((
-        SpectatorPlidBundle::default(),
-    )).id();
-    let e_plid1 = commands.spawn((
-        PlayerPlidBundle::new(1.into(), s_colors.colors[1].into(), &[e_subplid]),
-    )).id();
-    commands.spawn((
-        SessionGovernorBundle::new(
-            1.into(), &[e_plid0, e_plid1], &[&[], &[e_subplid]]
-        ),
-        PlidScoreByOwnedPct,
+    let e_driver = setup_offline_governors(&mut commands, &settings, &q_user.single().0, 1, 1);
+    commands.entity(e_driver).insert((
+        SimpleMapGenerator {
+            topology: s_mapgen.topology,
+            size: s_mapgen.size,
+            mapgen: s_mapgen.mapgen.clone(),
+            seed: s_mapgen.seed,
+        },
+        SetupOfflineGame {
+            settings: s_offline.game.clone(),
+            minegen: s_offline.minegen.clone(),
+            seed: s_offline.seed,
+        },
     ));
-    commands.spawn((
-        DriverGovernorBundle::default(),
+
+    state.set(AppState::GameLoading);
+}
+
+/// Start an offline game where we control all the plids (hotseat)
+///
+/// The number of plids can be given as an argument, otherwise it
+/// comes from `OfflineMinesweeperSettings`.
+fn start_minesweeper_playground(
+    In(args): In<Vec<String>>,
+    mut commands: Commands,
+    settings: Settings,
+    mut state: ResMut<NextState<AppState>>,
+    q_user: Query<&MyUserProfile, With<UserGovernor>>,
+) {
+    let s_mapgen = settings.get::<SimpleMapSettings>().unwrap();
+    let s_offline = settings.get::<OfflineMinesweeperSettings>().unwrap();
+
+    let n_plids = match args.first().map(|s| s.parse::<u8>()) {
+        Some(Ok(n)) => n,
+        Some(Err(e)) => {
+            error!("Invalid number of plids: {}", e);
+            return;
+        }
+        None => s_offline.playground_plids,
+    };
+    if n_plids == 0 || n_plids > MAX_PLIDS {
+        error!("Number of plids must be between 1 and {}!", MAX_PLIDS);
+        return;
+    }
+
+    let e_driver = setup_offline_governors(&mut commands, &settings, &q_user.single().0, n_plids, 1);
+    commands.entity(e_driver).insert((
         SimpleMapGenerator {
             topology: s_mapgen.topology,
             size: s_mapgen.size,
+            mapgen: s_mapgen.mapgen.clone(),
+            seed: s_mapgen.seed,
         },
         SetupOfflineGame {
             settings: s_offline.game.clone(),
             minegen: s_offline.minegen.clone(),
+            seed: s_offline.seed,
         },
     ));
+
+    state.set(AppState::GameLoading);
+}
+
+/// Start an offline Co-op game, where multiple subplids share one plid
+///
+/// We control all the subplids (switch with `play_subplid`).
+/// The number of subplids can be given as an argument, otherwise
+/// it comes from `OfflineMinesweeperSettings`.
+fn start_minesweeper_coop(
+    In(args): In<Vec<String>>,
+    mut commands: Commands,
+    settings: Settings,
+    mut state: ResMut<NextState<AppState>>,
+    q_user: Query<&MyUserProfile, With<UserGovernor>>,
+) {
+    let s_mapgen = settings.get::<SimpleMapSettings>().unwrap();
+    let s_offline = settings.get::<OfflineMinesweeperSettings>().unwrap();
+
+    let n_subplids = match args.first().map(|s| s.parse::<u8>()) {
+        Some(Ok(n)) => n,
+        Some(Err(e)) => {
+            error!("Invalid number of subplids: {}", e);
+            return;
+        }
+        None => s_offline.coop_subplids,
+    };
+    if n_subplids < 2 || n_subplids > MAX_SUBPLIDS {
+        error!("Number of subplids must be between 2 and {}!", MAX_SUBPLIDS);
+        return;
+    }
+
+    let e_driver = setup_offline_governors(&mut commands, &settings, &q_user.single().0, 1, n_subplids);
+    commands.entity(e_driver).insert((
+        SimpleMapGenerator {
+            topology: s_mapgen.topology,
+            size: s_mapgen.size,
+            mapgen: s_mapgen.mapgen.clone(),
+            seed: s_mapgen.seed,
+        },
+        SetupOfflineGame {
+            settings: s_offline.game.clone(),
+            minegen: s_offline.minegen.clone(),
+            seed: s_offline.seed,
+        },
+    ));
+
+    state.set(AppState::GameLoading);
+}
+
+/// Continue a singleplayer game that was saved with `save_game`
+fn load_minesweeper_game(
+    In(args): In<Vec<String>>,
+    mut commands: Commands,
+    settings: Settings,
+    mut state: ResMut<NextState<AppState>>,
+    q_user: Query<&MyUserProfile, With<UserGovernor>>,
+) {
+    let Some(path) = args.first() else {
+        error!("Please specify the saved game file to load!");
+        return;
+    };
+    let data = match std::fs::read(path) {
+        Ok(data) => data,
+        Err(e) => {
+            error!("Cannot read saved game {:?}: {:#}", path, e);
+            return;
+        }
+    };
+    let (topology, map) = match read_saved_map(&data) {
+        Ok(map) => map,
+        Err(e) => {
+            error!("Cannot load saved game {:?}: {:#}", path, e);
+            return;
+        }
+    };
+    let map_src = MapDataOrig {
+        map: map.convert(|_, d| MapTileDataOrig::from(*d)),
+        cits: vec![],
+    };
+    commands.spawn(
+        MapGovernorBundle::from_map_src(topology, map_src)
+    );
+
+    let e_driver = setup_offline_governors(&mut commands, &settings, &q_user.single().0, 1, 1);
+    commands.entity(e_driver).insert((
+        OfflineHost::<Box<GameMinesweeper>>::restore(data),
+    ));
+
+    state.set(AppState::GameLoading);
+}
+
+fn cli_save_game(
+    In(args): In<Vec<String>>,
+    settings: Settings,
+    mut q_driver: Query<&mut OfflineHost<Box<GameMinesweeper>>, With<DriverGovernor>>,
+) {
+    let Ok(mut host) = q_driver.get_single_mut() else {
+        error!("Cannot save game: no offline game is running.");
+        return;
+    };
+    let path = if let Some(path) = args.first() {
+        PathBuf::from(path)
+    } else {
+        let s_offline = settings.get::<OfflineMinesweeperSettings>().unwrap();
+        let timestamp = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        Path::new(&s_offline.save_dir).join(format!("minesweeper-{}.save", timestamp))
+    };
+    host.save(path);
+}
+
+/// Switch which subplid we control, within the plid we are playing as
+fn cli_play_subplid(
+    In(args): In<Vec<String>>,
+    mut commands: Commands,
+    q_session: Query<(Entity, &PlayersIndex, &PlidPlayingAs), With<SessionGovernor>>,
+) {
+    let Ok((e_session, players, playing_as)) = q_session.get_single() else {
+        error!("Cannot switch subplid: no session.");
+        return;
+    };
+    let Some(Ok(subplid)) = args.first().map(|s| s.parse::<u8>()) else {
+        error!("Please specify the subplid to play as!");
+        return;
+    };
+    let n_subplids = players.e_subplid.get(playing_as.0.i())
+        .map(|subs| subs.len())
+        .unwrap_or(0);
+    if subplid as usize >= n_subplids {
+        error!("Cannot switch subplid: {:?} has no subplid {}.", playing_as.0, subplid);
+        return;
+    }
+    info!("Now playing as subplid {} of {:?}.", subplid, playing_as.0);
+    commands.entity(e_session).insert(SubPlidPlayingAs(subplid));
+}
+
+fn cli_game_pause(
+    mut q_driver: Query<&mut OfflineHost<Box<GameMinesweeper>>, With<DriverGovernor>>,
+) {
+    if let Ok(mut host) = q_driver.get_single_mut() {
+        host.set_paused(true);
+    }
+}
+
+fn cli_game_resume(
+    mut q_driver: Query<&mut OfflineHost<Box<GameMinesweeper>>, With<DriverGovernor>>,
+) {
+    if let Ok(mut host) = q_driver.get_single_mut() {
+        host.set_paused(false);
+    }
+}
+
+/// Spawn everything needed for an offline session, except for the game itself
+///
+/// Every plid gets `n_subplids` subplids for the local user, and is playable by us.
+/// We start out playing as plid 1 (and subplid 0, if there are several).
+///
+/// Returns the Driver Governor, for the game to be set up on.
+fn setup_offline_governors(
+    commands: &mut Commands,
+    settings: &Settings,
+    user: &UserProfile,
+    n_plids: u8,
+    n_subplids: u8,
+) -> Entity {
+    let s_colors = settings.get::<PlidColorSettings>().unwrap();
+    let s_gfx = settings.get::<GraphicsStyleSettings>().unwrap();
+    let s_replay = settings.get::<ReplaySettings>().unwrap();
+
+    let mut e_plids = vec![commands.spawn((
+        SpectatorPlidBundle::default(),
+    )).id()];
+    let mut e_subplids = vec![vec![]];
+    for i in 1..=n_plids {
+        let e_subplid: Vec<Entity> = (0..n_subplids).map(|subplid| commands.spawn((
+            SubPlidBundle::new(subplid, user),
+        )).id()).collect();
+        // the palette is user-configurable and may have fewer entries
+        // than players; fall back to the neutral color
+        let color = s_colors.colors.get(i as usize)
+            .or(s_colors.colors.first())
+            .copied()
+            .unwrap_or_default();
+        e_plids.push(commands.spawn((
+            PlayerPlidBundle::new(i.into(), color.into(), &e_subplid),
+            PlidPlayable,
+        )).id());
+        e_subplids.push(e_subplid);
+    }
+    let e_subplids: Vec<&[Entity]> = e_subplids.iter().map(|v| v.as_slice()).collect();
+    let e_session = commands.spawn((
+        SessionGovernorBundle::new(
+            1.into(), &e_plids, &e_subplids,
+        ),
+        PlidScoreByOwnedPct,
+    )).id();
+    if n_subplids > 1 {
+        commands.entity(e_session).insert(SubPlidPlayingAs(0));
+    }
+    let e_driver = commands.spawn((
+        DriverGovernorBundle::default(),
+        GameRecorder::new(s_replay.autosave_replays),
+    )).id();
     let e_gov_gfx = commands.spawn((
         GraphicsGovernorBundle {
             cleanup: default(),
@@ -70,5 +325,5 @@ fn start_minesweeper_singleplayer(
         };
     }
 
-    state.set(AppState::GameLoading);
+    e_driver
 }
@@ -1,4 +1,91 @@
+use mw_app_core::{input::*, map::{GridCursor, MapGovernor}};
+use mw_game_minesweeper::MinesweeperInputAction;
+
 use crate::prelude::*;
 
 pub fn plugin(app: &mut App) {
+    app.add_systems(Startup, (
+        setup_inputs
+            .in_set(SetStage::Provide(GameInputSS::Setup)),
+    ));
+    app.add_systems(
+        OnEnter(AppState::InGame),
+        enable_inputs,
+    );
+    app.add_systems(
+        InputActionOnPress(ACTION_EXPLORE.into()),
+        input_action_explore,
+    );
+    app.add_systems(
+        InputActionOnPress(ACTION_FLAG.into()),
+        input_action_flag,
+    );
+    app.add_systems(
+        InputActionOnPress(ACTION_CHORD.into()),
+        input_action_chord,
+    );
+}
+
+/// Marker for the input actions used to play Minesweeper
+#[derive(Component)]
+struct MinesweeperInput;
+
+fn setup_inputs(mut commands: Commands) {
+    commands.spawn((
+        MinesweeperInput,
+        InputActionBundle::from(ACTION_EXPLORE),
+    ));
+    commands.spawn((
+        MinesweeperInput,
+        InputActionBundle::from(ACTION_FLAG),
+    ));
+    commands.spawn((
+        MinesweeperInput,
+        InputActionBundle::from(ACTION_CHORD),
+    ));
+}
+
+fn enable_inputs(
+    mut commands: Commands,
+    q_actions: Query<Entity, (With<MinesweeperInput>, With<InputAction>)>,
+) {
+    for e in &q_actions {
+        commands.entity(e).insert(InputActionEnabled);
+    }
+}
+
+fn input_action_explore(
+    mut evw: EventWriter<MinesweeperInputAction>,
+    q_map: Query<&GridCursor, With<MapGovernor>>,
+) {
+    let Ok(crs) = q_map.get_single() else {
+        return;
+    };
+    if let Some(pos) = crs.0 {
+        evw.send(MinesweeperInputAction::ExploreTile { pos });
+    }
+}
+
+fn input_action_flag(
+    mut evw: EventWriter<MinesweeperInputAction>,
+    q_map: Query<&GridCursor, With<MapGovernor>>,
+) {
+    let Ok(crs) = q_map.get_single() else {
+        return;
+    };
+    if let Some(pos) = crs.0 {
+        evw.send(MinesweeperInputAction::ToggleFlag { pos });
+    }
+}
+
+fn input_action_chord(
+    mut evw: EventWriter<MinesweeperInputAction>,
+    q_map: Query<&GridCursor, With<MapGovernor>>,
+) {
+    let Ok(crs) = q_map.get_single() else {
+        return;
+    };
+    if let Some(pos) = crs.0 {
+        evw.send(MinesweeperInputAction::ChordTile { pos });
+    }
 }
@@ -1,4 +1,6 @@
 use mw_app_core::{driver::DriverGovernor, map::*};
+use mw_common::mapgen::{gen_map, MapGenSettings};
+use mw_common::prelude::rand_pcg::Pcg64Mcg;
 
 use crate::prelude::*;
 
@@ -13,7 +15,7 @@ pub fn plugin(app: &mut App) {
 }
 
 /// Add this onto the Driver Governor to generate a simple
-/// flat map for a local/offline session.
+/// procedural island map (with no cities) for a local/offline session.
 ///
 /// During the GameLoading state, this will
 /// enable a system that sets up the Map Governor.
@@ -21,6 +23,9 @@ pub fn plugin(app: &mut App) {
 pub struct SimpleMapGenerator {
     pub topology: Topology,
     pub size: u8,
+    pub mapgen: MapGenSettings,
+    /// Fixed RNG seed, to generate the same map (random if `None`)
+    pub seed: Option<u64>,
 }
 
 fn gen_simple_map(
@@ -32,11 +37,26 @@ fn gen_simple_map(
         return true.into();
     }
     let gen = q_driver.single();
+    let mut rng = if let Some(seed) = gen.seed {
+        Pcg64Mcg::seed_from_u64(seed)
+    } else {
+        Pcg64Mcg::from_entropy()
+    };
     let mut empty_tile = MapTileDataOrig::default();
-    empty_tile.set_kind(TileKind::Regular);
     empty_tile.set_item(ItemKind::Safe);
     empty_tile.set_region(0);
-    let map_src = MapDataPos::new(gen.size, empty_tile);
+    let map_src: MapDataPos<MapTileDataOrig> = match gen.topology {
+        Topology::Hex => {
+            let mut map: MapDataC<Hex, _> = MapData::new(gen.size, empty_tile);
+            gen_map(&gen.mapgen, &mut map, &mut rng, |d, kind| d.set_kind(kind));
+            map.rekey()
+        }
+        Topology::Sq => {
+            let mut map: MapDataC<Sq, _> = MapData::new(gen.size, empty_tile);
+            gen_map(&gen.mapgen, &mut map, &mut rng, |d, kind| d.set_kind(kind));
+            map.rekey()
+        }
+    };
     let map_src = MapDataOrig {
         map: map_src,
         cits: vec![],
@@ -20,6 +20,8 @@ pub fn plugin(app: &mut App) {
 pub struct SetupOfflineGame {
     pub settings: MinesweeperSettings,
     pub minegen: MineGenSettings,
+    /// Fixed RNG seed (random if `None`)
+    pub seed: Option<u64>,
 }
 
 #[derive(Default)]
@@ -42,24 +44,50 @@ fn setup_offline_game(
             let Ok((mapdesc, mapdata)) = q_map.get_single() else {
                 return false.into();
             };
-            let n_plids = q_session.single().e_plid.len() - 1;
+            let players = q_session.single();
+            let n_plids = players.e_plid.len() - 1;
+            // plids with multiple subplids are Co-op
+            let subplids: Vec<(PlayerId, u8)> = players.e_subplid.iter()
+                .enumerate()
+                .skip(1)
+                .filter(|(_, subs)| subs.len() > 1)
+                .map(|(i, subs)| (PlayerId::from(i as u8), subs.len() as u8))
+                .collect();
             let (_, setup) = q_driver.single();
             let rt = AsyncComputeTaskPool::get();
             match mapdesc.topology {
                 Topology::Hex => {
                     let settings = setup.settings.clone();
+                    let seed = setup.seed;
+                    let subplids = subplids.clone();
                     let mapdata = mapdata.map.clone();
                     let task = rt.spawn(async move {
-                        GameMinesweeperBuilder::new(settings, n_plids as u8)
+                        let mut builder = GameMinesweeperBuilder::new(settings, n_plids as u8);
+                        if let Some(seed) = seed {
+                            builder = builder.with_seed(seed);
+                        }
+                        for (plid, n_subplids) in subplids {
+                            builder = builder.with_subplids(plid, n_subplids);
+                        }
+                        builder
                             .with_mapdata_hex(mapdata.size(), |c| mapdata[c.into()].kind())
                     });
                     *state = SetupState::Awaiting(task);
                 }
                 Topology::Sq => {
                     let settings = setup.settings.clone();
+                    let seed = setup.seed;
+                    let subplids = subplids.clone();
                     let mapdata = mapdata.map.clone();
                     let task = rt.spawn(async move {
-                        GameMinesweeperBuilder::new(settings, n_plids as u8)
+                        let mut builder = GameMinesweeperBuilder::new(settings, n_plids as u8);
+                        if let Some(seed) = seed {
+                            builder = builder.with_seed(seed);
+                        }
+                        for (plid, n_subplids) in subplids {
+                            builder = builder.with_subplids(plid, n_subplids);
+                        }
+                        builder
                             .with_mapdata_sq(mapdata.size(), |c| mapdata[c.into()].kind())
                     });
                     *state = SetupState::Awaiting(task);
@@ -70,9 +98,11 @@ fn setup_offline_game(
         SetupState::Awaiting(task) => {
             if let Some(game) = block_on(poll_once(task)) {
                 let (e_driver, setup) = q_driver.single();
+                info!("Offline minesweeper game seed: {}", game.seed());
                 commands.entity(e_driver).insert((
                     OfflineHost::new(game, Box::new(MinesweeperInitData {
-                        minegen: setup.minegen.clone()
+                        minegen: setup.minegen.clone(),
+                        seed: None,
                     })),
                 ));
                 *state = SetupState::Done;
@@ -1,3 +1,4 @@
+use mw_common::mapgen::MapGenSettings;
 use mw_game_minesweeper::{minegen::MineGenSettings, MinesweeperSettings};
 
 use crate::prelude::*;
@@ -7,11 +8,32 @@ pub fn plugin(app: &mut App) {
     app.init_setting::<OfflineMinesweeperSettings>(SETTINGS_APP.as_ref());
 }
 
-#[derive(Reflect, Default, Debug, Clone)]
+#[derive(Reflect, Debug, Clone)]
 #[reflect(Setting)]
 pub struct OfflineMinesweeperSettings {
     pub game: MinesweeperSettings,
     pub minegen: MineGenSettings,
+    /// Fixed RNG seed, to replay the same board (random if `None`)
+    pub seed: Option<u64>,
+    /// Where to save games in progress, if no path is given
+    pub save_dir: String,
+    /// How many plids to start Playground (hotseat) games with
+    pub playground_plids: u8,
+    /// How many subplids share the plid in Co-op games
+    pub coop_subplids: u8,
+}
+
+impl Default for OfflineMinesweeperSettings {
+    fn default() -> Self {
+        OfflineMinesweeperSettings {
+            game: default(),
+            minegen: default(),
+            seed: None,
+            save_dir: "saves".into(),
+            playground_plids: 2,
+            coop_subplids: 2,
+        }
+    }
 }
 
 impl Setting for OfflineMinesweeperSettings {}
@@ -21,11 +43,19 @@ impl Setting for OfflineMinesweeperSettings {}
 pub struct SimpleMapSettings {
     pub topology: Topology,
     pub size: u8,
+    pub mapgen: MapGenSettings,
+    /// Fixed RNG seed, to generate the same map (random if `None`)
+    pub seed: Option<u64>,
 }
 
 impl Default for SimpleMapSettings {
     fn default() -> Self {
-        SimpleMapSettings { topology: Topology::Hex, size: 24 }
+        SimpleMapSettings {
+            topology: Topology::Hex,
+            size: 24,
+            mapgen: default(),
+            seed: None,
+        }
     }
 }
 
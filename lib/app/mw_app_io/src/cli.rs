@@ -1,11 +1,12 @@
 use bevy::tasks::IoTaskPool;
-use mw_app_core::{graphics::*, map::*, settings::GraphicsStyleSettings};
+use mw_app_core::{driver::*, graphics::*, map::*, player::*, session::*, settings::{GraphicsStyleSettings, PlidColorSettings}, user::UserProfile};
 
-use crate::{mwfile::{loader::{load_mwfile, MwFileLoaderSettings}, saver::{save_mwfile, MwFileSaverSettings}, MwMap}, prelude::*};
+use crate::{mwfile::{loader::{load_mwfile, MwFileLoaderSettings}, replay::ReplayDriver, saver::{save_mwfile, MwFileSaverSettings}, MwMap, MwReplay}, prelude::*};
 
 pub fn plugin(app: &mut App) {
     app.register_clicommand_args("save_map", save_map);
     app.register_clicommand_args("start_map_viewer", start_map_viewer);
+    app.register_clicommand_args("start_replay", start_replay);
 }
 
 fn save_map(
@@ -34,7 +35,7 @@ fn save_map(
     rt.spawn(async move {
         let r: AnyResult<()> = async {
             let mut file = async_fs::File::create(&path).await?;
-            save_mwfile(&mut file, &settings, &mwmap, None).await?;
+            save_mwfile(&mut file, &settings, &mwmap, None, None).await?;
             file.sync_all().await?;
             Ok(())
         }.await;
@@ -111,3 +112,127 @@ fn start_map_viewer(
 
     state.set(AppState::GameLoading);
 }
+
+fn start_replay(
+    In(args): In<Vec<String>>,
+    mut commands: Commands,
+    settings: Settings,
+    mut state: ResMut<NextState<AppState>>,
+) {
+    let Some(path) = args.first().cloned() else {
+        error!("Cannot load replay: please specify path!");
+        return;
+    };
+
+    let lsettings = MwFileLoaderSettings {
+        load_replay: true,
+        load_map_items: true,
+        verify_checksums: true,
+    };
+    let rt = IoTaskPool::get();
+    let p = path.clone();
+    let r: Vec<AnyResult<(MwMap, Option<MwReplay>)>> = rt.scope(|s| s.spawn(async move {
+        let mut file = async_fs::File::open(&p).await?;
+        Ok(load_mwfile(&mut file, &lsettings).await?)
+    }));
+    let (mwmap, mwreplay) = match r.into_iter().next() {
+        Some(Ok((map, Some(replay)))) => {
+            info!("Replay loaded from {:?}.", path);
+            (map, replay)
+        },
+        Some(Ok((_, None))) => {
+            error!("File {:?} does not contain a replay.", path);
+            return;
+        },
+        Some(Err(e)) => {
+            error!("Could not load replay from {:?}: {:#}.", path, e);
+            return;
+        },
+        None => {
+            error!("Could not load replay from {:?}", path);
+            return;
+        }
+    };
+    let driver = match ReplayDriver::new(&mwreplay) {
+        Ok(driver) => driver,
+        Err(e) => {
+            error!("Could not decode replay from {:?}: {:#}.", path, e);
+            return;
+        }
+    };
+
+    // Use the names/colors from the file if it has them,
+    // otherwise fall back to our default colors and no subplids
+    let s_colors = settings.get::<PlidColorSettings>().unwrap();
+    let roster = mwreplay.players();
+    let e_subplids: Vec<Vec<Entity>> = (0..=mwreplay.max_plid()).map(|i| {
+        let Some(info) = roster.get(i as usize) else {
+            return vec![];
+        };
+        info.subplids.iter().enumerate().map(|(subplid, sub)| {
+            let profile = UserProfile {
+                display_name: sub.display_name.clone(),
+            };
+            commands.spawn((
+                SubPlidBundle::new(subplid as u8, &profile),
+            )).id()
+        }).collect()
+    }).collect();
+    let mut e_plids = vec![commands.spawn((
+        SpectatorPlidBundle::default(),
+    )).id()];
+    for i in 1..=mwreplay.max_plid() {
+        let color = if let Some(info) = roster.get(i as usize) {
+            let [r, g, b] = info.color;
+            Color::srgb_u8(r, g, b)
+        } else {
+            s_colors.colors.get(i as usize)
+                .or(s_colors.colors.first())
+                .copied()
+                .unwrap_or_default()
+                .into()
+        };
+        e_plids.push(commands.spawn((
+            PlayerPlidBundle::new(i.into(), color, &e_subplids[i as usize]),
+        )).id());
+    }
+    let e_subplids: Vec<&[Entity]> = e_subplids.iter().map(|v| v.as_slice()).collect();
+    commands.spawn((
+        SessionGovernorBundle::new(
+            PlayerId::Neutral, &e_plids, &e_subplids,
+        ),
+    ));
+    commands.spawn((
+        DriverGovernorBundle::default(),
+        driver,
+    ));
+    commands.spawn(
+        MapGovernorBundle::from_map_src(mwmap.topology, mwmap.data)
+    );
+
+    let s_gfx = settings.get::<GraphicsStyleSettings>().unwrap();
+    let e_gov_gfx = commands.spawn((
+        GraphicsGovernorBundle {
+            cleanup: default(),
+            marker: GraphicsGovernor,
+            style: CurrentGraphicsStyle(s_gfx.game_preferred_style),
+        },
+        DisplayDigitsMode::Game,
+        DisplayItemsMode::MyItems,
+    )).id();
+    if s_gfx.game_enable_both_styles {
+        commands.entity(e_gov_gfx).insert((
+            Gfx2dEnabled,
+            Gfx3dEnabled,
+        ));
+    } else {
+        match s_gfx.game_preferred_style {
+            GraphicsStyle::Gfx2d => commands.entity(e_gov_gfx)
+                .insert(Gfx2dEnabled),
+            GraphicsStyle::Gfx3d => commands.entity(e_gov_gfx)
+                .insert(Gfx3dEnabled),
+        };
+    }
+
+    state.set(AppState::GameLoading);
+}
@@ -16,6 +16,7 @@ use crate::prelude::*;
 pub fn plugin(app: &mut App) {
     app.add_plugins((
         crate::cli::plugin,
+        crate::mwfile::plugin,
         crate::settings::plugin,
     ));
 }
@@ -1,4 +1,6 @@
 use mw_app_core::map::{MapDataOrig, MapDescriptor};
+use mw_dataformat::keyframe::MwKeyframes;
+use mw_dataformat::players::PlidInfo;
 
 use crate::prelude::*;
 
@@ -13,6 +15,10 @@ pub fn plugin(app: &mut App) {
     app.init_asset::<MwMap>();
     app.init_asset::<MwReplay>();
     app.init_asset_loader::<loader::MwFileLoader>();
+    app.add_plugins((
+        record::plugin,
+        replay::plugin,
+    ));
 }
 
 #[derive(Asset, TypePath)]
@@ -30,7 +36,28 @@ pub struct MwMap {
 #[derive(Asset, TypePath)]
 pub struct MwReplay {
     pub map: Handle<MwMap>,
+    /// The `max_plid` that the frame data was encoded with
+    max_plid: u8,
+    /// Who played the game (indexed by plid, starting with the spectator)
+    players: Vec<PlidInfo>,
     raw_framedata: Vec<u8>,
+    /// Snapshots of the views, to help with seeking (if the file has them)
+    keyframes: Option<MwKeyframes>,
+}
+
+impl MwReplay {
+    pub fn max_plid(&self) -> u8 {
+        self.max_plid
+    }
+    pub fn players(&self) -> &[PlidInfo] {
+        &self.players
+    }
+    pub fn raw_framedata(&self) -> &[u8] {
+        &self.raw_framedata
+    }
+    pub fn keyframes(&self) -> Option<&MwKeyframes> {
+        self.keyframes.as_ref()
+    }
 }
 
 impl MwMap {
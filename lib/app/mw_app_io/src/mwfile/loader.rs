@@ -75,7 +75,7 @@ pub async fn load_mwfile(
         let len_headers = MwFileHeader::serialized_len() + ISHeader::serialized_len();
         let mut bytes = Vec::new();
         let mfr = if settings.load_replay {
-            reader.read_to_end(&mut buf).await?;
+            reader.read_to_end(&mut bytes).await?;
             let mut mfr = MwFileReader::new(Cursor::new(&mut bytes), &mut buf)?;
             if settings.verify_checksums {
                 mfr.verify_checksums()?;
@@ -121,10 +121,22 @@ pub async fn load_mwfile(
             data: orig,
         };
         if settings.load_replay {
+            let players = isr.read_players()?;
             let mut mfr = mfr.finish_is(isr)?;
+            // keyframes are optional; we can still play the replay without them
+            let keyframes = match mfr.read_keyframes(&mut scratch) {
+                Ok(keyframes) => keyframes,
+                Err(e) => {
+                    warn!("Ignoring keyframes in MineWars file: {}", e);
+                    None
+                }
+            };
             let mwreplay = MwReplay {
                 map: default(),
+                max_plid: mfr.is_header().max_plid(),
+                players,
                 raw_framedata: mfr.get_uncompressed_framedata()?,
+                keyframes,
             };
             Ok((mwmap, Some(mwreplay)))
         } else {
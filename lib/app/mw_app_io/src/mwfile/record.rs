@@ -1,7 +1,270 @@
-use crate::prelude::*;
+use std::io::Cursor;
 
-use super::*;
+use bevy::tasks::IoTaskPool;
+use mw_app_core::{driver::{DriverGovernor, GameOutEventSS, GameOverEvent, NeedsDriverGovernorSet}, map::{MapDataOrig, MapDescriptor, MapGovernor}, player::{Plid, PlidColor, SubPlid, SubPlidUserProfile}, session::{PlayersIndex, SessionGovernor}};
+use mw_dataformat::{players::{PlidInfo, SubPlidInfo}, write::{MwFrameBuilder, MwWriterError}};
 
+use crate::{prelude::*, settings::ReplaySettings};
+
+use super::{saver::{save_mwfile, MwFileSaverSettings}, *};
+
+pub fn plugin(app: &mut App) {
+    app.register_clicommand_args("save_replay", cli_save_replay);
+    app.add_systems(Update, (
+        record_gameevents
+            .in_set(SetStage::WantChanged(GameOutEventSS)),
+        autosave_replay
+            .in_set(SetStage::Want(GameOutEventSS))
+            .after(record_gameevents)
+            .run_if(on_event::<GameOverEvent>()),
+    )
+        .in_set(InStateSet(AppState::InGame))
+        .in_set(NeedsDriverGovernorSet)
+        .run_if(any_filter::<(With<GameRecorder>, With<DriverGovernor>)>)
+    );
+}
+
+/// Records all game events, so the session can be saved as a replay file
+///
+/// Insert this on the Driver Governor entity.
 #[derive(Component)]
 pub struct GameRecorder {
+    start: Option<Instant>,
+    events: Vec<(u64, GameEvent)>,
+    /// Save a replay file automatically when the game ends
+    pub autosave: bool,
+}
+
+impl GameRecorder {
+    pub fn new(autosave: bool) -> Self {
+        Self {
+            start: None,
+            events: Vec::new(),
+            autosave,
+        }
+    }
+
+    /// How many events have been recorded so far
+    pub fn n_events(&self) -> usize {
+        self.events.len()
+    }
+
+    /// Encode everything recorded so far as replay frame data
+    ///
+    /// Events are only encoded into the views of plids up to `max_plid`.
+    pub fn encode_replay(&self, max_plid: u8) -> Result<MwReplay, MwWriterError> {
+        let mut buf = Vec::new();
+        let mut out = Vec::new();
+        let mut b_frames = MwFrameBuilder::new(Cursor::new(&mut out), &mut buf, max_plid);
+        let mut msgs: Vec<Vec<MwEv>> = vec![Vec::new(); max_plid as usize + 1];
+        for events in self.events.chunk_by(|a, b| a.0 == b.0) {
+            let time_ms = events[0].0;
+            let mut plids = Plids::default();
+            msgs.iter_mut().for_each(|v| v.clear());
+            for (_, ev) in events {
+                for plid in ev.plids.iter(Some(max_plid)) {
+                    msgs[plid.i()].push(ev.ev.clone());
+                    plids += plid;
+                }
+            }
+            let msgs: Vec<&[MwEv]> = msgs.iter().map(|v| v.as_slice()).collect();
+            b_frames.append_msgs(time_ms, plids, &msgs)?;
+        }
+        Ok(MwReplay {
+            map: default(),
+            max_plid,
+            players: Vec::new(),
+            raw_framedata: out,
+            keyframes: None,
+        })
+    }
+}
+
+fn record_gameevents(
+    mut q_driver: Query<&mut GameRecorder, With<DriverGovernor>>,
+    mut evr: EventReader<GameEvent>,
+) {
+    let mut recorder = q_driver.single_mut();
+    let now = Instant::now();
+    let start = *recorder.start.get_or_insert(now);
+    let time_ms = (now - start).as_millis() as u64;
+    for ev in evr.read() {
+        recorder.events.push((time_ms, ev.clone()));
+    }
+}
+
+fn autosave_replay(
+    settings: Settings,
+    q_driver: Query<&GameRecorder, With<DriverGovernor>>,
+    q_session: Query<&PlayersIndex, With<SessionGovernor>>,
+    q_plid: Query<Option<&PlidColor>, With<Plid>>,
+    q_subplid: Query<&SubPlidUserProfile, With<SubPlid>>,
+    q_map: Query<(&MapDescriptor, &MapDataOrig), With<MapGovernor>>,
+) {
+    let recorder = q_driver.single();
+    if !recorder.autosave {
+        return;
+    }
+    let s_replay = settings.get::<ReplaySettings>().unwrap();
+    let path = new_replay_path(&s_replay.replay_dir);
+    let roster = players_roster(q_session.single(), &q_plid, &q_subplid);
+    save_replay(path, recorder, q_session.single(), roster, q_map.get_single().ok());
+}
+
+fn cli_save_replay(
+    In(args): In<Vec<String>>,
+    settings: Settings,
+    q_driver: Query<&GameRecorder, With<DriverGovernor>>,
+    q_session: Query<&PlayersIndex, With<SessionGovernor>>,
+    q_plid: Query<Option<&PlidColor>, With<Plid>>,
+    q_subplid: Query<&SubPlidUserProfile, With<SubPlid>>,
+    q_map: Query<(&MapDescriptor, &MapDataOrig), With<MapGovernor>>,
+) {
+    let Ok(recorder) = q_driver.get_single() else {
+        error!("Cannot save replay: no game is being recorded.");
+        return;
+    };
+    let path = if let Some(path) = args.first() {
+        PathBuf::from(path)
+    } else {
+        let s_replay = settings.get::<ReplaySettings>().unwrap();
+        new_replay_path(&s_replay.replay_dir)
+    };
+    let roster = players_roster(q_session.single(), &q_plid, &q_subplid);
+    save_replay(path, recorder, q_session.single(), roster, q_map.get_single().ok());
+}
+
+/// Collect the names and colors of everyone in the session, for the replay file
+fn players_roster(
+    players: &PlayersIndex,
+    q_plid: &Query<Option<&PlidColor>, With<Plid>>,
+    q_subplid: &Query<&SubPlidUserProfile, With<SubPlid>>,
+) -> Vec<PlidInfo> {
+    players.e_plid.iter().enumerate().map(|(i, e_plid)| {
+        let color = match q_plid.get(*e_plid) {
+            Ok(Some(color)) => {
+                let [r, g, b, _] = color.color.to_srgba().to_u8_array();
+                [r, g, b]
+            }
+            _ => [0, 0, 0],
+        };
+        let subplids = players.e_subplid.get(i)
+            .map(|e_subplids| e_subplids.iter().map(|e| SubPlidInfo {
+                display_name: q_subplid.get(*e)
+                    .map(|profile| profile.0.display_name.clone())
+                    .unwrap_or_default(),
+            }).collect())
+            .unwrap_or_default();
+        PlidInfo { color, subplids }
+    }).collect()
+}
+
+fn new_replay_path(dir: &str) -> PathBuf {
+    let timestamp = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    Path::new(dir).join(format!("replay-{}.minewars", timestamp))
+}
+
+fn save_replay(
+    path: PathBuf,
+    recorder: &GameRecorder,
+    players: &PlayersIndex,
+    roster: Vec<PlidInfo>,
+    map: Option<(&MapDescriptor, &MapDataOrig)>,
+) {
+    let Some((desc, orig)) = map else {
+        error!("Cannot save replay: no map currently active.");
+        return;
+    };
+    let max_plid = (players.e_plid.len() as u8).saturating_sub(1);
+    let mut mwreplay = match recorder.encode_replay(max_plid) {
+        Ok(mwreplay) => mwreplay,
+        Err(e) => {
+            error!("Cannot save replay: could not encode game events: {:#}", e);
+            return;
+        }
+    };
+    mwreplay.players = roster;
+    let mwmap = MwMap {
+        topology: desc.topology,
+        data: orig.clone(),
+    };
+    let settings = MwFileSaverSettings {
+        save_replay: true,
+        save_map_items: true,
+        compress_map: true,
+        compress_frames: true,
+    };
+    let rt = IoTaskPool::get();
+    rt.spawn(async move {
+        let r: AnyResult<()> = async {
+            if let Some(dir) = path.parent() {
+                async_fs::create_dir_all(dir).await?;
+            }
+            let mut file = async_fs::File::create(&path).await?;
+            save_mwfile(&mut file, &settings, &mwmap, None, Some(&mwreplay)).await?;
+            file.sync_all().await?;
+            Ok(())
+        }.await;
+        match r {
+            Ok(_) => info!("Replay saved to {:?}.", path),
+            Err(e) => error!("Could not save replay to {:?}: {:#}.", path, e),
+        };
+    }).detach();
+}
+
+#[cfg(test)]
+mod test {
+    use bevy::tasks::block_on;
+    use mw_app_core::map::MapTileDataOrig;
+    use mw_dataformat::read::{FrameKind, MwFileReader, MwFrameReader};
+
+    use super::*;
+
+    #[test]
+    fn recorded_replay_reads_back() {
+        let mut recorder = GameRecorder::new(false);
+        recorder.events.push((100, GameEvent {
+            plids: Plids::all(true),
+            ev: MwEv::Tremor,
+        }));
+        recorder.events.push((250, GameEvent {
+            plids: Plids::from(PlayerId::from(1)),
+            ev: MwEv::Smoke { pos: Pos(1, 2) },
+        }));
+        let mut mwreplay = recorder.encode_replay(1).unwrap();
+        mwreplay.players = vec![PlidInfo::default(), PlidInfo::default()];
+        let mwmap = MwMap {
+            topology: Topology::Hex,
+            data: MapDataOrig {
+                map: MapDataPos::new(3, MapTileDataOrig::default()),
+                cits: vec![Pos(0, 0)],
+            },
+        };
+        let mut out = Vec::new();
+        block_on(save_mwfile(&mut out, &MwFileSaverSettings::default(), &mwmap, None, Some(&mwreplay))).unwrap();
+
+        let mut buf = Vec::new();
+        let mut scratch = Vec::new();
+        let mut mfr = MwFileReader::new(Cursor::new(&out[..]), &mut buf).unwrap();
+        mfr.verify_checksums().unwrap();
+        let (mfr, mut isr) = mfr.read_is().unwrap();
+        assert_eq!(isr.read_cits_pos().unwrap(), &[Pos(0, 0)]);
+        assert_eq!(isr.read_players().unwrap().len(), 2);
+        let mfr = mfr.finish_is(isr).unwrap();
+        let MwFrameReader::Compressed(mut frames) = mfr.read_frames(Some(&mut scratch)).unwrap() else {
+            panic!("frames should be compressed");
+        };
+        frames.advance_next_frame().unwrap();
+        assert_eq!(frames.frame_kind(), FrameKind::Homogenous);
+        assert_eq!(frames.current_time_ms(), 100);
+        assert_eq!(frames.get_player_stream(PlayerId::from(0)).unwrap(), &[0b00000001]);
+        frames.advance_next_frame().unwrap();
+        assert_eq!(frames.current_time_ms(), 250);
+        assert!(!frames.contains_view(PlayerId::from(0)));
+        assert_eq!(frames.get_player_stream(PlayerId::from(1)).unwrap(), &[0b00000010, 1, 2]);
+        assert!(frames.advance_next_frame().is_err());
+    }
 }
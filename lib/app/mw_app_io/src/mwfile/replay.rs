@@ -0,0 +1,394 @@
+//! FileDriver: playback of game events from a replay file
+//!
+//! The frame data is decoded upfront into `GameEvent`s, which are then
+//! sent out on the original timeline (as far as playback controls allow).
+//!
+//! Every plid (including the spectator) gets its own view, built from
+//! that plid's stream in the replay, so the user can switch between the
+//! points of view of the different players while watching.
+//!
+//! If the file has keyframes, seeking backwards restores the views from
+//! the nearest keyframe and continues from there. Otherwise, the session
+//! has to be reloaded, to replay everything from the start.
+
+use std::io::{Cursor, ErrorKind};
+
+use mw_app_core::{driver::{DriverGovernor, GameOutEventSS, GameOverEvent, NeedsDriverGovernorSet}, map::{MapDataOrig, MapGovernor}, session::{PlayersIndex, PlidViewing, SessionGovernor}, view::{ViewBundle, ViewMapData}};
+use mw_dataformat::{keyframe::MwViewSnapshot, msg::{bin::{MsgBinRead, MsgBinReadError}, MsgReader}, read::{FrameKind, MwFrameDataReader, MwReaderError}};
+
+use crate::prelude::*;
+
+use super::*;
+
+pub fn plugin(app: &mut App) {
+    app.register_clicommand_noargs("replay_pause", cli_replay_pause);
+    app.register_clicommand_noargs("replay_resume", cli_replay_resume);
+    app.register_clicommand_noargs("replay_step", cli_replay_step);
+    app.register_clicommand_args("replay_speed", cli_replay_speed);
+    app.register_clicommand_args("replay_seek", cli_replay_seek);
+    app.add_systems(
+        OnEnter(AppState::GameLoading),
+        setup_replay_views
+            .run_if(any_filter::<(With<ReplayDriver>, With<DriverGovernor>)>)
+    );
+    app.add_systems(Update,
+        update_replay
+            .in_set(InStateSet(AppState::InGame))
+            .in_set(SetStage::Provide(GameOutEventSS))
+            .in_set(NeedsDriverGovernorSet)
+            .run_if(any_filter::<(With<ReplayDriver>, With<DriverGovernor>)>)
+    );
+}
+
+#[derive(Debug, Error)]
+pub enum ReplayDecodeError {
+    #[error("Cannot decode frames: {0}")]
+    Frames(#[from] MwReaderError),
+    #[error("Cannot decode game update messages: {0}")]
+    Msgs(#[from] MsgBinReadError),
+}
+
+/// Driver implementation for replaying a recorded game
+///
+/// Insert this on the Driver Governor entity.
+#[derive(Component)]
+pub struct ReplayDriver {
+    frames: Vec<ReplayFrame>,
+    keyframes: Vec<ReplayKeyframe>,
+    next_frame: usize,
+    position_ms: f64,
+    paused: bool,
+    speed: f32,
+    step: bool,
+    seek_to: Option<u64>,
+    needs_reset: bool,
+    restore_keyframe: Option<usize>,
+    finished: bool,
+}
+
+/// All the game events that happen at a given time
+pub struct ReplayFrame {
+    pub time_ms: u64,
+    pub events: Vec<GameEvent>,
+}
+
+/// A point in the replay that we can seek back to, without starting over
+struct ReplayKeyframe {
+    /// The views include all frames up to (and including) this time
+    time_ms: u64,
+    /// Index of the first `ReplayFrame` not included in the views
+    next_frame: usize,
+    views: Vec<MwViewSnapshot>,
+}
+
+impl ReplayDriver {
+    pub fn new(mwreplay: &MwReplay) -> Result<Self, ReplayDecodeError> {
+        let frames = decode_replay_frames(mwreplay)?;
+        let keyframes = match prepare_replay_keyframes(mwreplay, &frames) {
+            Ok(keyframes) => keyframes,
+            Err(e) => {
+                warn!("Cannot use replay keyframes: {}", e);
+                Vec::new()
+            }
+        };
+        Ok(Self {
+            frames,
+            keyframes,
+            next_frame: 0,
+            position_ms: 0.0,
+            paused: false,
+            speed: 1.0,
+            step: false,
+            seek_to: None,
+            needs_reset: false,
+            restore_keyframe: None,
+            finished: false,
+        })
+    }
+    pub fn frames(&self) -> &[ReplayFrame] {
+        &self.frames
+    }
+    /// Current playback position
+    pub fn position_ms(&self) -> u64 {
+        self.position_ms as u64
+    }
+    /// Total length of the replay
+    pub fn duration_ms(&self) -> u64 {
+        self.frames.last().map(|f| f.time_ms).unwrap_or(0)
+    }
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+    pub fn set_paused(&mut self, paused: bool) {
+        self.paused = paused;
+    }
+    pub fn speed(&self) -> f32 {
+        self.speed
+    }
+    /// Set the playback speed multiplier (1.0 is realtime)
+    pub fn set_speed(&mut self, speed: f32) {
+        self.speed = speed.max(0.0);
+    }
+    /// Pause and advance to the next frame
+    pub fn step_frame(&mut self) {
+        self.paused = true;
+        self.step = true;
+    }
+    /// Jump to the given playback position
+    ///
+    /// Seeking backwards restores the views from the last keyframe
+    /// before `time_ms`. If there is none, it requires resetting all
+    /// game state and replaying from the start, so it will reload
+    /// the session.
+    pub fn seek(&mut self, time_ms: u64) {
+        if (time_ms as f64) < self.position_ms {
+            let i = self.keyframes.partition_point(|k| k.time_ms <= time_ms);
+            if let Some(i) = i.checked_sub(1) {
+                self.next_frame = self.keyframes[i].next_frame;
+                self.position_ms = self.keyframes[i].time_ms as f64;
+                self.restore_keyframe = Some(i);
+                self.needs_reset = false;
+            } else {
+                self.next_frame = 0;
+                self.position_ms = 0.0;
+                self.restore_keyframe = None;
+                self.needs_reset = true;
+            }
+            self.finished = false;
+        }
+        self.seek_to = Some(time_ms);
+    }
+    /// Send events for all frames up to the current position
+    fn emit_frames(&mut self, evw: &mut EventWriter<GameEvent>) {
+        while let Some(frame) = self.frames.get(self.next_frame) {
+            if frame.time_ms as f64 > self.position_ms {
+                break;
+            }
+            evw.send_batch(frame.events.iter().cloned());
+            self.next_frame += 1;
+        }
+    }
+}
+
+/// Decode all the frames in a replay into `GameEvent`s
+///
+/// Consecutive frames with the same timestamp are merged.
+/// Messages that are identical across multiple views are
+/// combined into a single event.
+pub fn decode_replay_frames(mwreplay: &MwReplay) -> Result<Vec<ReplayFrame>, ReplayDecodeError> {
+    let mut buf = Vec::new();
+    let mut frames: Vec<ReplayFrame> = Vec::new();
+    let mut msgs = Vec::new();
+    let mut reader = MwFrameDataReader::new(
+        Cursor::new(mwreplay.raw_framedata()), &mut buf, mwreplay.max_plid()
+    )?;
+    loop {
+        match reader.advance_next_frame() {
+            Ok(()) => {}
+            Err(MwReaderError::Io(e)) if e.kind() == ErrorKind::UnexpectedEof => break,
+            Err(e) => return Err(e.into()),
+        }
+        if reader.frame_kind() == FrameKind::Keepalive {
+            continue;
+        }
+        let time_ms = reader.current_time_ms();
+        if frames.last().map(|f| f.time_ms) != Some(time_ms) {
+            frames.push(ReplayFrame {
+                time_ms,
+                events: Vec::new(),
+            });
+        }
+        let frame = frames.last_mut().unwrap();
+        let i_first_event = frame.events.len();
+        for (i, stream) in reader.iter_streams().enumerate() {
+            let mut stream = stream?;
+            if stream.is_empty() {
+                continue;
+            }
+            let plid = PlayerId::from(i as u8);
+            msgs.clear();
+            MsgBinRead::new().read_all(&mut stream, &mut msgs)?;
+            for ev in msgs.drain(..) {
+                let existing = frame.events[i_first_event..].iter_mut()
+                    .find(|e| e.ev == ev && !e.plids.contains(plid));
+                if let Some(existing) = existing {
+                    existing.plids += plid;
+                } else {
+                    frame.events.push(GameEvent {
+                        plids: plid.into(),
+                        ev,
+                    });
+                }
+            }
+        }
+    }
+    Ok(frames)
+}
+
+/// Find the keyframes of a replay that can be used for seeking
+///
+/// A keyframe is only usable if it falls between two `ReplayFrame`s.
+/// If the frame right after it has the same timestamp as the one
+/// before it, it would split a `ReplayFrame` (because they are merged
+/// by time), so it is skipped.
+///
+/// It must also have a view for every plid. Otherwise, restoring it
+/// would leave the other views as they were before seeking, so it is
+/// skipped too (and seeking falls back to an earlier one, or reloading).
+fn prepare_replay_keyframes(
+    mwreplay: &MwReplay,
+    frames: &[ReplayFrame],
+) -> Result<Vec<ReplayKeyframe>, MwReaderError> {
+    let Some(keyframes) = mwreplay.keyframes() else {
+        return Ok(Vec::new());
+    };
+    let mut buf = Vec::new();
+    let mut reader = MwFrameDataReader::new(
+        Cursor::new(mwreplay.raw_framedata()), &mut buf, mwreplay.max_plid()
+    )?;
+    let mut r = Vec::new();
+    for keyframe in keyframes.keyframes() {
+        let time_ms = keyframe.entry.time_ms;
+        let covers_all = (0..=mwreplay.max_plid())
+            .all(|i| keyframe.get_view(PlayerId::from(i)).is_some());
+        if !covers_all {
+            continue;
+        }
+        reader.seek_to_keyframe(keyframe);
+        match reader.advance_next_frame() {
+            Ok(()) => {}
+            Err(MwReaderError::Io(e)) if e.kind() == ErrorKind::UnexpectedEof => continue,
+            Err(e) => return Err(e),
+        }
+        if reader.current_time_ms() <= time_ms {
+            continue;
+        }
+        r.push(ReplayKeyframe {
+            time_ms,
+            next_frame: frames.partition_point(|f| f.time_ms <= time_ms),
+            views: keyframe.views.clone(),
+        });
+    }
+    Ok(r)
+}
+
+/// (Re)create a fresh view for every plid in the replay
+///
+/// This also runs when the session is reloaded on a backwards seek,
+/// so that the views get rebuilt from the start.
+fn setup_replay_views(
+    mut commands: Commands,
+    q_session: Query<&PlayersIndex, With<SessionGovernor>>,
+    q_map: Query<&MapDataOrig, With<MapGovernor>>,
+) {
+    let (Ok(players), Ok(orig)) = (q_session.get_single(), q_map.get_single()) else {
+        return;
+    };
+    for (i, e_plid) in players.e_plid.iter().enumerate() {
+        commands.entity(*e_plid).insert(ViewBundle {
+            mapdata: ViewMapData::from_map_orig(orig, PlayerId::from(i as u8)),
+        });
+    }
+}
+
+fn update_replay(
+    time: Res<Time>,
+    mut q_driver: Query<&mut ReplayDriver, With<DriverGovernor>>,
+    mut q_session: Query<(&PlayersIndex, &mut PlidViewing), With<SessionGovernor>>,
+    mut q_view: Query<&mut ViewMapData>,
+    mut evw: EventWriter<GameEvent>,
+    mut evw_gameover: EventWriter<GameOverEvent>,
+    mut state: ResMut<NextState<AppState>>,
+) {
+    let mut driver = q_driver.single_mut();
+    if driver.needs_reset {
+        // reload the session, to start over with clean game state
+        driver.needs_reset = false;
+        state.set(AppState::GameLoading);
+        return;
+    }
+    if let Some(i) = driver.restore_keyframe.take() {
+        let (players, mut viewing) = q_session.single_mut();
+        for view in driver.keyframes[i].views.iter() {
+            let Some(e_plid) = players.e_plid.get(view.plid.i()) else {
+                continue;
+            };
+            if let Ok(mut mapdata) = q_view.get_mut(*e_plid) {
+                mapdata.0 = view.map.clone();
+            }
+        }
+        // rebuild the map tiles from the restored view
+        viewing.set_changed();
+    }
+    if let Some(time_ms) = driver.seek_to.take() {
+        driver.position_ms = time_ms as f64;
+    } else if driver.step {
+        if let Some(frame) = driver.frames.get(driver.next_frame) {
+            driver.position_ms = frame.time_ms as f64;
+        }
+    } else if !driver.paused {
+        let speed = driver.speed as f64;
+        driver.position_ms += time.delta_seconds_f64() * 1000.0 * speed;
+    }
+    driver.step = false;
+    driver.emit_frames(&mut evw);
+    if !driver.finished && driver.next_frame >= driver.frames.len() {
+        info!("Replay finished.");
+        driver.finished = true;
+        evw_gameover.send(GameOverEvent);
+    }
+}
+
+fn cli_replay_pause(
+    mut q_driver: Query<&mut ReplayDriver, With<DriverGovernor>>,
+) {
+    if let Ok(mut driver) = q_driver.get_single_mut() {
+        driver.set_paused(true);
+    }
+}
+
+fn cli_replay_resume(
+    mut q_driver: Query<&mut ReplayDriver, With<DriverGovernor>>,
+) {
+    if let Ok(mut driver) = q_driver.get_single_mut() {
+        driver.set_paused(false);
+    }
+}
+
+fn cli_replay_step(
+    mut q_driver: Query<&mut ReplayDriver, With<DriverGovernor>>,
+) {
+    if let Ok(mut driver) = q_driver.get_single_mut() {
+        driver.step_frame();
+    }
+}
+
+fn cli_replay_speed(
+    In(args): In<Vec<String>>,
+    mut q_driver: Query<&mut ReplayDriver, With<DriverGovernor>>,
+) {
+    let Ok(mut driver) = q_driver.get_single_mut() else {
+        error!("No replay is being played.");
+        return;
+    };
+    let Some(Ok(speed)) = args.first().map(|s| s.parse::<f32>()) else {
+        error!("Please specify the playback speed multiplier!");
+        return;
+    };
+    driver.set_speed(speed);
+}
+
+fn cli_replay_seek(
+    In(args): In<Vec<String>>,
+    mut q_driver: Query<&mut ReplayDriver, With<DriverGovernor>>,
+) {
+    let Ok(mut driver) = q_driver.get_single_mut() else {
+        error!("No replay is being played.");
+        return;
+    };
+    let Some(Ok(secs)) = args.first().map(|s| s.parse::<f64>()) else {
+        error!("Please specify the time to seek to (in seconds)!");
+        return;
+    };
+    driver.seek((secs.max(0.0) * 1000.0) as u64);
+}
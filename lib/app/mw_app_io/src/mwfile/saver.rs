@@ -3,6 +3,7 @@ use std::io::Cursor;
 use bevy::{asset::{saver::AssetSaver, AsyncWriteExt}, tasks::futures_lite::AsyncWrite};
 use loader::MwFileLoaderSettings;
 use mw_app_core::map::MapTileDataOrig;
+use mw_common::game::MwRules;
 use mw_dataformat::write::{MwFileBuilder, MwWriterError};
 
 use crate::prelude::*;
@@ -58,7 +59,7 @@ impl AssetSaver for MwFileSaver {
             .get();
         let mwreplay = asset.get_labeled::<MwReplay, _>("Replay")
             .map(|x| x.get());
-        save_mwfile(writer, settings, mwmap, mwreplay).await?;
+        save_mwfile(writer, settings, mwmap, None, mwreplay).await?;
         let out_settings = loader::MwFileLoaderSettings {
             load_replay: settings.save_replay,
             load_map_items: settings.save_map_items,
@@ -72,13 +73,20 @@ pub async fn save_mwfile(
     writer: &mut (dyn AsyncWrite + Unpin + Send + Sync),
     settings: &MwFileSaverSettings,
     mwmap: &MwMap,
+    rules: Option<&MwRules>,
     mut mwreplay: Option<&MwReplay>,
 ) -> Result<(), MwFileSaverError> {
+    if !settings.save_replay {
+        mwreplay = None;
+    };
     let mut scratch = Vec::new();
     let mut buf = Vec::new();
     let mut out = Vec::new();
-    let (b_file, b_is) = MwFileBuilder::new(Cursor::new(&mut out), &mut buf)?
+    let (b_file, mut b_is) = MwFileBuilder::new(Cursor::new(&mut out), &mut buf)?
         .start_is()?;
+    if let Some(mwreplay) = mwreplay {
+        b_is = b_is.with_max_plid(mwreplay.max_plid, 0);
+    }
     let b_is = match mwmap.topology {
         Topology::Hex => {
             let map: MapDataC<Hex, MapTileDataOrig> = mwmap.data.map.clone().rekey();
@@ -98,10 +106,15 @@ pub async fn save_mwfile(
         }
     };
     let b_is = b_is.with_cits(mwmap.data.cits.iter().map(|pos| (*pos, [].as_slice())))?;
-    // TODO: rules
-    // TODO: players
-    if !settings.save_replay {
-        mwreplay = None;
+    let b_is = if let Some(rules) = rules {
+        b_is.with_rules(rules)?
+    } else {
+        b_is.without_rules()?
+    };
+    let b_is = if let Some(mwreplay) = mwreplay {
+        b_is.with_players(&mwreplay.players)?
+    } else {
+        b_is.without_players()?
     };
     if let Some(mwreplay) = mwreplay {
         if settings.compress_frames {
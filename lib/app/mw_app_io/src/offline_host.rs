@@ -1,8 +1,8 @@
 use std::collections::BTreeMap;
 
 use async_channel::{Receiver, Sender, TryRecvError};
-use bevy::tasks::{block_on, poll_once, AsyncComputeTaskPool, Task};
-use mw_app_core::{driver::{DriverGovernor, GameOutEventSS, NeedsDriverGovernorSet}, session::{NeedsSessionGovernorSet, PlidPlayingAs, SessionGovernor}};
+use bevy::tasks::{block_on, poll_once, AsyncComputeTaskPool, IoTaskPool, Task};
+use mw_app_core::{driver::{DriverGovernor, GameOutEventSS, GameOverEvent, NeedsDriverGovernorSet}, session::{NeedsSessionGovernorSet, PlidPlayingAs, SessionGovernor, SubPlidPlayingAs}};
 use mw_common::driver::*;
 
 use crate::prelude::*;
@@ -49,15 +49,30 @@ where
 }
 
 #[derive(Component)]
-pub struct OfflineHost<G: Game>(OfflineHostState<G>);
+pub struct OfflineHost<G: Game> {
+    state: OfflineHostState<G>,
+    paused: bool,
+    save_requests: Vec<(PathBuf, SaveFn<G>)>,
+}
+
+type SaveFn<G> = fn(
+    &G,
+    Instant,
+    &[(Instant, <<G as Game>::Io as GameIo>::SchedEvent)],
+) -> AnyResult<Vec<u8>>;
+type RestoreFn<G> = fn(&mut HostState<G>, &[u8]) -> AnyResult<G>;
 
 enum OfflineHostState<G: Game> {
     NotInitialized {
         game: G,
         init_data: Box<G::InitData>,
     },
+    NotRestored {
+        data: Vec<u8>,
+        restore: RestoreFn<G>,
+    },
     Initializing {
-        task: Task<(G, HostState<G>)>,
+        task: Task<AnyResult<(G, HostState<G>)>>,
     },
     Initialized {
         game: G,
@@ -68,6 +83,7 @@ enum OfflineHostState<G: Game> {
         rx: Receiver<TaskOut<G>>,
         task: Task<()>,
         next_sched: Option<Instant>,
+        paused: bool,
     },
     GameOver,
 }
@@ -75,9 +91,16 @@ enum OfflineHostState<G: Game> {
 enum TaskIn<G: Game> {
     GameInput {
         plid: PlayerId,
+        subplid: u8,
         event: <G::Io as GameIo>::InputAction,
     },
     SchedTrigger(Instant),
+    Pause(Instant),
+    Resume(Instant),
+    Save {
+        path: PathBuf,
+        save: SaveFn<G>,
+    },
     Maintain,
 }
 
@@ -85,19 +108,60 @@ enum TaskOut<G: Game> {
     GameOutput(GameOutput<G::Io>),
     NextSched(Instant),
     NoSchedsRemain,
+    Saved {
+        path: PathBuf,
+        result: AnyResult<Vec<u8>>,
+    },
     GameOver,
 }
 
 impl<G: Game> OfflineHost<G> {
     pub fn new(game: G, init_data: Box<G::InitData>) -> Self {
-        Self(OfflineHostState::NotInitialized { game, init_data })
+        Self {
+            state: OfflineHostState::NotInitialized { game, init_data },
+            paused: false,
+            save_requests: vec![],
+        }
+    }
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+    /// Pause or resume the game
+    ///
+    /// While paused, the game's clock is stopped (anything it has
+    /// scheduled is delayed accordingly) and player inputs are ignored.
+    pub fn set_paused(&mut self, paused: bool) {
+        self.paused = paused;
     }
 }
 
+impl<G: GameSnapshot> OfflineHost<G> {
+    /// Continue a game from a snapshot, instead of initializing a new game
+    ///
+    /// `data` is what was written to a file by `save`.
+    pub fn restore(data: Vec<u8>) -> Self {
+        Self {
+            state: OfflineHostState::NotRestored { data, restore: restore_game::<G> },
+            paused: false,
+            save_requests: vec![],
+        }
+    }
+    /// Save a snapshot of the full game state to a file
+    ///
+    /// Happens in the background, as soon as the game is running.
+    pub fn save(&mut self, path: PathBuf) {
+        self.save_requests.push((path, G::save_state));
+    }
+}
+
+fn restore_game<G: GameSnapshot>(host: &mut HostState<G>, data: &[u8]) -> AnyResult<G> {
+    G::restore_state(host, data)
+}
+
 fn init_offline_game<G: Game>(
     mut q_driver: Query<&mut OfflineHost<G>, With<DriverGovernor>>,
 ) -> Progress {
-    let state = &mut q_driver.single_mut().0;
+    let state = &mut q_driver.single_mut().state;
     let temp = std::mem::replace(state, OfflineHostState::GameOver);
     let r;
     *state = match temp {
@@ -106,20 +170,39 @@ fn init_offline_game<G: Game>(
             let mut host = HostState::<G>::default();
             let task = rt.spawn(async move {
                 game.init(&mut host, init_data);
-                (game, host)
+                Ok((game, host))
             });
             info!("Offline game initializing.");
             r = false.into();
             OfflineHostState::Initializing { task }
         }
+        OfflineHostState::NotRestored { data, restore } => {
+            let rt = AsyncComputeTaskPool::get();
+            let mut host = HostState::<G>::default();
+            let task = rt.spawn(async move {
+                let game = restore(&mut host, &data)?;
+                Ok((game, host))
+            });
+            info!("Offline game restoring from snapshot.");
+            r = false.into();
+            OfflineHostState::Initializing { task }
+        }
         OfflineHostState::Initializing { mut task } => {
-            if let Some((game, host)) = block_on(poll_once(&mut task)) {
-                info!("Offline game initialized.");
-                r = true.into();
-                OfflineHostState::Initialized { game, host }
-            } else {
-                r = false.into();
-                OfflineHostState::Initializing { task }
+            match block_on(poll_once(&mut task)) {
+                Some(Ok((game, host))) => {
+                    info!("Offline game initialized.");
+                    r = true.into();
+                    OfflineHostState::Initialized { game, host }
+                }
+                Some(Err(e)) => {
+                    error!("Offline game could not be initialized: {:#}", e);
+                    r = true.into();
+                    OfflineHostState::GameOver
+                }
+                None => {
+                    r = false.into();
+                    OfflineHostState::Initializing { task }
+                }
             }
         }
         s => {
@@ -131,23 +214,50 @@ fn init_offline_game<G: Game>(
 }
 
 fn update_offline_game<G: Game, EIn, EOut>(
-    q_session: Query<&PlidPlayingAs, With<SessionGovernor>>,
+    q_session: Query<(&PlidPlayingAs, Option<&SubPlidPlayingAs>), With<SessionGovernor>>,
     mut q_driver: Query<&mut OfflineHost<G>, With<DriverGovernor>>,
     mut evr_in: EventReader<EIn>,
     mut evw_out: EventWriter<EOut>,
+    mut evw_gameover: EventWriter<GameOverEvent>,
 )
 where
     EIn: Event + Clone + Into<<G::Io as GameIo>::InputAction>,
     EOut: Event + From<(Plids, <G::Io as GameIo>::OutEvent)>,
 {
-    let plid = q_session.single().0;
-    let state = &mut q_driver.single_mut().0;
+    let (plid, subplid) = q_session.single();
+    let plid = plid.0;
+    let subplid = subplid.map(|s| s.0).unwrap_or(0);
+    let mut offline_host = q_driver.single_mut();
+    let offline_host = &mut *offline_host;
+    let state = &mut offline_host.state;
     let temp = std::mem::replace(state, OfflineHostState::GameOver);
     *state = match temp {
         OfflineHostState::GameOver => OfflineHostState::GameOver,
-        OfflineHostState::Running { tx, rx, task, mut next_sched } => {
+        OfflineHostState::Running { tx, rx, task, mut next_sched, mut paused } => {
             let mut game_over = false;
-            if evr_in.is_empty() {
+            if paused != offline_host.paused {
+                paused = offline_host.paused;
+                let msg = if paused {
+                    info!("Offline game paused.");
+                    TaskIn::Pause(Instant::now())
+                } else {
+                    info!("Offline game resumed.");
+                    TaskIn::Resume(Instant::now())
+                };
+                if tx.try_send(msg).is_err() {
+                    game_over = true;
+                }
+                // will be recomputed by the task
+                next_sched = None;
+            }
+            for (path, save) in offline_host.save_requests.drain(..) {
+                if tx.try_send(TaskIn::Save { path, save }).is_err() {
+                    game_over = true;
+                }
+            }
+            if paused {
+                evr_in.clear();
+            } else if evr_in.is_empty() {
                 if let Some(time) = &next_sched {
                     let now = Instant::now();
                     if now >= *time {
@@ -159,7 +269,7 @@ where
                 }
             }
             for ev in evr_in.read() {
-                if tx.try_send(TaskIn::GameInput { plid, event: ev.clone().into()}).is_err() {
+                if tx.try_send(TaskIn::GameInput { plid, subplid, event: ev.clone().into()}).is_err() {
                     game_over = true;
                 }
             }
@@ -175,6 +285,9 @@ where
                         TaskOut::NextSched(time)  => {
                             next_sched = Some(time);
                         }
+                        TaskOut::Saved { path, result } => {
+                            save_snapshot_file(path, result);
+                        }
                         TaskOut::GameOver => {
                             game_over = true;
                         }
@@ -189,10 +302,12 @@ where
                 }
             }
             if game_over {
+                info!("Offline game over.");
+                evw_gameover.send(GameOverEvent);
                 OfflineHostState::GameOver
             } else {
                 OfflineHostState::Running {
-                    tx, rx, task, next_sched,
+                    tx, rx, task, next_sched, paused,
                 }
             }
         }
@@ -209,17 +324,46 @@ where
                 rx: rx_out,
                 task,
                 next_sched: None,
+                paused: false,
             }
         }
         _ => panic!("Offline Game Not Initialized!")
     }
 }
 
+fn save_snapshot_file(path: PathBuf, result: AnyResult<Vec<u8>>) {
+    let data = match result {
+        Ok(data) => data,
+        Err(e) => {
+            error!("Cannot save game: {:#}", e);
+            return;
+        }
+    };
+    let rt = IoTaskPool::get();
+    rt.spawn(async move {
+        let r: AnyResult<()> = async {
+            if let Some(dir) = path.parent() {
+                async_fs::create_dir_all(dir).await?;
+            }
+            async_fs::write(&path, &data).await?;
+            Ok(())
+        }.await;
+        match r {
+            Ok(_) => info!("Game saved to {:?}.", path),
+            Err(e) => error!("Could not save game to {:?}: {:#}.", path, e),
+        };
+    }).detach();
+}
+
 struct HostState<G: Game> {
     events: Vec<GameOutput<G::Io>>,
     scheds: BTreeMap<Instant, <G::Io as GameIo>::SchedEvent>,
     cancel: HashSet<<G::Io as GameIo>::SchedEvent>,
     game_over: bool,
+    /// When the game was paused, if it is paused now
+    paused_at: Option<Instant>,
+    /// How long the game has spent paused, in total
+    paused_total: Duration,
 }
 
 impl<G: Game> Default for HostState<G> {
@@ -229,6 +373,8 @@ impl<G: Game> Default for HostState<G> {
             scheds: default(),
             cancel: default(),
             game_over: false,
+            paused_at: None,
+            paused_total: Duration::ZERO,
         }
     }
 }
@@ -238,7 +384,8 @@ impl<G: Game> Host<G::Io> for HostState<G> {
         self.events.push(output);
     }
     fn sched(&mut self, time: Instant, event: <G::Io as GameIo>::SchedEvent) {
-        self.scheds.insert(time, event);
+        // our scheds are kept in real time
+        self.scheds.insert(time + self.paused_total, event);
     }
     fn desched_all(&mut self, event: <G::Io as GameIo>::SchedEvent) {
         self.cancel.insert(event);
@@ -246,9 +393,36 @@ impl<G: Game> Host<G::Io> for HostState<G> {
     fn game_over(&mut self) {
         self.game_over = true;
     }
+    /// The game's clock does not count the time spent paused
+    fn now(&self) -> Instant {
+        self.paused_at.unwrap_or_else(Instant::now) - self.paused_total
+    }
 }
 
 impl<G: Game> HostState<G> {
+    fn pause(&mut self, time: Instant) {
+        if self.paused_at.is_none() {
+            self.paused_at = Some(time);
+        }
+    }
+    /// Shift all pending scheds later, by however long we were paused
+    fn resume(&mut self, time: Instant) {
+        let Some(paused_at) = self.paused_at.take() else {
+            return;
+        };
+        let paused_for = time.saturating_duration_since(paused_at);
+        self.paused_total += paused_for;
+        self.scheds = std::mem::take(&mut self.scheds)
+            .into_iter()
+            .map(|(time, ev)| (time + paused_for, ev))
+            .collect();
+    }
+    /// Pending scheds, as seen by the game's clock
+    fn game_scheds(&self) -> Vec<(Instant, <G::Io as GameIo>::SchedEvent)> {
+        self.scheds.iter()
+            .map(|(time, ev)| (*time - self.paused_total, ev.clone()))
+            .collect()
+    }
     fn maintain_scheds(&mut self) -> TaskOut<G> {
         if !self.cancel.is_empty() {
             self.scheds
@@ -295,10 +469,10 @@ async fn task_host_game<G: Game>(
                     break 'main;
                 };
             }
-            TaskIn::GameInput { plid, event }  => {
+            TaskIn::GameInput { plid, subplid, event }  => {
                 let input = GameInput {
                     plid,
-                    subplid: 0, // FIXME
+                    subplid,
                     input: event,
                 };
                 game.input(&mut host, input);
@@ -318,6 +492,21 @@ async fn task_host_game<G: Game>(
                     game.maintain();
                 }
             }
+            TaskIn::Pause(time) => {
+                host.pause(time);
+            }
+            TaskIn::Resume(time) => {
+                host.resume(time);
+                let Ok(_) = tx.send(host.maintain_scheds()).await else {
+                    break 'main;
+                };
+            }
+            TaskIn::Save { path, save } => {
+                let result = save(&game, host.now(), &host.game_scheds());
+                let Ok(_) = tx.send(TaskOut::Saved { path, result }).await else {
+                    break 'main;
+                };
+            }
             TaskIn::Maintain => {
                 for out in host.events.drain(..) {
                     let Ok(_) = tx.send(TaskOut::GameOutput (out)).await else {
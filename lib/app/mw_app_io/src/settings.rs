@@ -4,6 +4,31 @@ use crate::prelude::*;
 
 pub fn plugin(app: &mut App) {
     // app.init_setting::<NetworkingSettings>(SETTINGS_LOCAL.as_ref());
+    app.init_setting::<ReplaySettings>(SETTINGS_LOCAL.as_ref());
+}
+
+#[derive(Reflect, Debug, Clone)]
+#[reflect(Setting)]
+pub struct ReplaySettings {
+    /// Record game sessions and save a replay file when the game ends
+    ///
+    /// Off by default, so that games do not litter the filesystem.
+    pub autosave_replays: bool,
+    /// Where to save automatically-recorded replay files
+    ///
+    /// Relative paths are relative to the current working directory.
+    pub replay_dir: String,
+}
+
+impl Setting for ReplaySettings {}
+
+impl Default for ReplaySettings {
+    fn default() -> Self {
+        ReplaySettings {
+            autosave_replays: false,
+            replay_dir: "replays".into(),
+        }
+    }
 }
 
 // #[derive(Reflect, Clone, PartialEq)]
@@ -1,29 +1,106 @@
+use mw_app_core::{assets::SpritesAssets, player::{Plid, PlidStanding, SubPlidUserProfile}, session::{NeedsSessionGovernorSet, PlayersIndex, SessionGovernor}};
 use mw_ui_common::root::spawn_root;
 
-use crate::prelude::*;
+use crate::{assets::UiAssets, prelude::*, settings::DesktopUiSettings};
 
 pub fn plugin(app: &mut App) {
+    app.add_systems(Update,
+        spawn_results_scoreboard
+            .run_if(any_filter::<Changed<PlidStanding>>)
+            .in_set(NeedsSessionGovernorSet)
+    );
 }
 
-fn spawn_full_scoreboard(
+/// Marker for the UI root containing the scoreboard
+#[derive(Component)]
+struct FullScoreboard;
+
+/// At the end of the game, show everyone's final results
+///
+/// Standings may arrive over multiple frames (one event per plid),
+/// so the scoreboard is rebuilt whenever any of them change.
+fn spawn_results_scoreboard(
     mut commands: Commands,
+    settings: Settings,
+    assets_spr: Res<SpritesAssets>,
+    assets_ui: Res<UiAssets>,
+    q_session: Query<&PlayersIndex, With<SessionGovernor>>,
+    q_plid: Query<(Entity, &Plid, &PlidStanding)>,
+    q_subplid: Query<&SubPlidUserProfile>,
+    q_scoreboard: Query<Entity, With<FullScoreboard>>,
 ) {
-    let e_root = spawn_root(&mut commands, Style {
+    for e in &q_scoreboard {
+        commands.entity(e).despawn_recursive();
+    }
+    let s_ui = settings.get::<DesktopUiSettings>().unwrap();
+    let players = q_session.single();
+    let text_style = TextStyle {
+        font: assets_ui.font.clone(),
+        font_size: 24.0,
+        color: Color::WHITE,
+    };
+    let mut standings: Vec<_> = q_plid.iter().collect();
+    standings.sort_by_key(|(_, plid, standing)| (standing.rank, plid.0.i()));
+    let e_scoreboard = spawn_full_scoreboard(&mut commands);
+    for (row, (e_plid, plid, standing)) in standings.into_iter().enumerate() {
+        let e_plid_icon = super::spawn_plid_icon(
+            &mut commands, &assets_spr, e_plid, plid.0,
+            s_ui.mini_scoreboard_settings.icon_size,
+        );
+        let mut stats = format!(
+            "#{}: {} tiles, {} lives",
+            standing.rank, standing.tiles, standing.lives,
+        );
+        if standing.elim_order != 0 {
+            stats.push_str(&format!(
+                ", eliminated at {}:{:02}",
+                standing.elim_secs / 60, standing.elim_secs % 60,
+            ));
+        }
+        let e_plid_stats = commands.spawn((
+            TextBundle::from_section(stats, text_style.clone()),
+        )).id();
+        let names: Vec<String> = players.e_subplid.get(plid.0.i())
+            .map(|e_subplids| e_subplids.iter()
+                .filter_map(|e| q_subplid.get(*e).ok())
+                .map(|profile| profile.0.display_name.clone())
+                .collect())
+            .unwrap_or_default();
+        let e_subplid_info = commands.spawn((
+            TextBundle::from_section(names.join(", "), text_style.clone()),
+        )).id();
+        spawn_scoreboard_row(
+            &mut commands, e_scoreboard,
+            e_plid_icon, e_plid_stats, e_subplid_info,
+            row as i16 + 1,
+        );
+    }
+}
+
+fn spawn_full_scoreboard(
+    commands: &mut Commands,
+) -> Entity {
+    let e_root = spawn_root(commands, Style {
         flex_direction: FlexDirection::Column,
         justify_content: JustifyContent::Center,
         align_items: AlignItems::Center,
         ..Default::default()
     });
+    commands.entity(e_root).insert((GameFullCleanup, FullScoreboard));
     let e_scoreboard = commands.spawn((
         NodeBundle {
             style: Style {
                 display: bevy::ui::Display::Grid,
+                column_gap: Val::Px(16.0),
+                row_gap: Val::Px(8.0),
                 ..Default::default()
             },
+            background_color: Color::BLACK.with_alpha(0.75).into(),
             ..Default::default()
         },
     )).id();
     commands.entity(e_root).add_child(e_scoreboard);
+    e_scoreboard
 }
 
 fn spawn_scoreboard_row(
@@ -56,7 +133,7 @@ fn spawn_scoreboard_row(
                 justify_content: JustifyContent::Center,
                 align_items: AlignItems::Center,
                 grid_row: GridPlacement::start(row_start),
-                grid_column: GridPlacement::start(1),
+                grid_column: GridPlacement::start(2),
                 ..Default::default()
             },
             ..Default::default()
@@ -71,7 +148,7 @@ fn spawn_scoreboard_row(
                 justify_content: JustifyContent::Center,
                 align_items: AlignItems::Center,
                 grid_row: GridPlacement::start(row_start),
-                grid_column: GridPlacement::start(1),
+                grid_column: GridPlacement::start(3),
                 ..Default::default()
             },
             ..Default::default()
@@ -1,5 +1,7 @@
 use crate::{prelude::*, plid::{PlayerId, Plids}};
 
+pub mod headless;
+
 pub struct GameInput<Io: GameIo> {
     pub plid: PlayerId,
     pub subplid: u8,
@@ -54,6 +56,13 @@ pub trait Host<Io: GameIo>: Sized + Send + Sync + 'static {
     /// Cancel scheduled events equal to the value given
     fn desched_all(&mut self, event: Io::SchedEvent);
     fn game_over(&mut self);
+    /// The current time, as seen by the game
+    ///
+    /// Game code should use this (rather than `Instant::now()`) as the base
+    /// for computing `sched` times, so that hosts with a virtual clock work.
+    fn now(&self) -> std::time::Instant {
+        std::time::Instant::now()
+    }
 }
 
 /// Abstract interface through which the Host communicates with the Game
@@ -85,3 +94,25 @@ pub trait Game: Sized + Send + Sync + 'static {
 
     fn maintain(&mut self) {}
 }
+
+/// A Game whose complete state can be saved, to be restored and continued later
+///
+/// Times are saved relative to the host's clock at the time of saving,
+/// so a restored game has the same amount of time remaining on everything.
+pub trait GameSnapshot: Game {
+    /// Serialize the full state of the game
+    ///
+    /// `scheds` are the events that the host has pending for the game.
+    fn save_state(
+        &self,
+        now: std::time::Instant,
+        scheds: &[(std::time::Instant, <Self::Io as GameIo>::SchedEvent)],
+    ) -> AnyResult<Vec<u8>>;
+
+    /// Reconstruct a game from data produced by `save_state`
+    ///
+    /// The game re-`sched`s its pending events with the host, and sends out
+    /// messages so that players can rebuild their view of the game world.
+    /// Do not call `Game::init` on a restored game.
+    fn restore_state<H: Host<Self::Io>>(host: &mut H, data: &[u8]) -> AnyResult<Self>;
+}
@@ -0,0 +1,307 @@
+//! Headless Host implementation, for driving a `Game` without an app/runtime
+//!
+//! Useful for tests and tools: feed scripted inputs into a game and inspect
+//! everything it outputs. Time is virtual: it only moves when the caller
+//! advances it, and any `sched` timers that fall due are triggered in order,
+//! so a game session lasting minutes can be simulated instantly.
+
+use std::collections::BTreeMap;
+
+use crate::prelude::*;
+
+use super::*;
+
+/// Host with a virtual clock, that records all game output
+///
+/// Used by `HeadlessSession`. Can also be used directly, if you want
+/// to call the `Game` methods yourself.
+pub struct HeadlessHost<Io: GameIo> {
+    start: std::time::Instant,
+    now: std::time::Instant,
+    log: Vec<(Duration, GameOutput<Io>)>,
+    scheds: BTreeMap<std::time::Instant, Vec<Io::SchedEvent>>,
+    game_over: bool,
+}
+
+impl<Io: GameIo> Default for HeadlessHost<Io> {
+    fn default() -> Self {
+        let start = std::time::Instant::now();
+        Self {
+            start,
+            now: start,
+            log: Vec::new(),
+            scheds: BTreeMap::new(),
+            game_over: false,
+        }
+    }
+}
+
+impl<Io: GameIo> Host<Io> for HeadlessHost<Io> {
+    fn msg(&mut self, output: GameOutput<Io>) {
+        self.log.push((self.elapsed(), output));
+    }
+    fn sched(&mut self, time: std::time::Instant, event: Io::SchedEvent) {
+        self.scheds.entry(time).or_default().push(event);
+    }
+    fn desched_all(&mut self, event: Io::SchedEvent) {
+        self.scheds.retain(|_, evs| {
+            evs.retain(|ev| *ev != event);
+            !evs.is_empty()
+        });
+    }
+    fn game_over(&mut self) {
+        self.game_over = true;
+    }
+    fn now(&self) -> std::time::Instant {
+        self.now
+    }
+}
+
+impl<Io: GameIo> HeadlessHost<Io> {
+    /// How much virtual time has passed since the host was created
+    pub fn elapsed(&self) -> Duration {
+        self.now - self.start
+    }
+    /// Has the game signaled that it is over?
+    pub fn is_game_over(&self) -> bool {
+        self.game_over
+    }
+    /// Time (since start) of the earliest pending sched event, if any
+    pub fn next_sched(&self) -> Option<Duration> {
+        self.scheds.first_key_value().map(|(t, _)| *t - self.start)
+    }
+    /// All pending sched events, in order
+    pub fn scheds(&self) -> Vec<(std::time::Instant, Io::SchedEvent)> {
+        self.scheds.iter()
+            .flat_map(|(t, evs)| evs.iter().map(|ev| (*t, ev.clone())))
+            .collect()
+    }
+    /// Number of pending sched events
+    pub fn n_scheds(&self) -> usize {
+        self.scheds.values().map(|evs| evs.len()).sum()
+    }
+    /// Everything the game has output so far, with the time it happened
+    pub fn output_log(&self) -> &[(Duration, GameOutput<Io>)] {
+        &self.log
+    }
+    /// Remove and return everything in the output log
+    pub fn take_output(&mut self) -> Vec<(Duration, GameOutput<Io>)> {
+        std::mem::take(&mut self.log)
+    }
+    /// Pop the earliest sched event, if it is due at or before `until`
+    ///
+    /// Moves the clock to the time of the event.
+    fn pop_sched_until(&mut self, until: std::time::Instant) -> Option<Io::SchedEvent> {
+        let mut entry = self.scheds.first_entry()?;
+        let time = *entry.key();
+        if time > until {
+            return None;
+        }
+        let ev = entry.get_mut().remove(0);
+        if entry.get().is_empty() {
+            entry.remove();
+        }
+        if time > self.now {
+            self.now = time;
+        }
+        Some(ev)
+    }
+}
+
+/// A `Game` together with a `HeadlessHost`
+///
+/// Wraps all the boilerplate of driving the game: maintenance, triggering
+/// scheds as the virtual clock advances, and stopping after game over
+/// (any further inputs or time advancement are ignored).
+pub struct HeadlessSession<G: Game> {
+    pub game: G,
+    pub host: HeadlessHost<G::Io>,
+}
+
+impl<G: Game> HeadlessSession<G> {
+    /// Set up a new session and initialize the game
+    pub fn new(mut game: G, init_data: Box<G::InitData>) -> Self {
+        let mut host = HeadlessHost::default();
+        game.init(&mut host, init_data);
+        let mut session = Self { game, host };
+        session.maintain();
+        session
+    }
+
+    /// Send a player input to the game, at the current virtual time
+    pub fn input(&mut self, plid: PlayerId, subplid: u8, input: <G::Io as GameIo>::InputAction) {
+        if self.host.game_over {
+            return;
+        }
+        self.game.input(&mut self.host, GameInput { plid, subplid, input });
+        self.maintain();
+    }
+
+    /// Advance the virtual clock, triggering any sched events that fall due
+    pub fn advance(&mut self, duration: Duration) {
+        let until = self.host.now + duration;
+        self.run_scheds_until(until);
+        if !self.host.game_over {
+            self.host.now = until;
+        }
+    }
+
+    /// Advance the virtual clock to the given time since start
+    ///
+    /// Does nothing if that time is already in the past.
+    pub fn advance_to(&mut self, elapsed: Duration) {
+        if let Some(duration) = elapsed.checked_sub(self.host.elapsed()) {
+            self.advance(duration);
+        }
+    }
+
+    /// Keep jumping the clock to the next sched event, until there are none left
+    ///
+    /// Returns early on game over.
+    pub fn fast_forward(&mut self) {
+        while let Some(time) = self.host.scheds.last_key_value().map(|(t, _)| *t) {
+            self.run_scheds_until(time);
+            if self.host.game_over {
+                break;
+            }
+        }
+    }
+
+    /// Unsched all events due at or before the given time, in order
+    fn run_scheds_until(&mut self, until: std::time::Instant) {
+        while !self.host.game_over {
+            let Some(ev) = self.host.pop_sched_until(until) else {
+                break;
+            };
+            self.game.unsched(&mut self.host, ev);
+            self.maintain();
+        }
+    }
+
+    fn maintain(&mut self) {
+        while self.game.needs_maintain() {
+            self.game.maintain();
+        }
+    }
+
+    /// Has the game signaled that it is over?
+    pub fn is_game_over(&self) -> bool {
+        self.host.game_over
+    }
+
+    /// How much virtual time has passed since the session was created
+    pub fn elapsed(&self) -> Duration {
+        self.host.elapsed()
+    }
+
+    /// Everything the game has output so far, with the time it happened
+    pub fn output_log(&self) -> &[(Duration, GameOutput<G::Io>)] {
+        self.host.output_log()
+    }
+
+    /// Remove and return everything in the output log
+    pub fn take_output(&mut self) -> Vec<(Duration, GameOutput<G::Io>)> {
+        self.host.take_output()
+    }
+}
+
+impl<G: GameSnapshot> HeadlessSession<G> {
+    /// Save the full state of the game, at the current virtual time
+    pub fn save(&self) -> AnyResult<Vec<u8>> {
+        self.game.save_state(self.host.now, &self.host.scheds())
+    }
+
+    /// Set up a new session, continuing a game saved with `save`
+    ///
+    /// The virtual clock of the new session starts from zero.
+    pub fn restore(data: &[u8]) -> AnyResult<Self> {
+        let mut host = HeadlessHost::default();
+        let game = G::restore_state(&mut host, data)?;
+        let mut session = Self { game, host };
+        session.maintain();
+        Ok(session)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    struct TestIo;
+
+    impl GameIo for TestIo {
+        type OutEvent = u32;
+        type SchedEvent = u32;
+        type InputAction = u32;
+    }
+
+    /// Echoes inputs back after `input` milliseconds; input 0 ends the game
+    struct TestGame;
+
+    impl Game for TestGame {
+        type Io = TestIo;
+        type InitData = ();
+
+        fn init<H: Host<TestIo>>(&mut self, host: &mut H, _: Box<()>) {
+            host.sched(host.now() + Duration::from_secs(10), 0);
+        }
+        fn unsched<H: Host<TestIo>>(&mut self, host: &mut H, event: u32) {
+            if event == 0 {
+                host.game_over();
+            } else {
+                host.msg((Plids::all(true), event).into());
+            }
+        }
+        fn input<H: Host<TestIo>>(&mut self, host: &mut H, input: GameInput<TestIo>) {
+            if input.input == 0 {
+                host.desched_all(0);
+            } else {
+                host.sched(host.now() + Duration::from_millis(input.input as u64), input.input);
+            }
+        }
+    }
+
+    fn log_values(session: &HeadlessSession<TestGame>) -> Vec<(u128, u32)> {
+        session.output_log().iter()
+            .map(|(t, out)| (t.as_millis(), out.output))
+            .collect()
+    }
+
+    #[test]
+    fn advance_triggers_scheds_in_order() {
+        let mut session = HeadlessSession::new(TestGame, Box::new(()));
+        session.input(PlayerId::from(1), 0, 300);
+        session.input(PlayerId::from(1), 0, 100);
+        session.advance(Duration::from_millis(200));
+        assert_eq!(log_values(&session), vec![(100, 100)]);
+        session.input(PlayerId::from(1), 0, 50);
+        session.advance(Duration::from_millis(200));
+        assert_eq!(log_values(&session), vec![(100, 100), (250, 50), (300, 300)]);
+        assert_eq!(session.elapsed(), Duration::from_millis(400));
+        assert!(!session.is_game_over());
+    }
+
+    #[test]
+    fn fast_forward_to_game_over() {
+        let mut session = HeadlessSession::new(TestGame, Box::new(()));
+        session.input(PlayerId::from(1), 0, 20000);
+        session.fast_forward();
+        assert!(session.is_game_over());
+        assert_eq!(session.elapsed(), Duration::from_secs(10));
+        assert!(session.output_log().is_empty());
+        session.input(PlayerId::from(1), 0, 1);
+        session.advance(Duration::from_secs(1));
+        assert_eq!(session.host.n_scheds(), 1);
+    }
+
+    #[test]
+    fn desched_cancels() {
+        let mut session = HeadlessSession::new(TestGame, Box::new(()));
+        session.input(PlayerId::from(1), 0, 0);
+        assert_eq!(session.host.next_sched(), None);
+        session.input(PlayerId::from(1), 0, 5);
+        session.fast_forward();
+        assert!(!session.is_game_over());
+        assert_eq!(log_values(&session), vec![(5, 5)]);
+    }
+}
@@ -133,6 +133,7 @@ pub enum ActionKind {
 ///
 /// The server uses this + more (additional rules for the
 /// MineWars Game) to actually run the game.
+#[derive(Debug, Clone, PartialEq)]
 #[cfg_attr(feature = "bevy", derive(Component))]
 pub struct MwRules {
     pub res_base: u8,
@@ -169,6 +170,86 @@ pub struct MapGenTileData {
     pub region: CitId,
 }
 
+/// The per-tile data of a view.
+///
+/// This is a compact bitfield representation of the game state that needs
+/// to be cached/tracked for view switching in multi-view modes.
+///
+/// We don't need all game state. Some can be discarded on view switch,
+/// or just kept live in ECS entities and hidden (like explosion effects, etc),
+/// some can be recomputed (vis levels, roads).
+#[bitfield]
+#[derive(Clone, Copy, Default)]
+pub struct ViewTileData {
+    pub owner: B4,
+    pub digit: B3,
+    pub asterisk: bool,
+    pub kind: TileKind,
+    pub item: ItemKind,
+    pub has_structure: bool,
+    pub structure: StructureKind,
+    pub flag: B4,
+    #[skip] __: B4,
+    pub region: u8,
+}
+
+impl ViewTileData {
+    pub fn from_kind(kind: TileKind) -> Self {
+        let mut t = Self::default();
+        t.set_kind(kind);
+        t
+    }
+    pub fn from_kind_item(kind: TileKind, item: ItemKind) -> Self {
+        let mut t = Self::default();
+        t.set_kind(kind);
+        t.set_item(item);
+        t
+    }
+}
+
+/// Update a view of the map with a game event, as seen by `plid`
+///
+/// Events that do not affect the per-tile view data are ignored,
+/// as are events for tiles outside of the map.
+pub fn update_view_map(view: &mut MapDataPos<ViewTileData>, plid: PlayerId, ev: &MwEv) {
+    match *ev {
+        MwEv::TileKind { pos, kind } => {
+            if let Some(tile) = view.get_mut(pos) {
+                tile.set_kind(kind);
+            }
+        },
+        MwEv::TileOwner { pos, plid } => {
+            if let Some(tile) = view.get_mut(pos) {
+                tile.set_owner(u8::from(plid));
+            }
+        },
+        MwEv::DigitCapture { pos, digit: MwDigit { digit, asterisk } } => {
+            if let Some(tile) = view.get_mut(pos) {
+                tile.set_owner(u8::from(plid));
+                tile.set_digit(digit);
+                tile.set_asterisk(asterisk);
+            }
+        },
+        MwEv::RevealItem { pos, item } => {
+            if let Some(tile) = view.get_mut(pos) {
+                tile.set_item(item);
+            }
+        },
+        MwEv::Flag { pos, plid } => {
+            if let Some(tile) = view.get_mut(pos) {
+                tile.set_flag(u8::from(plid));
+            }
+        },
+        MwEv::Explode { pos } => {
+            if let Some(tile) = view.get_mut(pos) {
+                // clear any item from the tile
+                tile.set_item(ItemKind::Safe);
+            }
+        },
+        _ => {}
+    }
+}
+
 /// A MineWars Digit Value
 ///
 /// This is an "enhanced" Minesweeper digit.
@@ -312,6 +393,18 @@ pub enum PlayerEv {
     MatchTimeRemain {
         secs: u16,
     },
+    /// Final results of a player, sent when the game is over.
+    Standing {
+        /// Position in the ranking (1 is the winner). Tied players share a rank.
+        rank: u8,
+        /// How many tiles the player owned at the end.
+        tiles: u16,
+        lives: u8,
+        /// In what order the player was eliminated (1 is first). 0 if survived.
+        elim_order: u8,
+        /// Seconds since the start of the game, when the player was eliminated.
+        elim_secs: u16,
+    },
     ChatFriendly {
         text: String,
     },
@@ -403,3 +496,42 @@ impl MwEv {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn view_map_updates() {
+        let mut view = MapDataPos::new(2, ViewTileData::from_kind(TileKind::Regular));
+        let me = PlayerId::from(1);
+        let pos = Pos(1, 0);
+        update_view_map(&mut view, me, &MwEv::DigitCapture {
+            pos, digit: MwDigit { digit: 2, asterisk: true },
+        });
+        let tile = view[pos];
+        assert_eq!((tile.owner(), tile.digit(), tile.asterisk()), (1, 2, true));
+
+        update_view_map(&mut view, me, &MwEv::TileOwner { pos, plid: PlayerId::from(3) });
+        update_view_map(&mut view, me, &MwEv::Flag { pos, plid: PlayerId::from(2) });
+        update_view_map(&mut view, me, &MwEv::RevealItem { pos, item: ItemKind::Mine });
+        let tile = view[pos];
+        assert_eq!((tile.owner(), tile.flag(), tile.item()), (3, 2, ItemKind::Mine));
+        // the digit is kept until the tile is captured again
+        assert_eq!(tile.digit(), 2);
+
+        update_view_map(&mut view, me, &MwEv::Explode { pos });
+        update_view_map(&mut view, me, &MwEv::TileKind { pos, kind: TileKind::Destroyed });
+        assert_eq!(view[pos].item(), ItemKind::Safe);
+        assert_eq!(view[pos].kind(), TileKind::Destroyed);
+
+        // other tiles, irrelevant events, and positions off the map are left alone
+        let before: Vec<u32> = view.iter_data().map(|t| u32::from_le_bytes(t.into_bytes())).collect();
+        update_view_map(&mut view, me, &MwEv::Tremor);
+        update_view_map(&mut view, me, &MwEv::TileOwner { pos: Pos(100, 100), plid: me });
+        let after: Vec<u32> = view.iter_data().map(|t| u32::from_le_bytes(t.into_bytes())).collect();
+        assert_eq!(before, after);
+        assert_eq!(view[Pos(0, 0)].owner(), 0);
+        assert_eq!(view[Pos(0, 0)].kind(), TileKind::Regular);
+    }
+}
@@ -138,7 +138,8 @@ impl Coord for Hex {
 
     fn map_area(r: u8) -> usize {
         // arithmetic sequence sum = total # of cells
-        (12 + (r as usize - 1) * 6) * r as usize / 2 + 1
+        let r = r as usize;
+        3 * r * (r + 1) + 1
     }
     fn xmin(r: u8, y: i8) -> i8 {
         let r = r as i8;
@@ -415,14 +416,13 @@ impl Iterator for IterCoords {
         if let Some(Hex(y, x)) = &mut self.next {
             let xmax = if *y < 0 { r } else { r - *y };
 
-            *x += 1;
-
-            if *x > xmax {
+            // compare before incrementing, so nothing overflows when `r` is 127
+            if *x < xmax {
+                *x += 1;
+            } else if *y < r {
                 *y += 1;
                 *x = if *y < 0 { -r - *y } else { -r }
-            }
-
-            if *y > r {
+            } else {
                 self.next = None;
             }
         }
@@ -488,4 +488,16 @@ mod test {
         );
         assert_eq!(Hex::index(3, Hex(3, 0)), Hex::map_area(3) - 1);
     }
+
+    #[test]
+    fn map_area() {
+        // a map of radius 0 is just the center tile
+        assert_eq!(Hex::map_area(0), 1);
+        assert_eq!(Hex::map_area(1), 7);
+        assert_eq!(Hex::map_area(2), 19);
+        // maps can be at most 127 rings (see `MapData::new`)
+        for r in 0..=127 {
+            assert_eq!(Hex::map_area(r), Hex::iter_coords(r).count());
+        }
+    }
 }
@@ -40,7 +40,7 @@ impl<T: Into<Pos> + From<Pos> + IsMapKey> MapDataLayout<T> for LinearLayout {
         self.r
     }
     fn require_size(&self) -> usize {
-        self.index(Pos(self.r as i8, self.r as i8))
+        self.index(Pos(self.r as i8, self.r as i8)) + 1
     }
     fn in_bounds(&self, c: T) -> bool {
         let pos = c.into();
@@ -90,7 +90,7 @@ impl<T: Into<Pos> + From<Pos> + IsMapKey> MapDataLayout<T> for MortonLayout {
         self.r
     }
     fn require_size(&self) -> usize {
-        self.index(Pos(self.r as i8, self.r as i8))
+        self.index(Pos(self.r as i8, self.r as i8)) + 1
     }
     fn in_bounds(&self, c: T) -> bool {
         let pos = c.into();
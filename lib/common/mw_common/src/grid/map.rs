@@ -182,7 +182,7 @@ impl<C: Coord, D, L: MapDataLayout<C>> MapData<C, D, L> {
         let mut y = -(self.size() as i8);
         let mut next_row = 0;
 
-        for (i, (c, d)) in self.iter().enumerate() {
+        for (i, c) in self.iter_coords(None).enumerate() {
             if i == next_row {
                 if i != 0 {
                     w.write(&[b'\n'])?;
@@ -196,7 +196,7 @@ impl<C: Coord, D, L: MapDataLayout<C>> MapData<C, D, L> {
                 y += 1;
             }
 
-            w.write(&[b' ', f(c, d)])?;
+            w.write(&[b' ', f(c, &self[c])])?;
         }
 
         w.write(&[b'\n'])?;
@@ -235,6 +235,7 @@ impl<C: IsMapKey, D, L: MapDataLayout<C>> MapData<C, D, L> {
 mod test {
     use super::*;
     use crate::grid::hex::Hex;
+    use crate::grid::sq::Sq;
 
     fn rings_hex() -> MapDataC<Hex, u8> {
         let mut map = MapData::new(3, 0);
@@ -314,4 +315,45 @@ mod test {
         .unwrap();
         assert_eq!(ascii.get_ref(), &out.as_bytes()[1..]);
     }
+
+    /// Every coordinate of a square map must be storable,
+    /// including the `(r, r)` corner, which has the highest index
+    fn sq_corners<L: MapDataLayout<Sq>>() {
+        for r in [0, 1, 3, 10] {
+            let mut map: MapData<Sq, u8, L> = MapData::new(r, 0);
+            for c in map.iter_coords(None) {
+                map[c] += 1;
+            }
+            assert_eq!(map[Sq(r as i8, r as i8)], 1);
+            assert_eq!(map[Sq(-(r as i8), -(r as i8))], 1);
+            let n: usize = map.iter_coords(None).map(|c| map[c] as usize).sum();
+            assert_eq!(n, Sq::map_area(r));
+        }
+    }
+
+    #[test]
+    fn sq_corners_linear() {
+        sq_corners::<LinearLayout>();
+    }
+
+    #[test]
+    fn sq_corners_morton() {
+        sq_corners::<MortonLayout>();
+    }
+
+    #[test]
+    fn sq_ascii() {
+        let out = r#"
+  a b c
+  d e f
+  g h i
+"#;
+        let mut map: MapDataC<Sq, u8> = MapData::new(1, 0);
+        for (i, c) in map.iter_coords(None).enumerate() {
+            map[c] = i as u8;
+        }
+        let mut ascii = std::io::Cursor::new(Vec::new());
+        map.ascii_art(&mut ascii, |_, d| b'a' + *d).unwrap();
+        assert_eq!(ascii.get_ref(), &out.as_bytes()[1..]);
+    }
 }
@@ -43,3 +43,4 @@ pub mod plid;
 pub mod phoneme;
 pub mod game;
 pub mod data;
+pub mod mapgen;
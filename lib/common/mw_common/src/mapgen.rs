@@ -0,0 +1,617 @@
+//! Procedural Map Generation
+//!
+//! Generates the geography of a map, as described in the game design docs:
+//! a single contiguous island, with rivers, lakes, Mountain/Forest clusters,
+//! and fertile coastlines. Then places cities and partitions the map into
+//! their regions. Works with any topology.
+
+use enum_map::{enum_map, EnumMap};
+use noise::{Fbm, NoiseFn, Perlin};
+
+use crate::algo::{flood, FloodQ, FloodSelect};
+use crate::phoneme::{gen_name, Ph};
+use crate::prelude::*;
+
+/// Parameters for the generation of the map geography
+///
+/// Probabilities/densities are in 1/255 units.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "bevy", derive(Reflect))]
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
+pub struct MapGenSettings {
+    /// Small values = less land / more water, big values = more land / less water
+    pub land_bias: u8,
+    /// How much noise to add to the shape of the island
+    pub coast_roughness: u8,
+    /// How many rivers to try to carve into the island
+    pub n_rivers: u8,
+    /// Probability of a land tile starting a lake
+    pub lake_density: u8,
+    /// Probability of a land tile starting a Mountain cluster
+    pub mountain_density: u8,
+    /// Probability of a land tile starting a Forest cluster
+    pub forest_density: u8,
+    /// Max number of tiles in a lake/Mountain/Forest cluster
+    pub cluster_size_max: u8,
+    /// Probability of land next to water being made Fertile
+    pub prob_fertile: u8,
+}
+
+impl Default for MapGenSettings {
+    fn default() -> Self {
+        Self {
+            land_bias: 128,
+            coast_roughness: 96,
+            n_rivers: 2,
+            lake_density: 1,
+            mountain_density: 4,
+            forest_density: 6,
+            cluster_size_max: 6,
+            prob_fertile: 160,
+        }
+    }
+}
+
+/// Generate the geography of a new map
+///
+/// Every tile of `mapdata` will be assigned a `TileKind` using
+/// the provided closure. The outermost ring is always Water.
+pub fn gen_map<C: Coord, D, L: MapDataLayout<C>>(
+    settings: &MapGenSettings,
+    mapdata: &mut MapData<C, D, L>,
+    rng: &mut impl Rng,
+    f_set_kind: impl Fn(&mut D, TileKind),
+) {
+    let mut gen = MapGen {
+        map: MapDataC::new(mapdata.size(), TileKind::Water),
+        size: mapdata.size(),
+        q: FloodQ::new(),
+    };
+
+    gen.gen_island(settings, rng);
+    for _ in 0..settings.n_rivers {
+        gen.gen_river(rng);
+    }
+    gen.gen_clusters(TileKind::Water, settings.lake_density, settings.cluster_size_max, rng);
+    gen.gen_clusters(TileKind::Mountain, settings.mountain_density, settings.cluster_size_max, rng);
+    gen.gen_clusters(TileKind::Forest, settings.forest_density, settings.cluster_size_max, rng);
+    gen.gen_fertile(settings.prob_fertile, rng);
+
+    for (c, d) in mapdata.iter_mut() {
+        let kind = if c.ring() < gen.size {
+            gen.map.get(c).copied().unwrap_or(TileKind::Water)
+        } else {
+            TileKind::Water
+        };
+        f_set_kind(d, kind);
+    }
+}
+
+struct MapGen<C: Coord> {
+    map: MapDataC<C, TileKind>,
+    size: u8,
+    q: FloodQ,
+}
+
+impl<C: Coord> MapGen<C> {
+    fn kind(&self, c: C) -> TileKind {
+        if c.ring() < self.size {
+            self.map.get(c).copied().unwrap_or(TileKind::Water)
+        } else {
+            TileKind::Water
+        }
+    }
+
+    fn is_land(&self, c: C) -> bool {
+        self.kind(c).is_land()
+    }
+
+    fn is_coast(&self, c: C) -> bool {
+        c.iter_n0().any(|c2| self.kind(c2) == TileKind::Water)
+    }
+
+    fn coords(&self) -> impl Iterator<Item = C> {
+        C::iter_coords(self.size.saturating_sub(1))
+    }
+
+    /// Shape the island from noise, with a falloff towards the edge of the map
+    ///
+    /// Only the largest contiguous landmass is kept.
+    fn gen_island(&mut self, settings: &MapGenSettings, rng: &mut impl Rng) {
+        let noise = Fbm::<Perlin>::new(rng.gen());
+        // distance to the closest tile of the edge ring,
+        // so that the falloff reaches 1.0 before the edge everywhere
+        let r_edge = C::origin().iter_ring(self.size)
+            .map(|c| c.translation().length())
+            .fold(f32::MAX, f32::min) as f64;
+        let roughness = settings.coast_roughness as f64 / 255.0 * 0.6;
+        let threshold = 0.6 - settings.land_bias as f64 / 255.0 * 0.6;
+        let noise_offset = [rng.gen_range(-64.0..64.0), rng.gen_range(-64.0..64.0)];
+        for c in self.coords() {
+            let t = c.translation();
+            let (x, y) = (t.x as f64 / r_edge, t.y as f64 / r_edge);
+            let falloff = (x * x + y * y).sqrt();
+            let n = noise.get([x * 2.5 + noise_offset[0], y * 2.5 + noise_offset[1]]);
+            if 1.0 - falloff + n * roughness > threshold {
+                self.map[c] = TileKind::Regular;
+            }
+        }
+
+        // find the largest landmass
+        let mut visited = MapDataC::<C, bool>::new(self.size, false);
+        let mut best = None;
+        let mut best_count = 0;
+        for c in self.coords() {
+            if visited[c] || !self.is_land(c) {
+                continue;
+            }
+            visited[c] = true;
+            let mut count = 1;
+            let mut q = std::mem::take(&mut self.q);
+            q.clear();
+            q.push_back(c.into());
+            flood(&mut q, |c2: C, _| {
+                if self.map.get(c2).is_some() && !visited[c2] && self.is_land(c2) {
+                    visited[c2] = true;
+                    count += 1;
+                    FloodSelect::Yes
+                } else {
+                    FloodSelect::No
+                }
+            });
+            self.q = q;
+            if count > best_count {
+                best = Some(c);
+                best_count = count;
+            }
+        }
+        let Some(start) = best else {
+            self.map[C::origin()] = TileKind::Regular;
+            return;
+        };
+
+        // sink everything else
+        let keep = self.landmass(start);
+        for c in self.coords() {
+            if !keep[c] {
+                self.map[c] = TileKind::Water;
+            }
+        }
+    }
+
+    /// Mark all land tiles reachable from `start`
+    fn landmass(&mut self, start: C) -> MapDataC<C, bool> {
+        let mut reached = MapDataC::<C, bool>::new(self.size, false);
+        reached[start] = true;
+        let mut q = std::mem::take(&mut self.q);
+        q.clear();
+        q.push_back(start.into());
+        flood(&mut q, |c: C, _| {
+            if self.map.get(c).is_some() && !reached[c] && self.is_land(c) {
+                reached[c] = true;
+                FloodSelect::Yes
+            } else {
+                FloodSelect::No
+            }
+        });
+        self.q = q;
+        reached
+    }
+
+    /// Can the land tile at `c` be converted into a non-land tile
+    /// without splitting the land into disconnected parts?
+    fn can_remove(&mut self, c: C) -> bool {
+        // Fast path: if all the land around the tile forms
+        // a single arc of its ring, it stays connected locally.
+        let ring: Vec<(bool, bool)> = c.iter_ring(1)
+            .map(|c2| (self.is_land(c2), c.iter_n0().any(|n| n == c2)))
+            .collect();
+        let Some(gap) = ring.iter().position(|(land, _)| !land) else {
+            return true;
+        };
+        let mut arcs = 0;
+        let mut in_arc = false;
+        let mut arc_has_n0 = false;
+        for i in 1..=ring.len() {
+            let (land, n0) = ring[(gap + i) % ring.len()];
+            if land {
+                in_arc = true;
+                arc_has_n0 |= n0;
+            } else if in_arc {
+                if arc_has_n0 {
+                    arcs += 1;
+                }
+                in_arc = false;
+                arc_has_n0 = false;
+            }
+        }
+        match arcs {
+            // never remove the last land tile
+            0 => return false,
+            1 => return true,
+            _ => {}
+        }
+
+        // Slow path: check that all the remaining land is reachable
+        let Some(start) = c.iter_n0().find(|c2| self.is_land(*c2)) else {
+            return true;
+        };
+        let old = self.map[c];
+        self.map[c] = TileKind::Water;
+        let reached = self.landmass(start);
+        self.map[c] = old;
+        self.coords().all(|c2| c2 == c || !self.is_land(c2) || reached[c2])
+    }
+
+    /// Carve a river from the coast towards the center of the island
+    ///
+    /// Rivers are one tile thick and never split the land.
+    fn gen_river(&mut self, rng: &mut impl Rng) {
+        let mouths: Vec<C> = self.coords()
+            .filter(|c| self.kind(*c) == TileKind::Regular && self.is_coast(*c))
+            .collect();
+        let Some(&mouth) = mouths.choose(rng) else {
+            return;
+        };
+        if !self.can_remove(mouth) {
+            return;
+        }
+        self.map[mouth] = TileKind::Water;
+
+        let len = rng.gen_range(2..=(self.size as u16 / 2).max(2));
+        let mut cur = mouth;
+        for _ in 1..len {
+            let candidates: Vec<C> = cur.iter_n0()
+                .filter(|c| self.kind(*c) == TileKind::Regular)
+                // must not touch any water, other than where we came from
+                .filter(|c| c.iter_n0().all(|c2| c2 == cur || self.kind(c2) != TileKind::Water))
+                .collect();
+            let next = candidates.into_iter()
+                .map(|c| (c.distance(C::origin()) + rng.gen_range(0..3), c))
+                .min_by_key(|(score, _)| *score)
+                .map(|(_, c)| c);
+            let Some(next) = next else {
+                break;
+            };
+            if !self.can_remove(next) {
+                break;
+            }
+            self.map[next] = TileKind::Water;
+            cur = next;
+        }
+    }
+
+    /// Grow clusters of tiles of the given kind, starting from random land tiles
+    ///
+    /// Clusters never touch each other and never split the land.
+    fn gen_clusters(&mut self, kind: TileKind, density: u8, size_max: u8, rng: &mut impl Rng) {
+        if density == 0 {
+            return;
+        }
+        let same_class = |k: TileKind| if kind == TileKind::Water {
+            k == TileKind::Water
+        } else {
+            k.is_rescluster()
+        };
+        let mut seeds: Vec<C> = self.coords()
+            .filter(|c| self.kind(*c) == TileKind::Regular)
+            .collect();
+        seeds.shuffle(rng);
+        let mut cluster = Vec::new();
+        let mut frontier = Vec::new();
+        for seed in seeds {
+            if !rng.gen_bool(density as f64 / 255.0) {
+                continue;
+            }
+            cluster.clear();
+            let target = rng.gen_range(2..=size_max.max(2)) as usize;
+            frontier.clear();
+            frontier.push(seed);
+            while cluster.len() < target && !frontier.is_empty() {
+                let c = frontier.swap_remove(rng.gen_range(0..frontier.len()));
+                if self.kind(c) != TileKind::Regular
+                    || c.iter_n0().any(|c2| same_class(self.kind(c2)) && !cluster.contains(&c2))
+                    || !self.can_remove(c)
+                {
+                    continue;
+                }
+                self.map[c] = kind;
+                cluster.push(c);
+                frontier.extend(c.iter_n0().filter(|c2| self.kind(*c2) == TileKind::Regular));
+            }
+        }
+    }
+
+    /// Make land next to water Fertile
+    fn gen_fertile(&mut self, prob: u8, rng: &mut impl Rng) {
+        let coast: Vec<C> = self.coords()
+            .filter(|c| self.kind(*c) == TileKind::Regular && self.is_coast(*c))
+            .collect();
+        for c in coast {
+            if rng.gen_bool(prob as f64 / 255.0) {
+                self.map[c] = TileKind::Fertile;
+            }
+        }
+    }
+}
+
+/// Resource weights to use for balancing regions, if no game rules are available
+pub fn default_res_tile() -> EnumMap<TileKind, u8> {
+    enum_map! {
+        TileKind::Regular => 1,
+        TileKind::Fertile => 2,
+        TileKind::Forest => 6,
+        TileKind::Mountain => 8,
+        _ => 0,
+    }
+}
+
+/// Pick locations for up to `n_cits` cities
+///
+/// Cities are only placed on land that is fully surrounded by land,
+/// spread out to be as far apart from each other as possible.
+/// Fewer cities may be returned, if the map does not have enough room.
+pub fn gen_cits<C: Coord, D, L: MapDataLayout<C>>(
+    n_cits: u8,
+    mapdata: &MapData<C, D, L>,
+    rng: &mut impl Rng,
+    f_get_kind: impl Fn(&D) -> TileKind,
+) -> Vec<C> {
+    let size = mapdata.size();
+    let is_land = |c: C| c.ring() <= size && mapdata.get(c).map(&f_get_kind).is_some_and(TileKind::is_land);
+    let mut candidates: Vec<C> = mapdata.iter_coords(None)
+        .filter(|c| is_land(*c) && c.iter_n1().all(is_land))
+        .collect();
+    // randomize how ties are broken
+    candidates.shuffle(rng);
+
+    let mut cits = Vec::with_capacity(n_cits as usize);
+    let Some(&first) = candidates.choose(rng) else {
+        return cits;
+    };
+    cits.push(first);
+    while cits.len() < n_cits as usize {
+        let best = candidates.iter()
+            .map(|c| (cits.iter().map(|cit| c.distance(*cit)).min().unwrap(), *c))
+            .max_by_key(|(d, _)| *d);
+        match best {
+            // cities must not be adjacent to each other
+            Some((d, c)) if d >= 2 => cits.push(c),
+            _ => break,
+        }
+    }
+    cits
+}
+
+/// Generate a random name for each city
+pub fn gen_cit_names(n_cits: usize, rng: &mut impl Rng) -> Vec<Vec<Ph>> {
+    let mut names: Vec<Vec<Ph>> = Vec::with_capacity(n_cits);
+    while names.len() < n_cits {
+        let name = gen_name(rng);
+        if !names.contains(&name) {
+            names.push(name);
+        }
+    }
+    names
+}
+
+/// Assign every land/ResCluster tile on the map to the region of a city
+///
+/// The regions are grown from the cities outward, trying to keep the total
+/// resources of each region balanced: the region with the fewest resources
+/// is always expanded next. ResClusters (Mountains/Forests) are preferred
+/// (closest first) and assigned whole; otherwise, land farthest away from
+/// other cities is taken first. Water is not assigned to any region (`0xFF`).
+pub fn gen_regions<C: Coord, D, L: MapDataLayout<C>>(
+    cits: &[C],
+    res_tile: &EnumMap<TileKind, u8>,
+    mapdata: &mut MapData<C, D, L>,
+    f_get_kind: impl Fn(&D) -> TileKind,
+    f_set_region: impl Fn(&mut D, CitId),
+) {
+    let size = mapdata.size();
+    let cits = &cits[..cits.len().min(0xFF)];
+    let kinds: MapDataC<C, TileKind> = MapData::new_with(size, |c: C| {
+        if c.ring() <= size {
+            mapdata.get(c).map(&f_get_kind).unwrap_or(TileKind::Water)
+        } else {
+            TileKind::Water
+        }
+    });
+    let kind = |c: C| if c.ring() <= size { kinds[c] } else { TileKind::Water };
+    let mut regions = MapDataC::<C, CitId>::new(size, 0xFF);
+
+    // for every tile, the two closest cities, to quickly
+    // find the distance to the closest *other* city
+    let closest: MapDataC<C, [(u16, CitId); 2]> = MapData::new_with(size, |c: C| {
+        let mut r = [(u16::MAX, 0xFF); 2];
+        if c.ring() <= size {
+            for (i, cit) in cits.iter().enumerate() {
+                let d = c.distance(*cit);
+                if d < r[0].0 {
+                    r[1] = r[0];
+                    r[0] = (d, i as CitId);
+                } else if d < r[1].0 {
+                    r[1] = (d, i as CitId);
+                }
+            }
+        }
+        r
+    });
+    let dist_other = |c: C, cit: CitId| {
+        let [a, b] = closest[c];
+        if a.1 == cit { b.0 } else { a.0 }
+    };
+
+    let mut res = vec![0u32; cits.len()];
+    let mut frontiers: Vec<Vec<C>> = vec![vec![]; cits.len()];
+    let mut q = FloodQ::new();
+    let mut assigned = Vec::new();
+    for (i, cit) in cits.iter().enumerate() {
+        if kind(*cit) != TileKind::Water && regions[*cit] == 0xFF {
+            regions[*cit] = i as CitId;
+            res[i] += res_tile[kind(*cit)] as u32;
+            frontiers[i].extend(cit.iter_n0().filter(|c| kind(*c) != TileKind::Water));
+        }
+    }
+
+    loop {
+        // the poorest region that can still grow
+        let next = (0..cits.len())
+            .filter(|i| !frontiers[*i].is_empty())
+            .min_by_key(|i| res[*i]);
+        let Some(i) = next else {
+            break;
+        };
+        let cit_id = i as CitId;
+        let cit = cits[i];
+        let frontier = &mut frontiers[i];
+        frontier.retain(|c| regions[*c] == 0xFF);
+        if frontier.is_empty() {
+            continue;
+        }
+        let best_cluster = frontier.iter().enumerate()
+            .filter(|(_, c)| kind(**c).is_rescluster())
+            .min_by_key(|(_, c)| c.distance(cit));
+        let pick = if let Some((idx, _)) = best_cluster {
+            idx
+        } else {
+            frontier.iter().enumerate()
+                .max_by_key(|(_, c)| (dist_other(**c, cit_id), std::cmp::Reverse(c.distance(cit))))
+                .map(|(idx, _)| idx)
+                .unwrap()
+        };
+        let c = frontier.swap_remove(pick);
+
+        assigned.clear();
+        regions[c] = cit_id;
+        assigned.push(c);
+        if kind(c).is_rescluster() {
+            q.clear();
+            q.push_back(c.into());
+            flood(&mut q, |c2: C, _| {
+                if kind(c2).is_rescluster() && regions[c2] == 0xFF {
+                    regions[c2] = cit_id;
+                    assigned.push(c2);
+                    FloodSelect::Yes
+                } else {
+                    FloodSelect::No
+                }
+            });
+        }
+        for c in assigned.iter() {
+            res[i] += res_tile[kind(*c)] as u32;
+            for c2 in c.iter_n0() {
+                if kind(c2) != TileKind::Water && regions[c2] == 0xFF && !frontier.contains(&c2) {
+                    frontier.push(c2);
+                }
+            }
+        }
+    }
+
+    for c in mapdata.iter_coords(None) {
+        if let Some(d) = mapdata.get_mut(c) {
+            f_set_region(d, regions[c]);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use rand_pcg::Pcg64Mcg;
+
+    use super::*;
+
+    fn check_map<C: Coord>(seed: u64, size: u8) {
+        let mut rng = Pcg64Mcg::seed_from_u64(seed);
+        let mut map = MapDataC::<C, TileKind>::new(size, TileKind::Regular);
+        gen_map(&MapGenSettings::default(), &mut map, &mut rng, |d, kind| *d = kind);
+
+        let land: Vec<C> = map.iter_coords(None).filter(|c| map[*c].is_land()).collect();
+        assert!(!land.is_empty());
+        assert!(C::origin().iter_ring(size).all(|c| map[c] == TileKind::Water));
+
+        // all land must be contiguous
+        let mut reached = MapDataC::<C, bool>::new(size, false);
+        reached[land[0]] = true;
+        let mut q = FloodQ::new();
+        q.push_back(land[0].into());
+        flood(&mut q, |c: C, _| {
+            if c.ring() <= size && !reached[c] && map[c].is_land() {
+                reached[c] = true;
+                FloodSelect::Yes
+            } else {
+                FloodSelect::No
+            }
+        });
+        assert!(land.iter().all(|c| reached[*c]));
+
+        // fertile land only on the coast
+        for c in map.iter_coords(None) {
+            if map[c] == TileKind::Fertile {
+                assert!(c.iter_n0().any(|c2| map[c2] == TileKind::Water));
+            }
+        }
+    }
+
+    fn check_regions<C: Coord>(seed: u64, size: u8, n_cits: u8) {
+        let mut rng = Pcg64Mcg::seed_from_u64(seed);
+        let mut map = MapDataC::<C, (TileKind, CitId)>::new(size, (TileKind::Water, 0xFF));
+        gen_map(&MapGenSettings::default(), &mut map, &mut rng, |d, kind| d.0 = kind);
+        let cits = gen_cits(n_cits, &map, &mut rng, |d| d.0);
+        assert!(!cits.is_empty());
+        gen_regions(&cits, &default_res_tile(), &mut map, |d| d.0, |d, region| d.1 = region);
+
+        for (i, cit) in cits.iter().enumerate() {
+            assert_eq!(map[*cit].1, i as CitId);
+            assert!(cit.iter_n1().all(|c| map[c].0.is_land()));
+        }
+        for c in map.iter_coords(None) {
+            let (kind, region) = map[c];
+            if kind == TileKind::Water {
+                assert_eq!(region, 0xFF);
+                continue;
+            }
+            assert!((region as usize) < cits.len());
+            // clusters are never split between regions
+            if kind.is_rescluster() {
+                for c2 in c.iter_n0() {
+                    if map[c2].0.is_rescluster() {
+                        assert_eq!(map[c2].1, region);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn island_hex() {
+        for seed in 0..8 {
+            check_map::<Hex>(seed, 16);
+        }
+        check_map::<Hex>(8, 1);
+    }
+
+    #[test]
+    fn island_sq() {
+        for seed in 0..8 {
+            check_map::<Sq>(seed, 16);
+        }
+        check_map::<Sq>(8, 1);
+    }
+
+    #[test]
+    fn regions_hex() {
+        for seed in 0..4 {
+            check_regions::<Hex>(seed, 20, 6);
+        }
+    }
+
+    #[test]
+    fn regions_sq() {
+        for seed in 0..4 {
+            check_regions::<Sq>(seed, 20, 6);
+        }
+    }
+}
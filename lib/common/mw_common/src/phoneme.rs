@@ -1,5 +1,7 @@
 //! Phoneme Encoding for Place Names
 
+use rand::prelude::*;
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 #[repr(u8)]
 pub enum Ph {
@@ -114,6 +116,47 @@ impl Ph {
     }
 }
 
+/// Generate a random pronounceable place name
+///
+/// Names are made of 2-3 syllables, alternating between consonants and vowels.
+/// Double consonants only occur in the middle of the name.
+pub fn gen_name(rng: &mut impl Rng) -> Vec<Ph> {
+    const VOWELS: &[Ph] = &[Ph::A, Ph::E, Ph::I, Ph::O, Ph::U];
+    const VOWELS_IOTATED: &[Ph] = &[Ph::Ya, Ph::Ye, Ph::Yi, Ph::Yo, Ph::Yu];
+    const CONSONANTS: &[Ph] = &[
+        Ph::B, Ph::Ch, Ph::D, Ph::F, Ph::G, Ph::H, Ph::K, Ph::Kh, Ph::L, Ph::M, Ph::N,
+        Ph::P, Ph::R, Ph::S, Ph::Sh, Ph::T, Ph::V, Ph::Z, Ph::Ts, Ph::Zh, Ph::Dj,
+    ];
+    const CONSONANTS_DOUBLE: &[Ph] = &[
+        Ph::Bb, Ph::Cch, Ph::Dd, Ph::Ff, Ph::Gg, Ph::Kk, Ph::Ll, Ph::Mm, Ph::Nn,
+        Ph::Pp, Ph::Rr, Ph::Ss, Ph::Ssh, Ph::Tt, Ph::Vv, Ph::Zz,
+    ];
+
+    let n_syllables = rng.gen_range(2..=3);
+    let mut name = Vec::with_capacity(n_syllables * 2 + 1);
+    for i in 0..n_syllables {
+        // the first syllable may start with a vowel
+        if i != 0 || rng.gen_bool(0.75) {
+            let consonants = if i != 0 && rng.gen_bool(0.15) {
+                CONSONANTS_DOUBLE
+            } else {
+                CONSONANTS
+            };
+            name.push(*consonants.choose(rng).unwrap());
+        }
+        let vowels = if rng.gen_bool(0.1) {
+            VOWELS_IOTATED
+        } else {
+            VOWELS
+        };
+        name.push(*vowels.choose(rng).unwrap());
+    }
+    if rng.gen_bool(0.4) {
+        name.push(*CONSONANTS.choose(rng).unwrap());
+    }
+    name
+}
+
 pub mod lang {
     use super::*;
 
@@ -0,0 +1,24 @@
+//! Parse arbitrary text as message assembly
+//!
+//! Must never panic. Whatever parses successfully must survive
+//! being re-assembled and parsed again unchanged.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use mw_dataformat::msg::{MsgReader, MsgWriter};
+use mw_dataformat::msg::asm::{MsgAsmRead, MsgAsmWrite};
+
+fuzz_target!(|data: &[u8]| {
+    let mut evs = Vec::new();
+    if MsgAsmRead::new().read_all(&mut &data[..], &mut evs).is_err() {
+        return;
+    }
+    let mut text = Vec::new();
+    MsgAsmWrite::new().write_all(&mut text, &evs)
+        .expect("parsed messages must be writable");
+    let mut evs2 = Vec::new();
+    MsgAsmRead::new().read_all(&mut &text[..], &mut evs2)
+        .expect("written messages must be parsable");
+    assert_eq!(evs, evs2);
+});
@@ -0,0 +1,24 @@
+//! Decode arbitrary bytes as binary messages
+//!
+//! Must never panic. Whatever decodes successfully must survive
+//! being re-encoded and decoded again unchanged.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use mw_dataformat::msg::{MsgReader, MsgWriter};
+use mw_dataformat::msg::bin::{MsgBinRead, MsgBinWrite};
+
+fuzz_target!(|data: &[u8]| {
+    let mut evs = Vec::new();
+    if MsgBinRead::new().read_all(&mut &data[..], &mut evs).is_err() {
+        return;
+    }
+    let mut bytes = Vec::new();
+    MsgBinWrite::new().write_all(&mut bytes, &evs)
+        .expect("decoded messages must be encodable");
+    let mut evs2 = Vec::new();
+    MsgBinRead::new().read_all(&mut &bytes[..], &mut evs2)
+        .expect("encoded messages must be decodable");
+    assert_eq!(evs, evs2);
+});
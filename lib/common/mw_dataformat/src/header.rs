@@ -27,6 +27,7 @@ pub struct ISHeader {
     pub len_mapdata_compressed: u32,
     pub len_rules: u16,
     pub len_citdata_names: u16,
+    pub len_players: u16,
 }
 
 /// The MineWars File Header Extras
@@ -94,6 +95,8 @@ impl ISHeader {
             let out_header: &mut ISHeader = bytemuck::from_bytes_mut(&mut out[start..]);
             out_header.len_rules = out_header.len_rules.swap_bytes();
             out_header.len_mapdata_compressed = out_header.len_mapdata_compressed.swap_bytes();
+            out_header.len_citdata_names = out_header.len_citdata_names.swap_bytes();
+            out_header.len_players = out_header.len_players.swap_bytes();
         }
     }
     pub fn deserialize(input: &[u8]) -> Self {
@@ -103,6 +106,8 @@ impl ISHeader {
         {
             out_header.len_rules = out_header.len_rules.swap_bytes();
             out_header.len_mapdata_compressed = out_header.len_mapdata_compressed.swap_bytes();
+            out_header.len_citdata_names = out_header.len_citdata_names.swap_bytes();
+            out_header.len_players = out_header.len_players.swap_bytes();
         }
         out_header
     }
@@ -127,6 +132,9 @@ impl ISHeader {
     pub fn len_rules(&self) -> usize {
         self.len_rules as usize
     }
+    pub fn len_players(&self) -> usize {
+        self.len_players as usize
+    }
     pub fn len_mapdata_compressed(&self) -> usize {
         self.len_mapdata_compressed as usize
     }
@@ -157,11 +165,18 @@ impl ISHeader {
         + self.len_citdata_pos()
         + self.len_citdata_names()
     }
+    pub fn offset_players(&self) -> usize {
+        self.len_mapdata_compressed()
+        + self.len_citdata_pos()
+        + self.len_citdata_names()
+        + self.len_rules()
+    }
     pub fn len_total_data(&self) -> usize {
         self.len_mapdata_compressed()
         + self.len_citdata_pos()
         + self.len_citdata_names()
         + self.len_rules()
+        + self.len_players()
     }
 }
 
@@ -210,3 +225,38 @@ impl MwFileHeader {
         std::mem::size_of::<u64>()
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn is_header_big_endian() {
+        let mut header = ISHeader::default();
+        header.version = Version(0, 0, 2, 0);
+        header.set_map_topology(Topology::Sq);
+        header.map_size = 24;
+        header.set_max_plid(6);
+        header.set_max_sub_plid(2);
+        header.n_regions = 5;
+        header.len_mapdata_compressed = 0x00012345;
+        header.len_rules = 0x0102;
+        header.len_citdata_names = 0x0304;
+        header.len_players = 0x0506;
+        let mut out = Vec::new();
+        header.serialize(&mut out);
+        assert_eq!(out, [
+            0, 0, 2, 0,
+            0b00001000, 24, 0x26, 5,
+            0x00, 0x01, 0x23, 0x45,
+            0x01, 0x02,
+            0x03, 0x04,
+            0x05, 0x06,
+        ]);
+        let header = ISHeader::deserialize(&out);
+        assert_eq!(header.len_mapdata_compressed(), 0x00012345);
+        assert_eq!(header.len_rules(), 0x0102);
+        assert_eq!(header.len_citdata_names(), 0x0304);
+        assert_eq!(header.len_players(), 0x0506);
+    }
+}
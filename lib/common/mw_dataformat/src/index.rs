@@ -0,0 +1,112 @@
+//! Seek Index for Frame Data
+//!
+//! Frame timestamps are delta-encoded, so frames can normally only be decoded
+//! sequentially, starting from the beginning. An index records, at regular
+//! intervals, where a frame begins and what the timestamp was just before it,
+//! so that a reader can resume decoding from there and jump close to any time.
+//!
+//! An index can be built in memory, by scanning the frames once, or stored
+//! in a MineWars file, as an optional trailing section after the frame data
+//! (see `trailer`).
+//!
+//! Files written before trailing sections existed may instead have a bare
+//! index right after the frame data, in the legacy encoding: the same 16-byte
+//! header (magic, u32 BE, seahash u64 BE), except that the u32 is the number
+//! of entries rather than the length in bytes. See `deserialize_legacy`.
+
+use crate::trailer::{serialize_section, TrailerDecodeError, SECTION_HEADER_LEN};
+
+/// Identifies the trailing section containing the index
+pub const INDEX_MAGIC: [u8; 4] = *b"MWIX";
+const INDEX_ENTRY_LEN: usize = 12;
+
+/// A point in the frame data where decoding can start from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MwFrameIndexEntry {
+    /// The timestamp just before the frame (that of the previous frame)
+    pub time_ms: u64,
+    /// Where the frame begins, relative to the start of the (uncompressed) frame data
+    pub offset: u32,
+}
+
+/// Index of frame data, for seeking
+///
+/// Entries are sorted by time.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MwFrameIndex {
+    entries: Vec<MwFrameIndexEntry>,
+}
+
+impl MwFrameIndex {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn entries(&self) -> &[MwFrameIndexEntry] {
+        &self.entries
+    }
+    /// Add an entry to the end of the index
+    ///
+    /// It must not be earlier than any existing entry.
+    pub fn push(&mut self, entry: MwFrameIndexEntry) {
+        debug_assert!(self.entries.last().map(|last| last.time_ms <= entry.time_ms).unwrap_or(true));
+        self.entries.push(entry);
+    }
+    /// Find where to start decoding from, to get to `time_ms`
+    ///
+    /// Returns the last entry before `time_ms`, so that no frames
+    /// at or after `time_ms` would be skipped.
+    pub fn lookup(&self, time_ms: u64) -> Option<&MwFrameIndexEntry> {
+        let i = self.entries.partition_point(|e| e.time_ms < time_ms);
+        i.checked_sub(1).map(|i| &self.entries[i])
+    }
+    pub fn serialized_len(&self) -> usize {
+        SECTION_HEADER_LEN + INDEX_ENTRY_LEN * self.entries.len()
+    }
+    /// Encode the index as a trailing section.
+    ///
+    /// The binary data will be appended to `out`.
+    pub fn serialize(&self, out: &mut Vec<u8>) {
+        out.reserve(self.serialized_len());
+        serialize_section(INDEX_MAGIC, out, |out| {
+            for entry in &self.entries {
+                out.extend_from_slice(&entry.time_ms.to_be_bytes());
+                out.extend_from_slice(&entry.offset.to_be_bytes());
+            }
+        });
+    }
+    /// Decode an index from the contents of its trailing section.
+    pub fn deserialize(data: &[u8]) -> Result<Self, TrailerDecodeError> {
+        if data.len() % INDEX_ENTRY_LEN != 0 {
+            return Err(TrailerDecodeError::BadSize);
+        }
+        let entries: Vec<_> = data.chunks_exact(INDEX_ENTRY_LEN)
+            .map(|b| MwFrameIndexEntry {
+                time_ms: u64::from_be_bytes(b[0..8].try_into().unwrap()),
+                offset: u32::from_be_bytes(b[8..12].try_into().unwrap()),
+            })
+            .collect();
+        if entries.windows(2).any(|w| w[0].time_ms > w[1].time_ms) {
+            return Err(TrailerDecodeError::BadData);
+        }
+        Ok(Self { entries })
+    }
+    /// Decode an index in the legacy encoding.
+    ///
+    /// `input` is everything after the frame data, which must be exactly
+    /// one legacy index (older files could not have anything else there).
+    pub fn deserialize_legacy(input: &[u8]) -> Result<Self, TrailerDecodeError> {
+        if input.len() < SECTION_HEADER_LEN || input[0..4] != INDEX_MAGIC {
+            return Err(TrailerDecodeError::BadData);
+        }
+        let n_entries = u32::from_be_bytes(input[4..8].try_into().unwrap()) as usize;
+        let checksum = u64::from_be_bytes(input[8..16].try_into().unwrap());
+        let data = &input[SECTION_HEADER_LEN..];
+        if data.len() != n_entries * INDEX_ENTRY_LEN {
+            return Err(TrailerDecodeError::BadSize);
+        }
+        if seahash::hash(data) != checksum {
+            return Err(TrailerDecodeError::BadChecksum);
+        }
+        Self::deserialize(data)
+    }
+}
@@ -0,0 +1,181 @@
+//! Keyframes: Snapshots of View State
+//!
+//! Reconstructing the map at a given time normally requires replaying every
+//! event from the start of the game. A MineWars file can optionally store
+//! periodic snapshots of each plid's view of the map (in the compact
+//! `ViewTileData` representation), so that a reader (replay seeking, late-join
+//! spectating) can start from the nearest one and only replay the frames after it.
+//!
+//! Keyframes are stored as an optional trailing section after the frame data
+//! (see `trailer`). The map data of each view is LZ4-compressed.
+//!
+//! Encoding (all integers BE):
+//!  - number of keyframes (u32)
+//!  - for each keyframe:
+//!    - frame index entry: time (u64), offset (u32)
+//!    - number of views (u8)
+//!    - for each view:
+//!      - plid (u8)
+//!      - length of compressed data (u32)
+//!      - compressed data: 4 bytes of `ViewTileData` per tile, in `MapData` order
+
+use mw_common::game::ViewTileData;
+use mw_common::grid::MapDataPos;
+use mw_common::plid::PlayerId;
+
+use crate::index::MwFrameIndexEntry;
+use crate::trailer::{serialize_section, TrailerDecodeError};
+
+/// Identifies the trailing section containing the keyframes
+pub const KEYFRAMES_MAGIC: [u8; 4] = *b"MWKF";
+const VIEW_TILE_LEN: usize = 4;
+
+/// A plid's view of the map, at the time of a keyframe
+#[derive(Clone)]
+pub struct MwViewSnapshot {
+    pub plid: PlayerId,
+    pub map: MapDataPos<ViewTileData>,
+}
+
+/// Snapshots of the views, at a point in the frame data
+#[derive(Clone)]
+pub struct MwKeyframe {
+    /// Where to continue decoding frames from
+    ///
+    /// The views are as of just before the frame at this entry.
+    pub entry: MwFrameIndexEntry,
+    pub views: Vec<MwViewSnapshot>,
+}
+
+impl MwKeyframe {
+    pub fn get_view(&self, plid: PlayerId) -> Option<&MapDataPos<ViewTileData>> {
+        self.views.iter()
+            .find(|v| v.plid == plid)
+            .map(|v| &v.map)
+    }
+}
+
+/// All the keyframes of a file
+///
+/// Keyframes are sorted by time.
+#[derive(Clone, Default)]
+pub struct MwKeyframes {
+    keyframes: Vec<MwKeyframe>,
+}
+
+impl MwKeyframes {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn keyframes(&self) -> &[MwKeyframe] {
+        &self.keyframes
+    }
+    /// Add a keyframe to the end
+    ///
+    /// It must not be earlier than any existing keyframe.
+    pub fn push(&mut self, keyframe: MwKeyframe) {
+        debug_assert!(self.keyframes.last().map(|last| last.entry.time_ms <= keyframe.entry.time_ms).unwrap_or(true));
+        self.keyframes.push(keyframe);
+    }
+    /// Find the keyframe to start from, to get to `time_ms`
+    ///
+    /// Returns the last keyframe before `time_ms`, so that no frames
+    /// at or after `time_ms` would be skipped.
+    pub fn lookup(&self, time_ms: u64) -> Option<&MwKeyframe> {
+        let i = self.keyframes.partition_point(|k| k.entry.time_ms < time_ms);
+        i.checked_sub(1).map(|i| &self.keyframes[i])
+    }
+    /// Encode the keyframes as a trailing section.
+    ///
+    /// The binary data will be appended to `out`.
+    ///
+    /// A `scratch` buffer must be provided (to help reuse allocations).
+    /// This function will clear it before and after use.
+    pub fn serialize(&self, out: &mut Vec<u8>, scratch: &mut Vec<u8>) {
+        serialize_section(KEYFRAMES_MAGIC, out, |out| {
+            out.extend_from_slice(&(self.keyframes.len() as u32).to_be_bytes());
+            for keyframe in &self.keyframes {
+                out.extend_from_slice(&keyframe.entry.time_ms.to_be_bytes());
+                out.extend_from_slice(&keyframe.entry.offset.to_be_bytes());
+                out.push(keyframe.views.len() as u8);
+                for view in &keyframe.views {
+                    out.push(u8::from(view.plid));
+                    scratch.clear();
+                    for tile in view.map.iter_data() {
+                        scratch.extend_from_slice(&tile.into_bytes());
+                    }
+                    let off_len = out.len();
+                    out.extend_from_slice(&[0; 4]);
+                    let out_start = out.len();
+                    out.resize(out_start + lz4_flex::block::get_maximum_output_size(scratch.len()), 0);
+                    let compr_len = lz4_flex::block::compress_into(scratch, &mut out[out_start..])
+                        .expect("LZ4 compression bug");
+                    out.truncate(out_start + compr_len);
+                    out[off_len..out_start].copy_from_slice(&(compr_len as u32).to_be_bytes());
+                }
+            }
+        });
+        scratch.clear();
+    }
+    /// Decode keyframes from the contents of their trailing section.
+    ///
+    /// `map_size` must be that of the file's map.
+    ///
+    /// A `scratch` buffer must be provided (to help reuse allocations).
+    /// This function will clear it before and after use.
+    pub fn deserialize(
+        mut data: &[u8],
+        map_size: u8,
+        scratch: &mut Vec<u8>,
+    ) -> Result<Self, TrailerDecodeError> {
+        if map_size > 127 {
+            return Err(TrailerDecodeError::BadData);
+        }
+        let n_keyframes = u32::from_be_bytes(take(&mut data, 4)?.try_into().unwrap());
+        let mut keyframes = Vec::new();
+        for _ in 0..n_keyframes {
+            let entry = MwFrameIndexEntry {
+                time_ms: u64::from_be_bytes(take(&mut data, 8)?.try_into().unwrap()),
+                offset: u32::from_be_bytes(take(&mut data, 4)?.try_into().unwrap()),
+            };
+            if keyframes.last().map(|last: &MwKeyframe| last.entry.time_ms > entry.time_ms).unwrap_or(false) {
+                return Err(TrailerDecodeError::BadData);
+            }
+            let n_views = take(&mut data, 1)?[0];
+            let mut views = Vec::with_capacity(n_views as usize);
+            for _ in 0..n_views {
+                let plid = PlayerId::from(take(&mut data, 1)?[0]);
+                let len = u32::from_be_bytes(take(&mut data, 4)?.try_into().unwrap());
+                let compressed = take(&mut data, len as usize)?;
+                let mut map = MapDataPos::new(map_size, ViewTileData::default());
+                let len_raw = map.data().len() * VIEW_TILE_LEN;
+                scratch.clear();
+                scratch.resize(len_raw, 0);
+                let data_len = lz4_flex::block::decompress_into(compressed, scratch)
+                    .map_err(TrailerDecodeError::BadCompression)?;
+                if data_len != len_raw {
+                    return Err(TrailerDecodeError::BadData);
+                }
+                for (tile, bytes) in map.data_mut().iter_mut().zip(scratch.chunks_exact(VIEW_TILE_LEN)) {
+                    *tile = ViewTileData::from_bytes(bytes.try_into().unwrap());
+                }
+                views.push(MwViewSnapshot { plid, map });
+            }
+            keyframes.push(MwKeyframe { entry, views });
+        }
+        scratch.clear();
+        if !data.is_empty() {
+            return Err(TrailerDecodeError::BadSize);
+        }
+        Ok(Self { keyframes })
+    }
+}
+
+fn take<'a>(data: &mut &'a [u8], len: usize) -> Result<&'a [u8], TrailerDecodeError> {
+    if data.len() < len {
+        return Err(TrailerDecodeError::BadSize);
+    }
+    let (r, rest) = data.split_at(len);
+    *data = rest;
+    Ok(r)
+}
@@ -2,10 +2,17 @@
 #![feature(round_char_boundary)]
 
 pub mod header;
+pub mod index;
+pub mod keyframe;
 pub mod map;
 pub mod msg;
+pub mod players;
+pub mod rules;
+pub mod trailer;
 
 pub mod read;
 pub mod write;
 
-pub const FORMAT_VERSION: header::Version = header::Version(0, 0, 1 ,0);
+/// Version 0.0.2: added the Players section to the IS and
+/// fixed the byte order of the Cits names length in the IS header.
+pub const FORMAT_VERSION: header::Version = header::Version(0, 0, 2 ,0);
@@ -49,3 +49,230 @@ pub trait MsgReader {
         Ok(total)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use proptest::prelude::{any, prop, Just, Strategy};
+    use proptest::{prop_assert, prop_assert_eq, prop_oneof, proptest};
+
+    use super::*;
+    use super::asm::{MsgAsmRead, MsgAsmWrite};
+    use super::bin::{MsgBinRead, MsgBinWrite};
+
+    fn arb_pos() -> impl Strategy<Value = Pos> {
+        (any::<i8>(), any::<i8>()).prop_map(|(y, x)| Pos(y, x))
+    }
+
+    fn arb_plid() -> impl Strategy<Value = PlayerId> {
+        (0..=MAX_PLIDS).prop_map(PlayerId::from)
+    }
+
+    /// Strings that survive both encodings unchanged:
+    /// short enough for binary, no comments/newlines/padding for asm.
+    fn arb_text() -> impl Strategy<Value = String> {
+        "[^;\\s\\p{Cc}]([^;\\p{Cc}]{0,60}[^;\\s\\p{Cc}])?"
+    }
+
+    fn arb_structure_kind() -> impl Strategy<Value = StructureKind> {
+        (0..4u8).prop_map(|k| StructureKind::from_u8(k).unwrap())
+    }
+
+    fn arb_player_ev() -> impl Strategy<Value = PlayerEv> {
+        prop_oneof![
+            arb_text().prop_map(|name| PlayerEv::Joined { name }),
+            any::<u8>().prop_map(|d| PlayerEv::NetRttInfo { duration: MwDur(d) }),
+            any::<u8>().prop_map(|d| PlayerEv::Timeout { duration: MwDur(d) }),
+            Just(PlayerEv::TimeoutFinished),
+            (arb_pos(), arb_plid()).prop_map(|(pos, killer)| PlayerEv::Exploded { pos, killer }),
+            any::<u8>().prop_map(|lives| PlayerEv::LivesRemain { lives }),
+            Just(PlayerEv::Protected),
+            Just(PlayerEv::Unprotected),
+            Just(PlayerEv::Eliminated),
+            Just(PlayerEv::Surrendered),
+            Just(PlayerEv::Disconnected),
+            Just(PlayerEv::Kicked),
+            any::<u16>().prop_map(|secs| PlayerEv::MatchTimeRemain { secs }),
+            (any::<u8>(), any::<u16>(), any::<u8>(), any::<u8>(), any::<u16>())
+                .prop_map(|(rank, tiles, lives, elim_order, elim_secs)| PlayerEv::Standing {
+                    rank, tiles, lives, elim_order, elim_secs,
+                }),
+            arb_text().prop_map(|text| PlayerEv::ChatAll { text }),
+            arb_text().prop_map(|text| PlayerEv::ChatFriendly { text }),
+            (any::<u8>(), arb_text()).prop_map(|(id, l10nkey)| PlayerEv::VoteNew { id, l10nkey }),
+            any::<u8>().prop_map(|id| PlayerEv::VoteNo { id }),
+            any::<u8>().prop_map(|id| PlayerEv::VoteYes { id }),
+            any::<u8>().prop_map(|id| PlayerEv::VoteFail { id }),
+            any::<u8>().prop_map(|id| PlayerEv::VotePass { id }),
+        ]
+    }
+
+    /// Any event that the binary encoding can represent exactly
+    fn arb_ev() -> impl Strategy<Value = MwEv> {
+        prop_oneof![
+            (any::<u8>(), arb_pos()).prop_map(|(i, pos)| MwEv::Debug(i, pos)),
+            (arb_plid(), prop::option::of(0..MAX_SUBPLIDS), arb_player_ev())
+                .prop_map(|(plid, subplid, ev)| MwEv::Player { plid, subplid, ev }),
+            Just(MwEv::Tremor),
+            arb_pos().prop_map(|pos| MwEv::Smoke { pos }),
+            arb_pos().prop_map(|pos| MwEv::Unsmoke { pos }),
+            (any::<u8>(), 0..(1u32 << 31))
+                .prop_map(|(cit, money)| MwEv::CitMoney { cit, money }),
+            (any::<u8>(), 0..(1u32 << 31), any::<u16>())
+                .prop_map(|(cit, money, income)| MwEv::CitIncome { cit, money, income }),
+            (any::<u8>(), any::<i16>())
+                .prop_map(|(cit, amount)| MwEv::CitMoneyTransact { cit, amount }),
+            (any::<u8>(), any::<u16>())
+                .prop_map(|(cit, res)| MwEv::CitRes { cit, res }),
+            (any::<u8>(), any::<u8>(), any::<u8>())
+                .prop_map(|(cit, export, import)| MwEv::CitTradeInfo { cit, export, import }),
+            (arb_plid(), arb_pos()).prop_map(|(plid, pos)| MwEv::Flag { plid, pos }),
+            arb_pos().prop_map(|pos| MwEv::StructureGone { pos }),
+            (arb_pos(), 1..=15u8).prop_map(|(pos, hp)| MwEv::StructureHp { pos, hp }),
+            arb_pos().prop_map(|pos| MwEv::Explode { pos }),
+            (arb_pos(), arb_structure_kind(), any::<u16>())
+                .prop_map(|(pos, kind, pts)| MwEv::BuildNew { pos, kind, pts }),
+            (arb_pos(), any::<u16>(), any::<u16>())
+                .prop_map(|(pos, current, rate)| MwEv::Construction { pos, current, rate }),
+            (arb_pos(), arb_structure_kind())
+                .prop_map(|(pos, kind)| MwEv::RevealStructure { pos, kind }),
+            (arb_pos(), 0..=7u8, any::<bool>())
+                .prop_map(|(pos, digit, asterisk)| MwEv::DigitCapture { pos, digit: MwDigit { digit, asterisk } }),
+            (arb_pos(), 0..4u8)
+                .prop_map(|(pos, item)| MwEv::RevealItem { pos, item: ItemKind::from_u8(item).unwrap() }),
+            (arb_pos(), 0..8u8)
+                .prop_map(|(pos, kind)| MwEv::TileKind { pos, kind: TileKind::from_u8(kind).unwrap() }),
+            (arb_pos(), 1..=MAX_PLIDS)
+                .prop_map(|(pos, plid)| MwEv::TileOwner { pos, plid: PlayerId::from(plid) }),
+        ]
+    }
+
+    /// Sequences of events, with plenty of runs that the encoders
+    /// pack into multi-tile messages
+    fn arb_evs() -> impl Strategy<Value = Vec<MwEv>> {
+        let run = prop_oneof![
+            arb_ev().prop_map(|ev| vec![ev]),
+            prop::collection::vec(arb_pos(), 1..40)
+                .prop_map(|v| v.into_iter().map(|pos| MwEv::Explode { pos }).collect()),
+            prop::collection::vec((arb_pos(), 0..=7u8, any::<bool>()), 1..40)
+                .prop_map(|v| v.into_iter().map(|(pos, digit, asterisk)| {
+                    MwEv::DigitCapture { pos, digit: MwDigit { digit, asterisk } }
+                }).collect()),
+            (1..=MAX_PLIDS, prop::collection::vec(arb_pos(), 1..40))
+                .prop_map(|(plid, v)| v.into_iter().map(|pos| {
+                    MwEv::TileOwner { pos, plid: PlayerId::from(plid) }
+                }).collect()),
+        ];
+        prop::collection::vec(run, 0..16)
+            .prop_map(|runs: Vec<Vec<MwEv>>| runs.concat())
+    }
+
+    fn bin_encode(evs: &[MwEv]) -> Vec<u8> {
+        let mut buf = Vec::new();
+        let (n_msgs, n_bytes) = MsgBinWrite::new().write_many(&mut buf, evs, usize::MAX).unwrap();
+        assert_eq!(n_msgs, evs.len());
+        assert_eq!(n_bytes, buf.len());
+        buf
+    }
+
+    fn bin_decode(mut data: &[u8]) -> Result<Vec<MwEv>, bin::MsgBinReadError> {
+        let mut out = Vec::new();
+        MsgBinRead::new().read_all(&mut data, &mut out)?;
+        Ok(out)
+    }
+
+    fn asm_encode(evs: &[MwEv]) -> Vec<u8> {
+        let mut buf = Vec::new();
+        let (n_msgs, n_bytes) = MsgAsmWrite::new().write_many(&mut buf, evs, usize::MAX).unwrap();
+        assert_eq!(n_msgs, evs.len());
+        assert_eq!(n_bytes, buf.len());
+        buf
+    }
+
+    fn asm_decode(mut data: &[u8]) -> Result<Vec<MwEv>, asm::MsgAsmReadError> {
+        let mut out = Vec::new();
+        MsgAsmRead::new().read_all(&mut data, &mut out)?;
+        Ok(out)
+    }
+
+    proptest! {
+        #[test]
+        fn bin_roundtrip(evs in arb_evs()) {
+            let bytes = bin_encode(&evs);
+            let decoded = bin_decode(&bytes).unwrap();
+            prop_assert_eq!(&decoded, &evs);
+            prop_assert_eq!(bin_encode(&decoded), bytes);
+        }
+
+        #[test]
+        fn bin_respects_max_bytes(evs in arb_evs(), max_bytes in 0..64usize) {
+            let mut buf = Vec::new();
+            let (n_msgs, n_bytes) = MsgBinWrite::new().write_many(&mut buf, &evs, max_bytes).unwrap();
+            prop_assert!(buf.len() <= max_bytes);
+            prop_assert_eq!(n_bytes, buf.len());
+            prop_assert_eq!(bin_decode(&buf).unwrap(), &evs[..n_msgs]);
+        }
+
+        #[test]
+        fn bin_truncates_strings(name in ".{0,200}") {
+            let ev = MwEv::Player {
+                plid: PlayerId::from(1),
+                subplid: None,
+                ev: PlayerEv::Joined { name: name.clone() },
+            };
+            let bytes = bin_encode(&[ev]);
+            let decoded = bin_decode(&bytes).unwrap();
+            let expected = MwEv::Player {
+                plid: PlayerId::from(1),
+                subplid: None,
+                ev: PlayerEv::Joined { name: name[..name.floor_char_boundary(255)].to_owned() },
+            };
+            prop_assert_eq!(&decoded, &[expected]);
+            prop_assert_eq!(bin_encode(&decoded), bytes);
+        }
+
+        #[test]
+        fn bin_decode_arbitrary(data in prop::collection::vec(any::<u8>(), 0..256)) {
+            if let Ok(evs) = bin_decode(&data) {
+                prop_assert_eq!(bin_decode(&bin_encode(&evs)).unwrap(), evs);
+            }
+        }
+
+        #[test]
+        fn asm_roundtrip(evs in arb_evs(), nop in any::<bool>()) {
+            let mut evs = evs;
+            if nop {
+                evs.push(MwEv::Nop);
+            }
+            let text = asm_encode(&evs);
+            let decoded = asm_decode(&text).unwrap();
+            prop_assert_eq!(&decoded, &evs);
+            prop_assert_eq!(asm_encode(&decoded), text);
+        }
+
+        #[test]
+        fn asm_decode_arbitrary(text in prop_oneof![
+            any::<String>(),
+            "((DEBUG|PLAYER|TILE|DIGITS|OWNER|FLAG|CITRES|CITTRANS|CITMONEY|CITINCOME|CITTRADE|STRUCT|NOSTRUCT|STRUCTHP|BUILDNEW|BUILD|ITEM|EXPLODE|SMOKE|UNSMOKE|SHAKE|NOP|JOIN|RTT|VOTE)?( [-0-9a-zA-Z*/,;]{1,8}){0,6}\n){1,8}",
+        ]) {
+            if let Ok(evs) = asm_decode(text.as_bytes()) {
+                prop_assert_eq!(asm_decode(&asm_encode(&evs)).unwrap(), evs);
+            }
+        }
+    }
+
+    #[test]
+    fn bin_rejects_unrepresentable() {
+        let bad = [
+            MwEv::TileOwner { pos: Pos(0, 0), plid: PlayerId::Neutral },
+            MwEv::StructureHp { pos: Pos(0, 0), hp: 0 },
+            MwEv::StructureHp { pos: Pos(0, 0), hp: 16 },
+            MwEv::CitMoney { cit: 0, money: 1 << 31 },
+            MwEv::Player { plid: PlayerId::from(16), subplid: None, ev: PlayerEv::Kicked },
+            MwEv::Player { plid: PlayerId::from(1), subplid: Some(MAX_SUBPLIDS), ev: PlayerEv::Kicked },
+        ];
+        for ev in bad {
+            let mut buf = Vec::new();
+            assert!(MsgBinWrite::new().write(&mut buf, &[ev.clone()], usize::MAX).is_err(), "{:?}", ev);
+        }
+    }
+}
@@ -89,6 +89,7 @@ impl MsgWriter for MsgAsmWrite {
                     PlayerEv::Disconnected => write!(&mut self.buf, " LEAVE")?,
                     PlayerEv::Kicked => write!(&mut self.buf, " KICK")?,
                     PlayerEv::MatchTimeRemain { secs } => write!(&mut self.buf, " TIMELIMIT {}", *secs)?,
+                    PlayerEv::Standing { rank, tiles, lives, elim_order, elim_secs } => write!(&mut self.buf, " STANDING {} {} {} {} {}", rank, tiles, lives, elim_order, elim_secs)?,
                     PlayerEv::ChatAll { text } => write!(&mut self.buf, " CHATALL {}", text)?,
                     PlayerEv::ChatFriendly { text } => write!(&mut self.buf, " CHAT {}", text)?,
                     PlayerEv::VoteNew { id, l10nkey } => write!(&mut self.buf, " VOTENEW {} {}", id, l10nkey)?,
@@ -216,7 +217,7 @@ impl MsgWriter for MsgAsmWrite {
                     TileKind::Regular => "regular",
                     TileKind::Fertile => "fertile",
                     TileKind::FoundationStruct => "foundation",
-                    TileKind::FoundationRoad => "foundation",
+                    TileKind::FoundationRoad => "roadfoundation",
                     TileKind::Destroyed => "destroyed",
                     TileKind::Mountain => "mountain",
                     TileKind::Forest => "forest",
@@ -288,7 +289,7 @@ impl MsgReader for MsgAsmRead {
                     None
                 } else {
                     let Ok(subplid) = arg_subplid.parse::<u8>() else {
-                        return Err(MsgAsmReadError::BadArg(arg_plid.to_owned()));
+                        return Err(MsgAsmReadError::BadArg(arg_subplid.to_owned()));
                     };
                     Some(subplid)
                 };
@@ -358,6 +359,28 @@ impl MsgReader for MsgAsmRead {
                         };
                         PlayerEv::MatchTimeRemain { secs }
                     },
+                    "STANDING" => {
+                        let mut args = [0u16; 5];
+                        for arg in args.iter_mut() {
+                            let Some(arg_str) = components.next() else {
+                                return Err(MsgAsmReadError::NotEnoughArgs);
+                            };
+                            let Ok(value) = arg_str.parse::<u16>() else {
+                                return Err(MsgAsmReadError::BadArg(arg_str.to_owned()));
+                            };
+                            *arg = value;
+                        }
+                        let [rank, tiles, lives, elim_order, elim_secs] = args;
+                        let to_u8 = |x: u16| u8::try_from(x)
+                            .map_err(|_| MsgAsmReadError::BadArg(x.to_string()));
+                        PlayerEv::Standing {
+                            rank: to_u8(rank)?,
+                            tiles,
+                            lives: to_u8(lives)?,
+                            elim_order: to_u8(elim_order)?,
+                            elim_secs,
+                        }
+                    },
                     "CHAT" => {
                         boundless_args = true;
                         let Some(arg_text) = components.remainder() else {
@@ -448,6 +471,7 @@ impl MsgReader for MsgAsmRead {
                     "FERTILE" => TileKind::Fertile,
                     "DESTROYED" => TileKind::Destroyed,
                     "FOUNDATION" => TileKind::FoundationStruct,
+                    "ROADFOUNDATION" => TileKind::FoundationRoad,
                     "MOUNTAIN" => TileKind::Mountain,
                     "FOREST" => TileKind::Forest,
                     other => {
@@ -492,7 +516,7 @@ impl MsgReader for MsgAsmRead {
                 let Ok(plid) = arg_plid.parse::<u8>() else {
                     return Err(MsgAsmReadError::BadArg(arg_plid.to_owned()));
                 };
-                if plid > 7 {
+                if plid == 0 || plid > MAX_PLIDS {
                     return Err(MsgAsmReadError::BadArg(arg_plid.to_owned()));
                 }
                 let plid = PlayerId::from(plid);
@@ -522,7 +546,7 @@ impl MsgReader for MsgAsmRead {
                 let Ok(plid) = arg_plid.parse::<u8>() else {
                     return Err(MsgAsmReadError::BadArg(arg_plid.to_owned()));
                 };
-                if plid > 7 {
+                if plid > MAX_PLIDS {
                     return Err(MsgAsmReadError::BadArg(arg_plid.to_owned()));
                 }
                 let plid = PlayerId::from(plid);
@@ -755,7 +779,7 @@ impl MsgReader for MsgAsmRead {
                     return Err(MsgAsmReadError::BadArg(arg_current.to_owned()));
                 };
                 let Ok(rate) = arg_rate.parse() else {
-                    return Err(MsgAsmReadError::BadArg(arg_current.to_owned()));
+                    return Err(MsgAsmReadError::BadArg(arg_rate.to_owned()));
                 };
                 out.push(MwEv::Construction {
                     pos, current, rate
@@ -865,5 +889,8 @@ fn parse_pos(s: &str) -> Result<Pos, MsgAsmReadError> {
     let Ok(x) = part_x.parse::<i8>() else {
         return err;
     };
+    if parts.next().is_some() {
+        return err;
+    }
     Ok(Pos(y,x))
 }
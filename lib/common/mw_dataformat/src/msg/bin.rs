@@ -68,33 +68,42 @@ impl MsgWriter for MsgBinWrite {
                 w.write_all(&[0b00001110, *i, pos.y() as u8, pos.x() as u8])?;
             }
             MwEv::Player { plid, subplid, ev: status } => {
-                let (byte_status, n_bytes, strlen) = match status {
+                let (byte_status, n_header, strlen) = match status {
                     PlayerEv::Joined { name } =>         (0b00000000, 4, name.floor_char_boundary(255)),
                     PlayerEv::NetRttInfo { .. } =>       (0b00000001, 4, 0),
-                    PlayerEv::Timeout { .. } =>          (0b00000010, 3, 0),
-                    PlayerEv::TimeoutFinished =>         (0b00000011, 6, 0),
-                    PlayerEv::Exploded { .. } =>         (0b00000100, 4, 0),
-                    PlayerEv::LivesRemain { .. } =>      (0b00000101, 3, 0),
+                    PlayerEv::Timeout { .. } =>          (0b00000010, 4, 0),
+                    PlayerEv::TimeoutFinished =>         (0b00000011, 3, 0),
+                    PlayerEv::Exploded { .. } =>         (0b00000100, 6, 0),
+                    PlayerEv::LivesRemain { .. } =>      (0b00000101, 4, 0),
                     PlayerEv::Protected =>               (0b00000110, 3, 0),
                     PlayerEv::Unprotected =>             (0b00000111, 3, 0),
                     PlayerEv::Eliminated =>              (0b00001000, 3, 0),
                     PlayerEv::Surrendered =>             (0b00001001, 3, 0),
                     PlayerEv::Disconnected =>            (0b00001010, 3, 0),
-                    PlayerEv::Kicked =>                  (0b00001011, 5, 0),
+                    PlayerEv::Kicked =>                  (0b00001011, 3, 0),
                     PlayerEv::ChatAll { text } =>        (0b00010000, 4, text.floor_char_boundary(255)),
                     PlayerEv::ChatFriendly { text }  =>  (0b00010001, 4, text.floor_char_boundary(255)),
-                    PlayerEv::MatchTimeRemain { .. } =>  (0b00010010, 4, 0),
+                    PlayerEv::MatchTimeRemain { .. } =>  (0b00010010, 5, 0),
+                    PlayerEv::Standing { .. } =>         (0b00010100, 10, 0),
                     PlayerEv::VoteNew { l10nkey, .. } => (0b00010011, 5, l10nkey.floor_char_boundary(255)),
                     PlayerEv::VoteNo { .. } =>           (0b00001100, 4, 0),
                     PlayerEv::VoteYes { .. } =>          (0b00001101, 4, 0),
                     PlayerEv::VoteFail { .. } =>         (0b00001110, 4, 0),
                     PlayerEv::VotePass { .. } =>         (0b00001111, 4, 0),
                 };
-                if max_bytes < n_bytes + strlen {
+                n_bytes = n_header + strlen;
+                if max_bytes < n_bytes {
                     return Ok((0, 0));
                 }
-                let byte1 = (u8::from(*plid) & 0x0F) | if let Some(x) = subplid {
-                    (x & 0x0F) << 4
+                if u8::from(*plid) > 0x0F {
+                    return Err(MsgBinWriteError::InvalidValue(u8::from(*plid) as u32));
+                }
+                let byte1 = u8::from(*plid) | if let Some(x) = subplid {
+                    // 15 is reserved to mean "no subplid"
+                    if *x >= 0x0F {
+                        return Err(MsgBinWriteError::InvalidValue(*x as u32));
+                    }
+                    x << 4
                 } else {
                     0xF0
                 };
@@ -109,6 +118,12 @@ impl MsgWriter for MsgBinWrite {
                     PlayerEv::Exploded { pos, killer } => w.write_all(&[pos.y() as u8, pos.x() as u8, u8::from(*killer)])?,
                     PlayerEv::LivesRemain { lives } => w.write_all(&[*lives])?,
                     PlayerEv::MatchTimeRemain { secs } => w.write_all(&secs.to_be_bytes())?,
+                    PlayerEv::Standing { rank, tiles, lives, elim_order, elim_secs } => {
+                        w.write_all(&[*rank])?;
+                        w.write_all(&tiles.to_be_bytes())?;
+                        w.write_all(&[*lives, *elim_order])?;
+                        w.write_all(&elim_secs.to_be_bytes())?;
+                    }
                     PlayerEv::VoteNo { id } => w.write_all(&[*id])?,
                     PlayerEv::VoteYes { id } => w.write_all(&[*id])?,
                     PlayerEv::VoteFail { id } => w.write_all(&[*id])?,
@@ -212,7 +227,8 @@ impl MsgWriter for MsgBinWrite {
                 if max_bytes < n_bytes {
                     return Ok((0, 0));
                 }
-                if *hp > 0x0F {
+                // zero would encode as StructureGone
+                if *hp == 0 || *hp > 0x0F {
                     return Err(MsgBinWriteError::InvalidValue(*hp as u32));
                 }
                 let byte0 = 0b00100000 | *hp;
@@ -265,7 +281,11 @@ impl MsgWriter for MsgBinWrite {
                 if max_bytes < n_bytes {
                     return Ok((0, 0));
                 }
-                let mut byte0 = 0b10000000 | (u8::from(*plid) & 0x0F);
+                // zero would encode as multi-tile Digits
+                if u8::from(*plid) == 0 || u8::from(*plid) > 0x0F {
+                    return Err(MsgBinWriteError::InvalidValue(u8::from(*plid) as u32));
+                }
+                let mut byte0 = 0b10000000 | u8::from(*plid);
                 let mut tilecount = 0;
                 for msg in msgs.iter().skip(1) {
                     if tilecount >= 7 {
@@ -286,10 +306,9 @@ impl MsgWriter for MsgBinWrite {
                 byte0 |= tilecount << 4;
                 w.write_all(&[byte0, pos.y() as u8, pos.x() as u8])?;
                 for msg in msgs.iter().skip(1).take(tilecount as usize) {
-                    let MwEv::TileOwner { pos, plid: _ } = msg else {
-                        unreachable!();
-                    };
-                    w.write_all(&[pos.y() as u8, pos.x() as u8])?;
+                    if let MwEv::TileOwner { pos, plid: _ } = msg {
+                        w.write_all(&[pos.y() as u8, pos.x() as u8])?;
+                    }
                 }
                 n_msgs += tilecount as usize;
             },
@@ -316,10 +335,9 @@ impl MsgWriter for MsgBinWrite {
                 byte0 |= tilecount;
                 w.write_all(&[byte0, pos.y() as u8, pos.x() as u8])?;
                 for msg in msgs.iter().skip(1).take(tilecount as usize) {
-                    let MwEv::Explode { pos } = msg else {
-                        unreachable!();
-                    };
-                    w.write_all(&[pos.y() as u8, pos.x() as u8])?;
+                    if let MwEv::Explode { pos } = msg {
+                        w.write_all(&[pos.y() as u8, pos.x() as u8])?;
+                    }
                 }
                 n_msgs += tilecount as usize;
             },
@@ -360,10 +378,9 @@ impl MsgWriter for MsgBinWrite {
                     byte0 |= tilecount << 4;
                     w.write_all(&[byte0, pos.y() as u8, pos.x() as u8])?;
                     for msg in msgs.iter().skip(1).take(tilecount as usize) {
-                        let MwEv::DigitCapture { pos, .. } = msg else {
-                            unreachable!();
-                        };
-                        w.write_all(&[pos.y() as u8, pos.x() as u8])?;
+                        if let MwEv::DigitCapture { pos, .. } = msg {
+                            w.write_all(&[pos.y() as u8, pos.x() as u8])?;
+                        }
                     }
                     let mut high = false;
                     let mut digbyte = 0;
@@ -371,7 +388,7 @@ impl MsgWriter for MsgBinWrite {
                     digbyte |= (*digit & 0x07) << 4;
                     for msg in msgs.iter().skip(1).take(tilecount as usize) {
                         let MwEv::DigitCapture { digit: MwDigit { digit, asterisk }, .. } = msg else {
-                            unreachable!();
+                            continue;
                         };
                         if high {
                             digbyte |= (*asterisk as u8) << 7;
@@ -548,6 +565,17 @@ impl MsgReader for MsgBinRead {
                     out.push(MwEv::Player { plid, subplid, ev: PlayerEv::VoteNew { id, l10nkey } });
                     Ok(1)
                 }
+                0b00010100 => {
+                    let mut data = [0; 7];
+                    r.read_exact(&mut data)?;
+                    let rank = data[0];
+                    let tiles = u16::from_be_bytes([data[1], data[2]]);
+                    let lives = data[3];
+                    let elim_order = data[4];
+                    let elim_secs = u16::from_be_bytes([data[5], data[6]]);
+                    out.push(MwEv::Player { plid, subplid, ev: PlayerEv::Standing { rank, tiles, lives, elim_order, elim_secs } });
+                    Ok(1)
+                }
                 _ => Err(MsgBinReadError::UnknownPlayerOp(bytes[1])),
             };
         }
@@ -581,6 +609,7 @@ impl MsgReader for MsgBinRead {
                 let income = u16::from_be_bytes([
                     bytes[0], bytes[1]
                 ]);
+                let money = money & !(1 << 31);
                 out.push(MwEv::CitIncome { cit, money, income });
             } else {
                 out.push(MwEv::CitMoney { cit, money });
@@ -758,7 +787,7 @@ impl MsgReader for MsgBinRead {
                 pos.set_y(bytes[i * 2 + 0] as i8);
                 pos.set_x(bytes[i * 2 + 1] as i8);
                 let off_digit = n_tiles * 2 + i / 2;
-                let (asterisk, digit) = if i / 2 == 0 {
+                let (asterisk, digit) = if i % 2 == 0 {
                     (
                         bytes[off_digit] & 0b10000000 != 0,
                         (bytes[off_digit] & 0b01110000) >> 4,
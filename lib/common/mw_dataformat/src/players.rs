@@ -0,0 +1,142 @@
+//! Encoding and Decoding of the Players Roster
+//!
+//! The roster describes who participated in a game: one entry per plid
+//! (starting with the spectator at plid 0), each with the users
+//! (subplids) that were controlling it.
+
+use thiserror::Error;
+
+/// Error when deserializing players data.
+#[derive(Debug, Error)]
+pub enum PlayersDecodeError {
+    /// The input buffer ended before all the entries were decoded
+    #[error("Players data is truncated")]
+    Truncated,
+    /// There is more data in the input buffer after all the entries
+    #[error("Players data has {0} unexpected trailing bytes")]
+    Trailing(usize),
+    /// A display name is not valid UTF-8
+    #[error("Player name is not valid UTF-8")]
+    BadName,
+}
+
+/// Info about a plid (team/empire) in the game
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PlidInfo {
+    /// sRGB color used to display the plid (unused for the spectator)
+    pub color: [u8; 3],
+    /// The users in control of this plid, indexed by subplid
+    pub subplids: Vec<SubPlidInfo>,
+}
+
+/// Info about a user (subplid) in the game
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SubPlidInfo {
+    pub display_name: String,
+}
+
+/// Encode the players roster.
+///
+/// `players` is indexed by plid, starting with the spectator.
+/// At most 255 entries/subplids will be encoded and display
+/// names are truncated to 255 bytes.
+///
+/// The binary players data will be appended to `out`.
+pub fn serialize_players(players: &[PlidInfo], out: &mut Vec<u8>) {
+    let n_plids = players.len().min(255);
+    out.push(n_plids as u8);
+    for plid in &players[..n_plids] {
+        out.extend_from_slice(&plid.color);
+        let n_subplids = plid.subplids.len().min(255);
+        out.push(n_subplids as u8);
+        for sub in &plid.subplids[..n_subplids] {
+            let name_len = sub.display_name.floor_char_boundary(255);
+            out.push(name_len as u8);
+            out.extend_from_slice(&sub.display_name.as_bytes()[..name_len]);
+        }
+    }
+}
+
+/// Decode the players roster.
+///
+/// `input` must contain exactly the encoded players data, nothing more.
+pub fn deserialize_players(mut input: &[u8]) -> Result<Vec<PlidInfo>, PlayersDecodeError> {
+    let mut take = |n: usize| -> Result<&[u8], PlayersDecodeError> {
+        if input.len() < n {
+            return Err(PlayersDecodeError::Truncated);
+        }
+        let (r, rest) = input.split_at(n);
+        input = rest;
+        Ok(r)
+    };
+    let n_plids = take(1)?[0];
+    let mut players = Vec::with_capacity(n_plids as usize);
+    for _ in 0..n_plids {
+        let color = take(3)?.try_into().unwrap();
+        let n_subplids = take(1)?[0];
+        let mut subplids = Vec::with_capacity(n_subplids as usize);
+        for _ in 0..n_subplids {
+            let name_len = take(1)?[0];
+            let name = std::str::from_utf8(take(name_len as usize)?)
+                .map_err(|_| PlayersDecodeError::BadName)?;
+            subplids.push(SubPlidInfo {
+                display_name: name.to_owned(),
+            });
+        }
+        players.push(PlidInfo { color, subplids });
+    }
+    if !input.is_empty() {
+        return Err(PlayersDecodeError::Trailing(input.len()));
+    }
+    Ok(players)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn players_roundtrip() {
+        let players = vec![
+            PlidInfo {
+                color: [0, 0, 0],
+                subplids: vec![SubPlidInfo { display_name: "watcher".into() }],
+            },
+            PlidInfo {
+                color: [255, 0, 128],
+                subplids: vec![
+                    SubPlidInfo { display_name: "alice".into() },
+                    SubPlidInfo { display_name: "бобъ".into() },
+                ],
+            },
+            PlidInfo {
+                color: [0, 200, 50],
+                subplids: vec![],
+            },
+        ];
+        let mut buf = Vec::new();
+        serialize_players(&players, &mut buf);
+        assert_eq!(deserialize_players(&buf).unwrap(), players);
+        assert!(matches!(
+            deserialize_players(&buf[..buf.len() - 1]),
+            Err(PlayersDecodeError::Truncated)
+        ));
+        buf.push(0);
+        assert!(matches!(
+            deserialize_players(&buf),
+            Err(PlayersDecodeError::Trailing(1))
+        ));
+    }
+
+    #[test]
+    fn long_name_truncated() {
+        let players = vec![PlidInfo {
+            color: [1, 2, 3],
+            subplids: vec![SubPlidInfo { display_name: "é".repeat(200) }],
+        }];
+        let mut buf = Vec::new();
+        serialize_players(&players, &mut buf);
+        let decoded = deserialize_players(&buf).unwrap();
+        assert_eq!(decoded[0].subplids[0].display_name, "é".repeat(127));
+    }
+}
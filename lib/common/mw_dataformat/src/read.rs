@@ -1,11 +1,11 @@
 //! Reading/Decoding MineWars Data Streams or Files
 
-use mw_common::{grid::*, phoneme::Ph, plid::PlayerId};
+use mw_common::{game::{update_view_map, MwRules, ViewTileData}, grid::*, phoneme::Ph, plid::PlayerId};
 use thiserror::Error;
 
 use std::{io::{Cursor, Read, Seek, SeekFrom}, iter::FusedIterator};
 
-use crate::{header::{ISHeader, MwFileHeader}, map::MapTileDataIn, FORMAT_VERSION};
+use crate::{header::{ISHeader, MwFileHeader}, index::{MwFrameIndex, MwFrameIndexEntry, INDEX_MAGIC}, keyframe::{MwKeyframe, MwKeyframes, MwViewSnapshot, KEYFRAMES_MAGIC}, map::MapTileDataIn, msg::{bin::{MsgBinRead, MsgBinReadError}, MsgReader}, players::PlidInfo, trailer::{find_section, TrailerDecodeError}, FORMAT_VERSION};
 
 #[derive(Debug, Error)]
 pub enum ChecksumError {
@@ -31,8 +31,18 @@ pub enum MwReaderError {
     Compression(#[from] lz4_flex::block::DecompressError),
     #[error("Map data cannot be decoded: {0}")]
     Map(#[from] crate::map::MapDecodeError),
+    #[error("Rules data cannot be decoded: {0}")]
+    Rules(#[from] crate::rules::RulesDecodeError),
+    #[error("Players data cannot be decoded: {0}")]
+    Players(#[from] crate::players::PlayersDecodeError),
     #[error("Wrong grid topology (hex/sq).")]
     WrongTopology,
+    #[error("Trailing section cannot be decoded: {0}")]
+    Trailer(#[from] TrailerDecodeError),
+    #[error("Frame messages cannot be decoded: {0}")]
+    Msg(#[from] MsgBinReadError),
+    #[error("Data too large: {len} bytes (limit is {max}).")]
+    TooLarge { len: usize, max: usize },
 }
 
 /// MineWars Decoder (for Full data / with file header)
@@ -69,6 +79,8 @@ pub enum MwFrameReader<'b, 's, R: Read + Seek> {
 }
 
 pub struct MwFrameDataReader<'b, R: Read + Seek> {
+    off_start: u64,
+    off_end: Option<u64>,
     off_data: u64,
     current_time_ms: u64,
     max_plid: u8,
@@ -161,6 +173,8 @@ impl<'b, R: Read + Seek> MwFileReader<'b, R> {
             };
             Ok(MwFrameReader::Compressed(
                 MwFrameDataReader {
+                    off_start: 0,
+                    off_end: None,
                     off_data: 0,
                     current_time_ms: 0,
                     max_plid: self.is_header.max_plid(),
@@ -173,6 +187,8 @@ impl<'b, R: Read + Seek> MwFileReader<'b, R> {
         } else {
             Ok(MwFrameReader::Uncompressed(
                 MwFrameDataReader {
+                    off_start: offset_framedata,
+                    off_end: Some(offset_framedata + self.file_header.len_framedata_compressed() as u64),
                     off_data: offset_framedata,
                     current_time_ms: 0,
                     max_plid: self.is_header.max_plid(),
@@ -208,6 +224,51 @@ impl<'b, R: Read + Seek> MwFileReader<'b, R> {
     pub fn is_framedata_compressed(&self) -> bool {
         self.file_header.is_framedata_compressed()
     }
+    /// Where the frame data ends (and any trailing sections begin)
+    pub fn offset_framedata_end(&self) -> usize {
+        self.offset_framedata() + self.file_header.len_framedata_compressed()
+    }
+    /// Read all the trailing sections after the frame data (empty if none)
+    pub fn read_trailer(&mut self) -> Result<&[u8], MwReaderError> {
+        let off_trailer = self.offset_framedata_end() as u64;
+        let len_file = self.reader.seek(SeekFrom::End(0))?;
+        self.buf.clear();
+        if len_file > off_trailer {
+            self.reader.seek(SeekFrom::Start(off_trailer))?;
+            self.reader.read_to_end(self.buf)?;
+        }
+        Ok(self.buf)
+    }
+    /// Read the seek index stored after the frame data
+    ///
+    /// Returns `None` if the file does not have one.
+    pub fn read_frame_index(&mut self) -> Result<Option<MwFrameIndex>, MwReaderError> {
+        let trailer = self.read_trailer()?;
+        let data = match find_section(trailer, INDEX_MAGIC) {
+            Ok(Some(data)) => data,
+            Ok(None) => return Ok(None),
+            // older files may have a bare index instead of trailing sections
+            Err(e) => return MwFrameIndex::deserialize_legacy(trailer)
+                .map(Some)
+                .map_err(|_| e.into()),
+        };
+        Ok(Some(MwFrameIndex::deserialize(data)?))
+    }
+    /// Read the keyframes stored after the frame data
+    ///
+    /// Returns `None` if the file does not have any.
+    pub fn read_keyframes(&mut self, scratch: &mut Vec<u8>) -> Result<Option<MwKeyframes>, MwReaderError> {
+        let map_size = self.is_header.map_size;
+        let trailer = self.read_trailer()?;
+        let data = match find_section(trailer, KEYFRAMES_MAGIC) {
+            Ok(Some(data)) => data,
+            Ok(None) => return Ok(None),
+            // older files may have a bare index (and nothing else) instead
+            Err(_) if MwFrameIndex::deserialize_legacy(trailer).is_ok() => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        Ok(Some(MwKeyframes::deserialize(data, map_size, scratch)?))
+    }
     pub fn verify_checksum_header(&mut self) -> Result<(), MwReaderError> {
         if self.compute_new_checksum_header()? != self.file_header.checksum_header {
             return Err(ChecksumError::BadHeader.into());
@@ -347,6 +408,40 @@ impl<'b, R: Read + Seek> MwISReader<'b, R> {
         self.reader.read_exact(self.buf)?;
         Ok(bytemuck::cast_slice(&self.buf))
     }
+    /// Get the encoded rules data, as-is
+    pub fn read_rules_raw(&mut self) -> Result<&[u8], MwReaderError> {
+        self.buf.resize(self.is_header.len_rules(), 0);
+        self.reader.seek(SeekFrom::Start(self.off_data as u64 + self.is_header.offset_rules() as u64))?;
+        self.reader.read_exact(self.buf)?;
+        Ok(&self.buf)
+    }
+    /// Decode the rules data
+    ///
+    /// Returns `None` if the file does not contain rules.
+    pub fn read_rules(&mut self) -> Result<Option<MwRules>, MwReaderError> {
+        if self.is_header.len_rules() == 0 {
+            return Ok(None);
+        }
+        let data = self.read_rules_raw()?;
+        Ok(Some(crate::rules::deserialize_rules(data)?))
+    }
+    /// Get the encoded players data, as-is
+    pub fn read_players_raw(&mut self) -> Result<&[u8], MwReaderError> {
+        self.buf.resize(self.is_header.len_players(), 0);
+        self.reader.seek(SeekFrom::Start(self.off_data as u64 + self.is_header.offset_players() as u64))?;
+        self.reader.read_exact(self.buf)?;
+        Ok(&self.buf)
+    }
+    /// Decode the players roster (indexed by plid, starting with the spectator)
+    ///
+    /// Returns an empty list if the file does not contain players info.
+    pub fn read_players(&mut self) -> Result<Vec<PlidInfo>, MwReaderError> {
+        if self.is_header.len_players() == 0 {
+            return Ok(Vec::new());
+        }
+        let data = self.read_players_raw()?;
+        Ok(crate::players::deserialize_players(data)?)
+    }
     pub fn read_cits_names(&mut self) -> Result<CitNamesIter<'_>, MwReaderError> {
         self.buf.resize(self.is_header.len_citdata_names(), 0);
         self.reader.seek(SeekFrom::Start(self.off_data as u64 + self.is_header.offset_citdata_names() as u64))?;
@@ -360,18 +455,44 @@ impl<'b, R: Read + Seek> MwISReader<'b, R> {
 }
 
 impl<'b, R: Read + Seek> MwFrameDataReader<'b, R> {
+    /// Create a reader for bare frame data, without an IS or file header
+    ///
+    /// Starts at the current position of `reader`. `max_plid` must
+    /// match the IS that the frames were encoded with.
+    pub fn new(mut reader: R, buf: &'b mut Vec<u8>, max_plid: u8) -> Result<Self, MwReaderError> {
+        let off_data = reader.stream_position()?;
+        Ok(Self {
+            off_start: off_data,
+            off_end: None,
+            off_data,
+            current_time_ms: 0,
+            max_plid,
+            n_views: 0,
+            frame_kind: FrameKind::Unknown,
+            buf,
+            reader,
+        })
+    }
+    pub fn max_plid(&self) -> u8 {
+        self.max_plid
+    }
     pub fn current_time_ms(&self) -> u64 {
         self.current_time_ms
     }
     pub fn advance_next_frame(&mut self) -> Result<(), MwReaderError> {
         self.off_data += self.offset_next_frame();
+        if self.off_end.map(|end| self.off_data >= end).unwrap_or(false) {
+            // do not decode anything after the frame data
+            self.frame_kind = FrameKind::Unknown;
+            return Err(std::io::Error::from(std::io::ErrorKind::UnexpectedEof).into());
+        }
         self.buf.resize(2, 0);
         self.reader.seek(SeekFrom::Start(self.off_data))?;
         self.reader.read_exact(self.buf)?;
         let h_delta = u16::from_be_bytes([self.buf[0], self.buf[1]]);
         if h_delta & !(1 << 15) == !(1 << 15) {
             // Keepalive frame
-            self.current_time_ms += 1 << 15;
+            self.current_time_ms += 0x7FFF;
             self.frame_kind = FrameKind::Keepalive;
         } else if h_delta & (1 << 15) != 0 {
             // Homogenous frame
@@ -403,13 +524,125 @@ impl<'b, R: Read + Seek> MwFrameDataReader<'b, R> {
         }
         Ok(())
     }
+    /// Go back to the start of the frame data
+    pub fn rewind(&mut self) {
+        self.seek_to_entry(&MwFrameIndexEntry {
+            time_ms: 0,
+            offset: 0,
+        });
+    }
+    /// Jump to a point in the frame data, as given by an index entry
+    ///
+    /// The next call to `advance_next_frame` will decode the frame there.
+    pub fn seek_to_entry(&mut self, entry: &MwFrameIndexEntry) {
+        self.off_data = self.off_start + entry.offset as u64;
+        self.current_time_ms = entry.time_ms;
+        self.n_views = 0;
+        self.frame_kind = FrameKind::Unknown;
+    }
+    /// Jump as close as possible before `time_ms`, using an index
+    ///
+    /// The frames that follow may still be earlier than `time_ms`.
+    pub fn seek_time(&mut self, index: &MwFrameIndex, time_ms: u64) {
+        match index.lookup(time_ms) {
+            Some(entry) => self.seek_to_entry(entry),
+            None => self.rewind(),
+        }
+    }
+    /// Scan all the frames to build an index, with entries at least `interval_ms` apart
+    ///
+    /// Rewinds back to the start afterwards.
+    pub fn build_index(&mut self, interval_ms: u64) -> Result<MwFrameIndex, MwReaderError> {
+        self.rewind();
+        let mut index = MwFrameIndex::new();
+        loop {
+            let entry = MwFrameIndexEntry {
+                time_ms: self.current_time_ms,
+                offset: (self.off_data + self.offset_next_frame() - self.off_start) as u32,
+            };
+            match self.advance_next_frame() {
+                Ok(()) => {}
+                Err(MwReaderError::Io(e)) if e.kind() == std::io::ErrorKind::UnexpectedEof => break,
+                Err(e) => return Err(e),
+            }
+            let due = index.entries().last()
+                .map(|last| entry.time_ms >= last.time_ms + interval_ms)
+                .unwrap_or(true);
+            if due {
+                index.push(entry);
+            }
+        }
+        self.rewind();
+        Ok(index)
+    }
+    /// Scan all the frames to build keyframes, at least `interval_ms` apart
+    ///
+    /// `initial` is the view of the map at the start of the game. All
+    /// plids (including the spectator view) get a snapshot in every keyframe.
+    ///
+    /// Rewinds back to the start afterwards.
+    pub fn build_keyframes(
+        &mut self,
+        initial: &MapDataPos<ViewTileData>,
+        interval_ms: u64,
+    ) -> Result<MwKeyframes, MwReaderError> {
+        self.rewind();
+        let mut views: Vec<_> = (0..=self.max_plid).map(|_| initial.clone()).collect();
+        let mut keyframes = MwKeyframes::new();
+        let mut last_time_ms = 0;
+        let mut r_bin = MsgBinRead::new();
+        let mut msgs = vec![];
+        loop {
+            let entry = MwFrameIndexEntry {
+                time_ms: self.current_time_ms,
+                offset: (self.off_data + self.offset_next_frame() - self.off_start) as u32,
+            };
+            match self.advance_next_frame() {
+                Ok(()) => {}
+                Err(MwReaderError::Io(e)) if e.kind() == std::io::ErrorKind::UnexpectedEof => break,
+                Err(e) => return Err(e),
+            }
+            // there is no point in a keyframe at the very start
+            if entry.time_ms > 0 && entry.time_ms >= last_time_ms + interval_ms {
+                keyframes.push(MwKeyframe {
+                    entry,
+                    views: views.iter().enumerate().map(|(i, map)| MwViewSnapshot {
+                        plid: PlayerId::from(i as u8),
+                        map: map.clone(),
+                    }).collect(),
+                });
+                last_time_ms = entry.time_ms;
+            }
+            for (i, stream) in self.iter_streams().enumerate() {
+                let mut stream = stream?;
+                if stream.is_empty() {
+                    continue;
+                }
+                msgs.clear();
+                r_bin.read_all(&mut stream, &mut msgs)?;
+                let plid = PlayerId::from(i as u8);
+                for ev in msgs.iter() {
+                    update_view_map(&mut views[i], plid, ev);
+                }
+            }
+        }
+        self.rewind();
+        Ok(keyframes)
+    }
+    /// Jump to a keyframe
+    ///
+    /// The next call to `advance_next_frame` will decode the first frame
+    /// that is not included in the keyframe's snapshots.
+    pub fn seek_to_keyframe(&mut self, keyframe: &MwKeyframe) {
+        self.seek_to_entry(&keyframe.entry);
+    }
     pub fn iter_streams(&mut self) -> MwFrameStreamIter<'_, 'b, R> {
         MwFrameStreamIter {
             i: 0,
             b_mask: 0,
             i_mask: self.len_plidsmask(),
             i_stream: 0,
-            off_stream: self.len_plidsmask() as u64 + self.n_views as u64,
+            off_stream: 2 + self.len_plidsmask() as u64 + self.n_views as u64,
             reader: self,
         }
     }
@@ -431,7 +664,7 @@ impl<'b, R: Read + Seek> MwFrameDataReader<'b, R> {
                 let plid = u8::from(plid);
                 let offset_lens = self.len_plidsmask();
                 let base_len = self.len_plidsmask() + self.n_views as usize;
-                let mut offset_stream = base_len as u64;
+                let mut offset_stream = 2 + base_len as u64;
                 let len_stream;
                 let mut buf_lens = &self.buf[offset_lens..];
                 let mut i = 0;
@@ -440,7 +673,7 @@ impl<'b, R: Read + Seek> MwFrameDataReader<'b, R> {
                         len_stream = buf_lens[0] as usize + 1;
                         break;
                     }
-                    let mask_byte = self.len_plidsmask() - i as usize / 8; // Big Endian
+                    let mask_byte = self.len_plidsmask() - 1 - i as usize / 8; // Big Endian
                     let mask_bit = i % 8;
                     if self.buf[mask_byte] & (1 << mask_bit) != 0 {
                         offset_stream += buf_lens[0] as u64 + 1;
@@ -455,8 +688,19 @@ impl<'b, R: Read + Seek> MwFrameDataReader<'b, R> {
             }
         }
     }
+    /// Append the encoded bytes of the current frame (as-is) to `out`
+    ///
+    /// Useful for copying frames into another file.
+    pub fn read_raw_frame(&mut self, out: &mut Vec<u8>) -> Result<(), MwReaderError> {
+        let len = self.offset_next_frame() as usize;
+        let start = out.len();
+        out.resize(start + len, 0);
+        self.reader.seek(SeekFrom::Start(self.off_data))?;
+        self.reader.read_exact(&mut out[start..])?;
+        Ok(())
+    }
     fn len_plidsmask(&self) -> usize {
-        (self.max_plid as usize + 1) / 8 + 1
+        self.max_plid as usize / 8 + 1
     }
     fn offset_next_frame(&self) -> u64 {
         let len_plidsmask = self.len_plidsmask();
@@ -485,7 +729,7 @@ impl<'b, R: Read + Seek> MwFrameDataReader<'b, R> {
         if plid > self.max_plid {
             return false;
         }
-        let mask_byte = self.len_plidsmask() - plid as usize / 8; // Big Endian
+        let mask_byte = self.len_plidsmask() - 1 - plid as usize / 8; // Big Endian
         let mask_bit = plid % 8;
         self.buf[mask_byte] & (1 << mask_bit) != 0
     }
@@ -579,12 +823,399 @@ impl<'a, 'b, R: Read + Seek> Iterator for MwFrameStreamIter<'a, 'b, R> {
 
 impl<'a, 'b, R: Read + Seek> FusedIterator for MwFrameStreamIter<'a, 'b, R> {}
 
+/// Incremental MineWars Decoder (for network streams, pipes, etc.)
+///
+/// Unlike the other readers, this does not need `Read + Seek`. Instead,
+/// it is push-based: feed it bytes as they arrive, in chunks of any size,
+/// using `push`. Then call `decode_next` repeatedly, to get any IS sections and
+/// frames that have become complete, until it returns `None`.
+///
+/// By default, it expects the network stream format: an IS followed by
+/// frames. Use `new_file` if the data starts with a `MwFileHeader`.
+/// Anything in a file after the frame data (such as a seek index) is skipped.
+///
+/// The sizes of the IS sections and of compressed frame data come from the
+/// (untrusted) headers, and the decoder has to buffer them whole. If any of
+/// them is bigger than the limit (see `with_max_len`), decoding fails with
+/// `MwReaderError::TooLarge`, instead of buffering without bound.
+pub struct MwStreamDecoder {
+    data: Vec<u8>,
+    off: usize,
+    max_len: usize,
+    state: StreamState,
+    len_frames_remaining: Option<usize>,
+    file_header: Option<MwFileHeader>,
+    is_header: Option<ISHeader>,
+    current_time_ms: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum StreamState {
+    FileHeader,
+    ISHeader,
+    IS(ISSection),
+    FramesCompressed,
+    Frames,
+    Trailer,
+}
+
+/// The parts of the IS data, in the order they are encoded
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ISSection {
+    MapData,
+    CitsPos,
+    CitsNames,
+    Rules,
+    Players,
+}
+
+/// Something that `MwStreamDecoder` has finished decoding
+pub enum MwStreamItem<'a> {
+    /// Only if decoding a file (see `MwStreamDecoder::new_file`)
+    FileHeader(MwFileHeader),
+    /// The IS header. The IS sections will follow.
+    ISHeader(ISHeader),
+    /// The encoded data of one IS section, as-is
+    ///
+    /// Sections that are absent (zero length) are skipped.
+    ISSection(ISSection, &'a [u8]),
+    /// A complete frame
+    Frame(MwStreamFrame<'a>),
+}
+
+/// A frame decoded by `MwStreamDecoder`
+#[derive(Clone, Copy)]
+pub struct MwStreamFrame<'a> {
+    time_ms: u64,
+    max_plid: u8,
+    frame_kind: FrameKind,
+    data: &'a [u8],
+}
+
+impl ISSection {
+    pub fn len(self, is_header: &ISHeader) -> usize {
+        match self {
+            ISSection::MapData => is_header.len_mapdata_compressed(),
+            ISSection::CitsPos => is_header.len_citdata_pos(),
+            ISSection::CitsNames => is_header.len_citdata_names(),
+            ISSection::Rules => is_header.len_rules(),
+            ISSection::Players => is_header.len_players(),
+        }
+    }
+    fn next(self) -> Option<ISSection> {
+        match self {
+            ISSection::MapData => Some(ISSection::CitsPos),
+            ISSection::CitsPos => Some(ISSection::CitsNames),
+            ISSection::CitsNames => Some(ISSection::Rules),
+            ISSection::Rules => Some(ISSection::Players),
+            ISSection::Players => None,
+        }
+    }
+}
+
+impl MwStreamDecoder {
+    /// The default limit for `with_max_len`
+    pub const DEFAULT_MAX_LEN: usize = 64 << 20;
+
+    /// Create a decoder for a stream that starts with an IS
+    pub fn new() -> Self {
+        Self::with_state(StreamState::ISHeader)
+    }
+    /// Create a decoder for the contents of a MineWars file
+    ///
+    /// If the frame data is compressed, no frames can be decoded
+    /// until all of it has been received.
+    pub fn new_file() -> Self {
+        Self::with_state(StreamState::FileHeader)
+    }
+    fn with_state(state: StreamState) -> Self {
+        Self {
+            data: Vec::new(),
+            off: 0,
+            max_len: Self::DEFAULT_MAX_LEN,
+            state,
+            len_frames_remaining: None,
+            file_header: None,
+            is_header: None,
+            current_time_ms: 0,
+        }
+    }
+    /// Set the maximum size (in bytes) of any single item to be buffered
+    pub fn with_max_len(mut self, max_len: usize) -> Self {
+        self.max_len = max_len;
+        self
+    }
+    fn check_len(&self, len: usize) -> Result<(), MwReaderError> {
+        if len > self.max_len {
+            return Err(MwReaderError::TooLarge { len, max: self.max_len });
+        }
+        Ok(())
+    }
+    /// Add more received bytes to be decoded
+    pub fn push(&mut self, bytes: &[u8]) {
+        // discard what has already been decoded
+        if self.off != 0 {
+            self.data.drain(..self.off);
+            self.off = 0;
+        }
+        self.data.extend_from_slice(bytes);
+    }
+    /// How many bytes have been received, but not decoded yet
+    ///
+    /// If this is not zero at the end of the input, the data was truncated.
+    pub fn len_pending(&self) -> usize {
+        self.data.len() - self.off
+    }
+    pub fn file_header(&self) -> Option<&MwFileHeader> {
+        self.file_header.as_ref()
+    }
+    pub fn is_header(&self) -> Option<&ISHeader> {
+        self.is_header.as_ref()
+    }
+    /// Have we gotten through the IS and are now decoding frames?
+    pub fn is_in_frames(&self) -> bool {
+        matches!(self.state, StreamState::Frames | StreamState::FramesCompressed | StreamState::Trailer)
+    }
+    /// The timestamp of the last frame decoded
+    pub fn current_time_ms(&self) -> u64 {
+        self.current_time_ms
+    }
+    /// Decode the next complete item, if enough data has been received
+    pub fn decode_next(&mut self) -> Result<Option<MwStreamItem<'_>>, MwReaderError> {
+        loop {
+            let avail = self.data.len() - self.off;
+            match self.state {
+                StreamState::FileHeader => {
+                    let len = MwFileHeader::serialized_len();
+                    if avail < len {
+                        return Ok(None);
+                    }
+                    let file_header = MwFileHeader::deserialize(&self.data[self.off..(self.off + len)]);
+                    self.off += len;
+                    self.file_header = Some(file_header);
+                    self.state = StreamState::ISHeader;
+                    return Ok(Some(MwStreamItem::FileHeader(file_header)));
+                }
+                StreamState::ISHeader => {
+                    let len = ISHeader::serialized_len();
+                    if avail < len {
+                        return Ok(None);
+                    }
+                    let is_header = ISHeader::deserialize(&self.data[self.off..(self.off + len)]);
+                    if !is_header.version_is_compatible(FORMAT_VERSION) {
+                        return Err(MwReaderError::VersionIncompatible);
+                    }
+                    self.off += len;
+                    self.is_header = Some(is_header);
+                    self.state = StreamState::IS(ISSection::MapData);
+                    return Ok(Some(MwStreamItem::ISHeader(is_header)));
+                }
+                StreamState::IS(section) => {
+                    let len = section.len(self.is_header.as_ref().unwrap());
+                    let next_state = match section.next() {
+                        Some(next) => StreamState::IS(next),
+                        None if self.file_header.map(|h| h.is_framedata_compressed()).unwrap_or(false) => {
+                            StreamState::FramesCompressed
+                        }
+                        None => StreamState::Frames,
+                    };
+                    if next_state == StreamState::Frames {
+                        self.len_frames_remaining = self.file_header
+                            .map(|h| h.len_framedata_compressed());
+                    }
+                    if len == 0 {
+                        self.state = next_state;
+                        continue;
+                    }
+                    self.check_len(len)?;
+                    if avail < len {
+                        return Ok(None);
+                    }
+                    let start = self.off;
+                    self.off += len;
+                    self.state = next_state;
+                    return Ok(Some(MwStreamItem::ISSection(section, &self.data[start..self.off])));
+                }
+                StreamState::FramesCompressed => {
+                    let file_header = self.file_header.unwrap();
+                    let len = file_header.len_framedata_compressed();
+                    self.check_len(len)?;
+                    self.check_len(file_header.len_framedata_raw())?;
+                    if avail < len {
+                        return Ok(None);
+                    }
+                    let mut frames = vec![0; file_header.len_framedata_raw()];
+                    let data_len = lz4_flex::block::decompress_into(
+                        &self.data[self.off..(self.off + len)], &mut frames
+                    )?;
+                    frames.truncate(data_len);
+                    self.len_frames_remaining = Some(data_len);
+                    // continue with the decompressed data in place of the compressed data
+                    frames.extend_from_slice(&self.data[(self.off + len)..]);
+                    self.data = frames;
+                    self.off = 0;
+                    self.state = StreamState::Frames;
+                }
+                StreamState::Trailer => {
+                    self.off = self.data.len();
+                    return Ok(None);
+                }
+                StreamState::Frames => {
+                    if self.len_frames_remaining == Some(0) {
+                        self.state = StreamState::Trailer;
+                        continue;
+                    }
+                    let Some(len) = self.len_next_frame() else {
+                        return Ok(None);
+                    };
+                    if let Some(remaining) = &mut self.len_frames_remaining {
+                        *remaining = remaining.saturating_sub(len);
+                    }
+                    let start = self.off;
+                    let h_delta = u16::from_be_bytes([self.data[start], self.data[start + 1]]);
+                    let frame_kind = if h_delta & !(1 << 15) == !(1 << 15) {
+                        self.current_time_ms += 0x7FFF;
+                        FrameKind::Keepalive
+                    } else if h_delta & (1 << 15) != 0 {
+                        self.current_time_ms += (h_delta & !(1 << 15)) as u64;
+                        FrameKind::Homogenous
+                    } else {
+                        self.current_time_ms += h_delta as u64;
+                        FrameKind::Heterogenous
+                    };
+                    self.off += len;
+                    return Ok(Some(MwStreamItem::Frame(MwStreamFrame {
+                        time_ms: self.current_time_ms,
+                        max_plid: self.is_header.as_ref().unwrap().max_plid(),
+                        frame_kind,
+                        data: &self.data[start..self.off],
+                    })));
+                }
+            }
+        }
+    }
+    /// Compute the length of the next frame, if enough of it has been received
+    fn len_next_frame(&self) -> Option<usize> {
+        let data = &self.data[self.off..];
+        if data.len() < 2 {
+            return None;
+        }
+        let len_plidsmask = self.is_header.as_ref().unwrap().max_plid() as usize / 8 + 1;
+        let h_delta = u16::from_be_bytes([data[0], data[1]]);
+        let len = if h_delta & !(1 << 15) == !(1 << 15) {
+            2
+        } else if h_delta & (1 << 15) != 0 {
+            let len_header = 2 + len_plidsmask + 1;
+            if data.len() < len_header {
+                return None;
+            }
+            len_header + data[len_header - 1] as usize + 1
+        } else {
+            if data.len() < 2 + len_plidsmask {
+                return None;
+            }
+            let n_views: usize = data[2..(2 + len_plidsmask)].iter()
+                .map(|b| b.count_ones() as usize)
+                .sum();
+            let len_header = 2 + len_plidsmask + n_views;
+            if data.len() < len_header {
+                return None;
+            }
+            data[(2 + len_plidsmask)..len_header].iter()
+                .fold(len_header, |len, b| len + *b as usize + 1)
+        };
+        (data.len() >= len).then_some(len)
+    }
+}
+
+impl<'a> MwStreamFrame<'a> {
+    /// The timestamp of this frame
+    pub fn current_time_ms(&self) -> u64 {
+        self.time_ms
+    }
+    pub fn frame_kind(&self) -> FrameKind {
+        self.frame_kind
+    }
+    /// The encoded bytes of the frame, as-is
+    pub fn raw(&self) -> &'a [u8] {
+        self.data
+    }
+    fn len_plidsmask(&self) -> usize {
+        self.max_plid as usize / 8 + 1
+    }
+    pub fn contains_view(&self, plid: PlayerId) -> bool {
+        match self.frame_kind {
+            FrameKind::Unknown | FrameKind::Keepalive => return false,
+            _ => {}
+        }
+        let plid = u8::from(plid);
+        if plid > self.max_plid {
+            return false;
+        }
+        let mask_byte = 2 + self.len_plidsmask() - 1 - plid as usize / 8; // Big Endian
+        let mask_bit = plid % 8;
+        self.data[mask_byte] & (1 << mask_bit) != 0
+    }
+    pub fn get_player_stream(&self, plid: PlayerId) -> &'a [u8] {
+        if !self.contains_view(plid) {
+            return &[];
+        }
+        let len_plidsmask = self.len_plidsmask();
+        match self.frame_kind {
+            FrameKind::Unknown | FrameKind::Keepalive => &[],
+            FrameKind::Homogenous => {
+                &self.data[(2 + len_plidsmask + 1)..]
+            }
+            FrameKind::Heterogenous => {
+                let n_views: usize = self.data[2..(2 + len_plidsmask)].iter()
+                    .map(|b| b.count_ones() as usize)
+                    .sum();
+                let lens = &self.data[(2 + len_plidsmask)..(2 + len_plidsmask + n_views)];
+                let mut offset_stream = 2 + len_plidsmask + n_views;
+                let mut i_view = 0;
+                for i in 0..u8::from(plid) {
+                    if self.contains_view(PlayerId::from(i)) {
+                        offset_stream += lens[i_view] as usize + 1;
+                        i_view += 1;
+                    }
+                }
+                let len_stream = lens[i_view] as usize + 1;
+                &self.data[offset_stream..(offset_stream + len_stream)]
+            }
+        }
+    }
+    /// Iterate over the data for each view, starting with plid 0
+    ///
+    /// Views not present in the frame are empty.
+    pub fn iter_streams(&self) -> impl Iterator<Item = &'a [u8]> + 'a {
+        let frame = *self;
+        (0..=frame.max_plid).map(move |i| frame.get_player_stream(PlayerId::from(i)))
+    }
+}
+
+impl Default for MwStreamDecoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 pub struct CitNamesIter<'b> {
     current_cit: u8,
     total_cits: u8,
     buf: &'b [u8],
 }
 
+impl<'b> CitNamesIter<'b> {
+    /// Iterate over encoded cit names data (such as an `ISSection::CitsNames`)
+    pub fn new(buf: &'b [u8], total_cits: u8) -> Self {
+        Self {
+            current_cit: 0,
+            total_cits,
+            buf,
+        }
+    }
+}
+
 impl<'b> Iterator for CitNamesIter<'b> {
     type Item = &'b [Ph];
     fn next(&mut self) -> Option<Self::Item> {
@@ -615,3 +1246,406 @@ impl<'b> ExactSizeIterator for CitNamesIter<'b> {
 }
 
 impl<'b> FusedIterator for CitNamesIter<'b> {}
+
+#[cfg(test)]
+mod test {
+    use mw_common::{game::{MapGenTileData, MwDigit, MwEv, TileKind}, plid::Plids};
+
+    use crate::write::{MwFileBuilder, MwFrameBuilder};
+
+    use super::*;
+
+    type DecodedFrame = (FrameKind, u64, Vec<Vec<u8>>);
+
+    fn append_test_frames<W: std::io::Write + Seek>(b_frames: &mut MwFrameBuilder<'_, W>) {
+        let all = [MwEv::Tremor];
+        let smokes: Vec<_> = (0..100).map(|i| MwEv::Smoke { pos: Pos(i, -i) }).collect();
+        b_frames.append_msgs(100, Plids::all(true), &[&all, &all, &all, &all]).unwrap();
+        b_frames.append_msgs(40100, Plids::all(true), &[&smokes, &[], &all]).unwrap();
+        b_frames.append_msgs(40150, Plids::all(true), &[&[], &[], &[], &all]).unwrap();
+    }
+
+    fn encode_test_file(compress_frames: bool) -> Vec<u8> {
+        let map: MapDataC<Hex, MapGenTileData> = MapData::new(4, MapGenTileData::default());
+        let cit_name = [Ph::A];
+        let mut buf = Vec::new();
+        let mut scratch = Vec::new();
+        let mut out = Cursor::new(Vec::new());
+        let (b_file, b_is) = MwFileBuilder::new(&mut out, &mut buf).unwrap()
+            .start_is().unwrap();
+        let is = b_is.with_max_plid(3, 0)
+            .with_map_uncompressed(&map, true).unwrap()
+            .with_cits([(Pos(1, 2), cit_name.as_slice())]).unwrap()
+            .finish().unwrap();
+        if compress_frames {
+            let (b_file, mut b_frames) = b_file.with_is_and_frame_compression(is, &mut scratch).unwrap()
+                .start_frames().unwrap();
+            append_test_frames(&mut b_frames);
+            b_file.with_frames(b_frames.finish().unwrap()).unwrap()
+                .finish().unwrap();
+        } else {
+            let (b_file, mut b_frames) = b_file.with_is(is).unwrap()
+                .start_frames().unwrap();
+            append_test_frames(&mut b_frames);
+            b_file.with_frames(b_frames.finish().unwrap()).unwrap()
+                .finish().unwrap();
+        }
+        out.into_inner()
+    }
+
+    fn decode_frames_seek(data: &[u8]) -> Vec<DecodedFrame> {
+        fn collect<R: Read + Seek>(frames: &mut MwFrameDataReader<'_, R>) -> Vec<DecodedFrame> {
+            let mut r = vec![];
+            while frames.advance_next_frame().is_ok() {
+                let streams = frames.iter_streams()
+                    .map(|s| s.unwrap().to_vec())
+                    .collect();
+                r.push((frames.frame_kind(), frames.current_time_ms(), streams));
+            }
+            r
+        }
+        let mut buf = Vec::new();
+        let mut scratch = Vec::new();
+        let mfr = MwFileReader::new(Cursor::new(data), &mut buf).unwrap();
+        match mfr.read_frames(Some(&mut scratch)).unwrap() {
+            MwFrameReader::Uncompressed(mut frames) => collect(&mut frames),
+            MwFrameReader::Compressed(mut frames) => collect(&mut frames),
+        }
+    }
+
+    fn decode_stream(
+        decoder: &mut MwStreamDecoder,
+        data: &[u8],
+        chunk_size: usize,
+    ) -> (Vec<(ISSection, Vec<u8>)>, Vec<DecodedFrame>) {
+        let mut sections = vec![];
+        let mut frames = vec![];
+        for chunk in data.chunks(chunk_size) {
+            decoder.push(chunk);
+            while let Some(item) = decoder.decode_next().unwrap() {
+                match item {
+                    MwStreamItem::ISSection(section, data) => {
+                        sections.push((section, data.to_vec()));
+                    }
+                    MwStreamItem::Frame(frame) => {
+                        let streams = frame.iter_streams()
+                            .map(|s| s.to_vec())
+                            .collect();
+                        frames.push((frame.frame_kind(), frame.current_time_ms(), streams));
+                    }
+                    _ => {}
+                }
+            }
+        }
+        (sections, frames)
+    }
+
+    #[test]
+    fn stream_matches_seek_reader() {
+        for compress_frames in [false, true] {
+            let data = encode_test_file(compress_frames);
+            let expected = decode_frames_seek(&data);
+            assert_eq!(expected.len(), 5);
+
+            let mut decoder = MwStreamDecoder::new_file();
+            let (sections, frames) = decode_stream(&mut decoder, &data, 7);
+            assert_eq!(decoder.len_pending(), 0);
+            assert_eq!(frames, expected);
+            let kinds: Vec<_> = sections.iter().map(|(s, _)| *s).collect();
+            assert_eq!(kinds, vec![ISSection::MapData, ISSection::CitsPos, ISSection::CitsNames]);
+            assert_eq!(bytemuck::cast_slice::<u8, Pos>(&sections[1].1), &[Pos(1, 2)]);
+            let names: Vec<_> = CitNamesIter::new(&sections[2].1, 1).collect();
+            assert_eq!(names, vec![&[Ph::A][..]]);
+        }
+    }
+
+    fn decode_bare_frames(data: &[u8], max_plid: u8) -> Vec<DecodedFrame> {
+        let mut buf = Vec::new();
+        let mut frames = MwFrameDataReader::new(Cursor::new(data), &mut buf, max_plid).unwrap();
+        let mut r = vec![];
+        while frames.advance_next_frame().is_ok() {
+            let streams = frames.iter_streams()
+                .map(|s| s.unwrap().to_vec())
+                .collect();
+            r.push((frames.frame_kind(), frames.current_time_ms(), streams));
+        }
+        r
+    }
+
+    #[test]
+    fn frames_hand_built_u8_mask() {
+        // max_plid 7 still fits in a u8 participation mask
+        let data = [
+            // homogenous, +100ms, spectator + plid 2, 2 bytes: SHAKE SHAKE
+            0x80, 0x64, 0b00000101, 0x01, 0x01, 0x01,
+            // heterogenous, +5ms, spectator + plid 1, lengths 1 and 3
+            0x00, 0x05, 0b00000011, 0x00, 0x02,
+            0x01, 0x02, 0x05, 0x06,
+            // two keepalives (the top bit is ignored)
+            0x7F, 0xFF,
+            0xFF, 0xFF,
+            // homogenous, +0ms, plid 7
+            0x80, 0x00, 0b10000000, 0x00, 0x01,
+        ];
+        let e: Vec<u8> = vec![];
+        let frames = decode_bare_frames(&data, 7);
+        assert_eq!(frames, vec![
+            (FrameKind::Homogenous, 100, vec![vec![1, 1], e.clone(), vec![1, 1], e.clone(), e.clone(), e.clone(), e.clone(), e.clone()]),
+            (FrameKind::Heterogenous, 105, vec![vec![1], vec![2, 5, 6], e.clone(), e.clone(), e.clone(), e.clone(), e.clone(), e.clone()]),
+            (FrameKind::Keepalive, 105 + 0x7FFF, vec![e.clone(); 8]),
+            (FrameKind::Keepalive, 105 + 2 * 0x7FFF, vec![e.clone(); 8]),
+            (FrameKind::Homogenous, 105 + 2 * 0x7FFF, vec![e.clone(), e.clone(), e.clone(), e.clone(), e.clone(), e.clone(), e.clone(), vec![1]]),
+        ]);
+    }
+
+    #[test]
+    fn frames_hand_built_u16_mask() {
+        // max_plid 9 needs a u16 participation mask (big endian)
+        let data = [
+            // heterogenous, +10ms, spectator + plid 9, lengths 1 and 3
+            0x00, 0x0A, 0x02, 0x01, 0x00, 0x02,
+            0x01, 0x02, 0x07, 0x08,
+            // homogenous, +1ms, plid 8, 1 byte: SHAKE
+            0x80, 0x01, 0x01, 0x00, 0x00, 0x01,
+        ];
+        let mut buf = Vec::new();
+        let mut frames = MwFrameDataReader::new(Cursor::new(&data[..]), &mut buf, 9).unwrap();
+        frames.advance_next_frame().unwrap();
+        assert_eq!(frames.frame_kind(), FrameKind::Heterogenous);
+        assert_eq!(frames.current_time_ms(), 10);
+        assert!(frames.contains_view(PlayerId::from(0)));
+        assert!(!frames.contains_view(PlayerId::from(8)));
+        assert!(frames.contains_view(PlayerId::from(9)));
+        assert_eq!(frames.get_player_stream(PlayerId::from(9)).unwrap(), &[2, 7, 8]);
+        assert_eq!(frames.get_player_stream(PlayerId::from(0)).unwrap(), &[1]);
+        assert_eq!(frames.get_player_stream(PlayerId::from(1)).unwrap(), &[] as &[u8]);
+        frames.advance_next_frame().unwrap();
+        assert_eq!(frames.frame_kind(), FrameKind::Homogenous);
+        assert_eq!(frames.current_time_ms(), 11);
+        assert!(frames.contains_view(PlayerId::from(8)));
+        assert!(!frames.contains_view(PlayerId::from(0)));
+        assert_eq!(frames.get_player_stream(PlayerId::from(8)).unwrap(), &[1]);
+        assert!(frames.advance_next_frame().is_err());
+
+        let e: Vec<u8> = vec![];
+        let mut expected_het = vec![e.clone(); 10];
+        expected_het[0] = vec![1];
+        expected_het[9] = vec![2, 7, 8];
+        let mut expected_hom = vec![e.clone(); 10];
+        expected_hom[8] = vec![1];
+        assert_eq!(decode_bare_frames(&data, 9), vec![
+            (FrameKind::Heterogenous, 10, expected_het),
+            (FrameKind::Homogenous, 11, expected_hom),
+        ]);
+    }
+
+    #[test]
+    fn stream_max_len() {
+        for compress_frames in [false, true] {
+            let data = encode_test_file(compress_frames);
+            let (_, frames) = decode_stream(&mut MwStreamDecoder::new_file(), &data, 7);
+            assert_eq!(frames.len(), 5);
+
+            // the map data is bigger than that; fail instead of waiting for it
+            let mut decoder = MwStreamDecoder::new_file().with_max_len(4);
+            decoder.push(&data[..(MwFileHeader::serialized_len() + ISHeader::serialized_len())]);
+            assert!(matches!(decoder.decode_next(), Ok(Some(MwStreamItem::FileHeader(_)))));
+            assert!(matches!(decoder.decode_next(), Ok(Some(MwStreamItem::ISHeader(_)))));
+            assert!(matches!(
+                decoder.decode_next(),
+                Err(MwReaderError::TooLarge { max: 4, .. })
+            ));
+        }
+    }
+
+    #[test]
+    fn stream_without_file_header() {
+        let data = encode_test_file(false);
+        let expected = decode_frames_seek(&data);
+        let stream = &data[MwFileHeader::serialized_len()..];
+
+        // feed it one byte at a time, but stop short of the end
+        let mut decoder = MwStreamDecoder::new();
+        let (_, frames) = decode_stream(&mut decoder, &stream[..(stream.len() - 1)], 1);
+        assert!(decoder.file_header().is_none());
+        assert_eq!(decoder.is_header().map(|h| h.max_plid()), Some(3));
+        assert!(decoder.is_in_frames());
+        assert_eq!(frames[..], expected[..(expected.len() - 1)]);
+        assert_eq!(decoder.len_pending(), expected.last().unwrap().2[3].len() + 3);
+
+        // the last frame completes with the final byte
+        decoder.push(&stream[(stream.len() - 1)..]);
+        let Some(MwStreamItem::Frame(frame)) = decoder.decode_next().unwrap() else {
+            panic!("expected the last frame");
+        };
+        assert_eq!(frame.current_time_ms(), 40150);
+        assert!(frame.contains_view(PlayerId::from(3)));
+        assert!(!frame.contains_view(PlayerId::from(0)));
+        assert!(decoder.decode_next().unwrap().is_none());
+        assert_eq!(decoder.len_pending(), 0);
+    }
+
+    #[test]
+    fn index_seek_and_trailer() {
+        for compress_frames in [false, true] {
+            let mut data = encode_test_file(compress_frames);
+            let expected = decode_frames_seek(&data);
+
+            let mut buf = Vec::new();
+            let mut scratch = Vec::new();
+            let mut mfr = MwFileReader::new(Cursor::new(&data), &mut buf).unwrap();
+            assert!(mfr.read_frame_index().unwrap().is_none());
+            let index = match mfr.read_frames(Some(&mut scratch)).unwrap() {
+                MwFrameReader::Uncompressed(mut frames) => frames.build_index(0).unwrap(),
+                MwFrameReader::Compressed(mut frames) => frames.build_index(0).unwrap(),
+            };
+            assert_eq!(index.entries().len(), expected.len());
+            assert_eq!(index.lookup(0), None);
+            assert_eq!(index.lookup(100).map(|e| e.time_ms), Some(0));
+            // both frames at 40100 come after the keepalive
+            assert_eq!(index.lookup(40100).map(|e| e.time_ms), Some(100 + 0x7FFF));
+
+            // store the index at the end of the file
+            let mut index_data = Vec::new();
+            index.serialize(&mut index_data);
+            assert_eq!(index_data.len(), index.serialized_len());
+            data.extend_from_slice(&index_data);
+            assert_eq!(decode_frames_seek(&data), expected);
+            let (_, frames) = decode_stream(&mut MwStreamDecoder::new_file(), &data, 5);
+            assert_eq!(frames, expected);
+
+            let mut mfr = MwFileReader::new(Cursor::new(&data), &mut buf).unwrap();
+            mfr.verify_checksums().unwrap();
+            assert_eq!(mfr.read_frame_index().unwrap().as_ref(), Some(&index));
+            let MwFrameReader::Uncompressed(mut frames) = mfr.read_frames(Some(&mut scratch)).unwrap()
+            else {
+                assert!(compress_frames);
+                continue;
+            };
+            frames.seek_time(&index, 40100);
+            frames.advance_next_frame().unwrap();
+            assert_eq!(frames.current_time_ms(), 40100);
+            let mut n_after = 0;
+            while frames.advance_next_frame().is_ok() {
+                n_after += 1;
+            }
+            assert_eq!(n_after, 2);
+            assert_eq!(frames.current_time_ms(), 40150);
+        }
+    }
+
+    #[test]
+    fn trailer_rejects_corruption() {
+        let mut index = MwFrameIndex::new();
+        index.push(MwFrameIndexEntry { time_ms: 0, offset: 0 });
+        index.push(MwFrameIndexEntry { time_ms: 5000, offset: 1234 });
+        let mut data = Vec::new();
+        index.serialize(&mut data);
+        let section = find_section(&data, INDEX_MAGIC).unwrap().unwrap();
+        assert_eq!(MwFrameIndex::deserialize(section).unwrap(), index);
+        assert!(find_section(&data, KEYFRAMES_MAGIC).unwrap().is_none());
+        assert!(matches!(MwFrameIndex::deserialize(&section[..20]), Err(TrailerDecodeError::BadSize)));
+        assert!(matches!(find_section(&data[..20], INDEX_MAGIC), Err(TrailerDecodeError::BadSize)));
+        data[20] ^= 1;
+        assert!(matches!(find_section(&data, INDEX_MAGIC), Err(TrailerDecodeError::BadChecksum)));
+    }
+
+    #[test]
+    fn legacy_index_compat() {
+        let mut data = encode_test_file(false);
+        let index = {
+            let mut buf = Vec::new();
+            let mfr = MwFileReader::new(Cursor::new(&data), &mut buf).unwrap();
+            let MwFrameReader::Uncompressed(mut frames) = mfr.read_frames(None).unwrap() else {
+                panic!("frames should not be compressed");
+            };
+            frames.build_index(0).unwrap()
+        };
+        // a bare index, as written before trailing sections existed
+        let mut entries = Vec::new();
+        for entry in index.entries() {
+            entries.extend_from_slice(&entry.time_ms.to_be_bytes());
+            entries.extend_from_slice(&entry.offset.to_be_bytes());
+        }
+        data.extend_from_slice(&INDEX_MAGIC);
+        data.extend_from_slice(&(index.entries().len() as u32).to_be_bytes());
+        data.extend_from_slice(&seahash::hash(&entries).to_be_bytes());
+        data.extend_from_slice(&entries);
+
+        let mut buf = Vec::new();
+        let mut scratch = Vec::new();
+        let mut mfr = MwFileReader::new(Cursor::new(&data), &mut buf).unwrap();
+        assert_eq!(mfr.read_frame_index().unwrap().as_ref(), Some(&index));
+        assert!(mfr.read_keyframes(&mut scratch).unwrap().is_none());
+        let trailer = mfr.read_trailer().unwrap();
+        assert!(find_section(trailer, INDEX_MAGIC).is_err());
+        assert_eq!(MwFrameIndex::deserialize_legacy(trailer).unwrap(), index);
+
+        // anything else that isn't valid trailing data is still an error
+        *data.last_mut().unwrap() ^= 1;
+        let mut mfr = MwFileReader::new(Cursor::new(&data), &mut buf).unwrap();
+        assert!(mfr.read_frame_index().is_err());
+        assert!(mfr.read_keyframes(&mut scratch).is_err());
+    }
+
+    #[test]
+    fn keyframes_roundtrip() {
+        let map: MapDataC<Hex, MapGenTileData> = MapData::new(4, MapGenTileData::default());
+        let mut buf = Vec::new();
+        let mut out = Cursor::new(Vec::new());
+        let (b_file, b_is) = MwFileBuilder::new(&mut out, &mut buf).unwrap()
+            .start_is().unwrap();
+        let is = b_is.with_max_plid(3, 0)
+            .with_map_uncompressed(&map, true).unwrap()
+            .finish().unwrap();
+        let (b_file, mut b_frames) = b_file.with_is(is).unwrap()
+            .start_frames().unwrap();
+        let capture = [MwEv::DigitCapture { pos: Pos(0, 1), digit: MwDigit { digit: 3, asterisk: true } }];
+        let owner = [MwEv::TileOwner { pos: Pos(1, 0), plid: PlayerId::from(2) }];
+        b_frames.append_msgs(1000, Plids::all(true), &[&[], &capture]).unwrap();
+        b_frames.append_msgs(2000, Plids::all(true), &[&owner, &owner, &owner, &owner]).unwrap();
+        b_frames.append_msgs(3000, Plids::all(true), &[&[MwEv::Tremor]]).unwrap();
+        b_file.with_frames(b_frames.finish().unwrap()).unwrap()
+            .finish().unwrap();
+        let mut data = out.into_inner();
+
+        let initial = MapDataPos::new(4, ViewTileData::from_kind(TileKind::Regular));
+        let mut scratch = Vec::new();
+        let mfr = MwFileReader::new(Cursor::new(&data), &mut buf).unwrap();
+        let MwFrameReader::Uncompressed(mut frames) = mfr.read_frames(None).unwrap() else {
+            panic!("frames should not be compressed");
+        };
+        let keyframes = frames.build_keyframes(&initial, 1000).unwrap();
+        keyframes.serialize(&mut data, &mut scratch);
+
+        let mut mfr = MwFileReader::new(Cursor::new(&data), &mut buf).unwrap();
+        assert!(mfr.read_frame_index().unwrap().is_none());
+        let keyframes = mfr.read_keyframes(&mut scratch).unwrap().unwrap();
+        let times: Vec<_> = keyframes.keyframes().iter().map(|k| k.entry.time_ms).collect();
+        assert_eq!(times, [1000, 2000]);
+        for keyframe in keyframes.keyframes() {
+            assert_eq!(keyframe.views.len(), 4);
+        }
+        let kf = &keyframes.keyframes()[0];
+        let tile = kf.get_view(PlayerId::from(1)).unwrap()[Pos(0, 1)];
+        assert_eq!((tile.owner(), tile.digit(), tile.asterisk()), (1, 3, true));
+        assert_eq!(kf.get_view(PlayerId::from(0)).unwrap()[Pos(0, 1)].owner(), 0);
+        assert_eq!(kf.get_view(PlayerId::from(3)).unwrap()[Pos(1, 0)].owner(), 0);
+        let kf = &keyframes.keyframes()[1];
+        for plid in 0..=3u8 {
+            assert_eq!(kf.get_view(PlayerId::from(plid)).unwrap()[Pos(1, 0)].owner(), 2);
+        }
+        assert_eq!(kf.get_view(PlayerId::from(1)).unwrap()[Pos(0, 1)].digit(), 3);
+
+        // resume decoding after the keyframe
+        let kf = keyframes.lookup(2500).unwrap();
+        assert_eq!(kf.entry.time_ms, 2000);
+        let MwFrameReader::Uncompressed(mut frames) = mfr.read_frames(None).unwrap() else {
+            panic!("frames should not be compressed");
+        };
+        frames.seek_to_keyframe(kf);
+        frames.advance_next_frame().unwrap();
+        assert_eq!(frames.current_time_ms(), 3000);
+        assert!(frames.advance_next_frame().is_err());
+    }
+}
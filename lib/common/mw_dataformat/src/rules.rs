@@ -0,0 +1,192 @@
+//! Encoding and Decoding of MineWars Game Rules
+
+use enum_map::{EnumArray, EnumMap};
+use thiserror::Error;
+
+use mw_common::data::{MwDur, MwRatio};
+use mw_common::game::MwRules;
+
+/// Error when deserializing rules data.
+#[derive(Debug, Error)]
+pub enum RulesDecodeError {
+    /// The input buffer ended before all the rules were decoded
+    #[error("Rules data is truncated")]
+    Truncated,
+    /// There is more data in the input buffer after all the rules
+    #[error("Rules data has {0} unexpected trailing bytes")]
+    Trailing(usize),
+    /// A table has a different number of entries than we have variants
+    #[error("Rules table has {0} entries, expected {1}")]
+    BadTableLength(u8, usize),
+}
+
+/// Encode game rules.
+///
+/// The binary rules data will be appended to `out`.
+pub fn serialize_rules(rules: &MwRules, out: &mut Vec<u8>) {
+    out.push(rules.res_base);
+    put_table(out, &rules.res_tile, |out, x| out.push(*x));
+    put_table(out, &rules.res_tile_harvest, put_u16);
+    put_u16(out, &rules.cit_starting_money_spawn);
+    put_u16(out, &rules.cit_starting_money_other);
+    out.push(rules.mult_cost_foreign_region.0);
+    out.push(rules.mult_costsharing_local_contribution.0);
+    out.push(rules.vis_radius);
+    out.push(rules.vis_radius_watchtower);
+    put_table(out, &rules.structure_return_bulldoze, put_u16);
+    put_table(out, &rules.structure_return_cancel, put_u16);
+    put_table(out, &rules.structure_cost_initial, put_u16);
+    put_table(out, &rules.structure_cost_construction, put_u16);
+    put_table(out, &rules.action_cost, put_u16);
+    put_table(out, &rules.action_cooldown, |out, x| out.push(x.0));
+    put_table(out, &rules.action_delay, |out, x| out.push(x.0));
+    put_table(out, &rules.item_cost, put_u16);
+    put_table(out, &rules.item_return, put_u16);
+    put_u16(out, &rules.cost_strike_tile);
+    out.push(rules.dur_land_protect.0);
+    out.push(rules.dur_capture_city.0);
+}
+
+/// Decode game rules.
+///
+/// `input` must contain exactly the encoded rules data, nothing more.
+pub fn deserialize_rules(input: &[u8]) -> Result<MwRules, RulesDecodeError> {
+    let mut r = RulesReader { input };
+    let rules = MwRules {
+        res_base: r.u8()?,
+        res_tile: r.table(RulesReader::u8)?,
+        res_tile_harvest: r.table(RulesReader::u16)?,
+        cit_starting_money_spawn: r.u16()?,
+        cit_starting_money_other: r.u16()?,
+        mult_cost_foreign_region: MwRatio(r.u8()?),
+        mult_costsharing_local_contribution: MwRatio(r.u8()?),
+        vis_radius: r.u8()?,
+        vis_radius_watchtower: r.u8()?,
+        structure_return_bulldoze: r.table(RulesReader::u16)?,
+        structure_return_cancel: r.table(RulesReader::u16)?,
+        structure_cost_initial: r.table(RulesReader::u16)?,
+        structure_cost_construction: r.table(RulesReader::u16)?,
+        action_cost: r.table(RulesReader::u16)?,
+        action_cooldown: r.table(RulesReader::dur)?,
+        action_delay: r.table(RulesReader::dur)?,
+        item_cost: r.table(RulesReader::u16)?,
+        item_return: r.table(RulesReader::u16)?,
+        cost_strike_tile: r.u16()?,
+        dur_land_protect: r.dur()?,
+        dur_capture_city: r.dur()?,
+    };
+    if !r.input.is_empty() {
+        return Err(RulesDecodeError::Trailing(r.input.len()));
+    }
+    Ok(rules)
+}
+
+fn put_u16(out: &mut Vec<u8>, x: &u16) {
+    out.extend_from_slice(&x.to_be_bytes());
+}
+
+/// Tables are encoded as a count byte, followed by the values in variant order
+fn put_table<K: EnumArray<V>, V>(
+    out: &mut Vec<u8>,
+    table: &EnumMap<K, V>,
+    mut f: impl FnMut(&mut Vec<u8>, &V),
+) {
+    out.push(K::LENGTH as u8);
+    for x in table.values() {
+        f(out, x);
+    }
+}
+
+struct RulesReader<'a> {
+    input: &'a [u8],
+}
+
+impl RulesReader<'_> {
+    fn bytes<const N: usize>(&mut self) -> Result<[u8; N], RulesDecodeError> {
+        let Some((bytes, rest)) = self.input.split_first_chunk::<N>() else {
+            return Err(RulesDecodeError::Truncated);
+        };
+        self.input = rest;
+        Ok(*bytes)
+    }
+    fn u8(&mut self) -> Result<u8, RulesDecodeError> {
+        Ok(self.bytes::<1>()?[0])
+    }
+    fn u16(&mut self) -> Result<u16, RulesDecodeError> {
+        Ok(u16::from_be_bytes(self.bytes()?))
+    }
+    fn dur(&mut self) -> Result<MwDur, RulesDecodeError> {
+        Ok(MwDur(self.u8()?))
+    }
+    fn table<K: EnumArray<V> + EnumArray<Option<V>>, V>(
+        &mut self,
+        mut f: impl FnMut(&mut Self) -> Result<V, RulesDecodeError>,
+    ) -> Result<EnumMap<K, V>, RulesDecodeError> {
+        let len = self.u8()?;
+        if len as usize != K::LENGTH {
+            return Err(RulesDecodeError::BadTableLength(len, K::LENGTH));
+        }
+        let mut r = Ok(());
+        let table = EnumMap::from_fn(|_| {
+            match f(self) {
+                Ok(x) => Some(x),
+                Err(e) => {
+                    r = Err(e);
+                    None
+                }
+            }
+        });
+        r?;
+        Ok(table.map(|_, x| x.unwrap()))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use enum_map::{enum_map, Enum};
+
+    use super::*;
+
+    fn test_rules() -> MwRules {
+        MwRules {
+            res_base: 3,
+            res_tile: EnumMap::from_fn(|k| Enum::into_usize(k) as u8),
+            res_tile_harvest: EnumMap::from_fn(|k| 1000 + Enum::into_usize(k) as u16),
+            cit_starting_money_spawn: 5000,
+            cit_starting_money_other: 2500,
+            mult_cost_foreign_region: MwRatio::new_lossy(3, 2),
+            mult_costsharing_local_contribution: MwRatio::new_lossy(1, 4),
+            vis_radius: 5,
+            vis_radius_watchtower: 9,
+            structure_return_bulldoze: EnumMap::from_fn(|k| 10 * Enum::into_usize(k) as u16),
+            structure_return_cancel: EnumMap::from_fn(|k| 20 * Enum::into_usize(k) as u16),
+            structure_cost_initial: EnumMap::from_fn(|k| 300 + Enum::into_usize(k) as u16),
+            structure_cost_construction: EnumMap::from_fn(|k| 400 + Enum::into_usize(k) as u16),
+            action_cost: EnumMap::from_fn(|k| 0x1234 + Enum::into_usize(k) as u16),
+            action_cooldown: EnumMap::from_fn(|k| MwDur::from_millis_lossy(100 * Enum::into_usize(k) as u16)),
+            action_delay: EnumMap::from_fn(|k| MwDur(Enum::into_usize(k) as u8)),
+            item_cost: enum_map! { _ => 50 },
+            item_return: enum_map! { _ => 25 },
+            cost_strike_tile: 75,
+            dur_land_protect: MwDur::from_millis_lossy(5000),
+            dur_capture_city: MwDur::from_millis_lossy(2000),
+        }
+    }
+
+    #[test]
+    fn rules_roundtrip() {
+        let rules = test_rules();
+        let mut buf = Vec::new();
+        serialize_rules(&rules, &mut buf);
+        assert_eq!(deserialize_rules(&buf).unwrap(), rules);
+        assert!(matches!(
+            deserialize_rules(&buf[..buf.len() - 1]),
+            Err(RulesDecodeError::Truncated)
+        ));
+        buf.push(0);
+        assert!(matches!(
+            deserialize_rules(&buf),
+            Err(RulesDecodeError::Trailing(1))
+        ));
+    }
+}
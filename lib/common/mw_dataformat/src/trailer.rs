@@ -0,0 +1,129 @@
+//! Optional Sections After the Frame Data
+//!
+//! A MineWars file may contain extra sections after the frame data. They hold
+//! data that could be recomputed from the frames, but is expensive to (such as
+//! seek indices and keyframes). They can be added or removed at will, without
+//! affecting the rest of the file.
+//!
+//! Each section starts with a 16-byte header: a 4-byte magic identifying the
+//! kind of section, the length of its contents (u32 BE), and a checksum of its
+//! contents (seahash, u64 BE). Readers should skip any sections they do not
+//! recognize.
+
+use thiserror::Error;
+
+/// Error when decoding trailing sections.
+#[derive(Debug, Error)]
+pub enum TrailerDecodeError {
+    #[error("Trailing section length is wrong")]
+    BadSize,
+    #[error("Trailing section checksum invalid")]
+    BadChecksum,
+    #[error("Trailing section data invalid")]
+    BadData,
+    #[error("Cannot decompress trailing section data: {0}")]
+    BadCompression(lz4_flex::block::DecompressError),
+}
+
+pub const SECTION_HEADER_LEN: usize = 16;
+
+/// A section, as found in the trailing data
+#[derive(Debug, Clone, Copy)]
+pub struct TrailerSection<'a> {
+    pub magic: [u8; 4],
+    /// The contents of the section (after the header)
+    pub data: &'a [u8],
+    /// The encoded bytes of the whole section, including the header
+    pub raw: &'a [u8],
+}
+
+/// Encode a section, appending it to `out`.
+///
+/// The contents are written by `f`, which must only append to the buffer.
+pub fn serialize_section(magic: [u8; 4], out: &mut Vec<u8>, f: impl FnOnce(&mut Vec<u8>)) {
+    let off_header = out.len();
+    out.extend_from_slice(&magic);
+    out.extend_from_slice(&[0; SECTION_HEADER_LEN - 4]);
+    let off_data = out.len();
+    f(out);
+    let len = (out.len() - off_data) as u32;
+    let checksum = seahash::hash(&out[off_data..]);
+    out[(off_header + 4)..(off_header + 8)].copy_from_slice(&len.to_be_bytes());
+    out[(off_header + 8)..off_data].copy_from_slice(&checksum.to_be_bytes());
+}
+
+/// Iterate over the sections in the trailing data, verifying their checksums
+///
+/// Stops after the first error.
+pub fn iter_sections(input: &[u8]) -> TrailerSectionsIter<'_> {
+    TrailerSectionsIter { input }
+}
+
+/// Find the contents of the (first) section with the given magic
+pub fn find_section(input: &[u8], magic: [u8; 4]) -> Result<Option<&[u8]>, TrailerDecodeError> {
+    for section in iter_sections(input) {
+        let section = section?;
+        if section.magic == magic {
+            return Ok(Some(section.data));
+        }
+    }
+    Ok(None)
+}
+
+/// Rebuild the trailing data, with the section(s) with the given magic
+/// replaced by `new` (or removed, if `new` is empty).
+///
+/// The new trailing data will be appended to `out`.
+pub fn replace_section(
+    input: &[u8],
+    magic: [u8; 4],
+    new: &[u8],
+    out: &mut Vec<u8>,
+) -> Result<(), TrailerDecodeError> {
+    for section in iter_sections(input) {
+        let section = section?;
+        if section.magic != magic {
+            out.extend_from_slice(section.raw);
+        }
+    }
+    out.extend_from_slice(new);
+    Ok(())
+}
+
+pub struct TrailerSectionsIter<'a> {
+    input: &'a [u8],
+}
+
+impl<'a> TrailerSectionsIter<'a> {
+    fn decode_section(&mut self) -> Result<TrailerSection<'a>, TrailerDecodeError> {
+        if self.input.len() < SECTION_HEADER_LEN {
+            return Err(TrailerDecodeError::BadSize);
+        }
+        let magic = self.input[0..4].try_into().unwrap();
+        let len = u32::from_be_bytes(self.input[4..8].try_into().unwrap()) as usize;
+        let checksum = u64::from_be_bytes(self.input[8..16].try_into().unwrap());
+        let Some(raw) = self.input.get(..(SECTION_HEADER_LEN + len)) else {
+            return Err(TrailerDecodeError::BadSize);
+        };
+        let data = &raw[SECTION_HEADER_LEN..];
+        if seahash::hash(data) != checksum {
+            return Err(TrailerDecodeError::BadChecksum);
+        }
+        self.input = &self.input[raw.len()..];
+        Ok(TrailerSection { magic, data, raw })
+    }
+}
+
+impl<'a> Iterator for TrailerSectionsIter<'a> {
+    type Item = Result<TrailerSection<'a>, TrailerDecodeError>;
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.input.is_empty() {
+            return None;
+        }
+        let r = self.decode_section();
+        if r.is_err() {
+            self.input = &[];
+        }
+        Some(r)
+    }
+}
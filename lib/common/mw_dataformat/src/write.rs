@@ -3,36 +3,51 @@
 use seahash::SeaHasher;
 use thiserror::Error;
 use std::{hash::Hasher, io::{Cursor, Seek, SeekFrom, Write}};
-use mw_common::{grid::*, phoneme::Ph};
+use mw_common::{driver::{GameIo, GameOutput}, game::{MwEv, MwRules}, grid::*, phoneme::Ph, plid::{PlayerId, Plids}};
 
-use crate::{header::{ISHeader, MwFileHeader}, map::MapTileDataOut};
+use crate::{header::{ISHeader, MwFileHeader}, map::MapTileDataOut, msg::{bin::{MsgBinWrite, MsgBinWriteError}, MsgWriter}, players::PlidInfo};
 
 #[derive(Debug, Error)]
 pub enum MwWriterError {
     #[error("I/O error: {0}")]
     Io(#[from] std::io::Error),
+    #[error("Message encoding error: {0}")]
+    Msg(#[from] MsgBinWriteError),
+    #[error("Message does not fit in a frame")]
+    MsgTooLong,
+    #[error("Frame timestamp {0} is earlier than the previous frame")]
+    TimeTravel(u64),
+    #[error("Rules data is too long")]
+    RulesTooLong,
+    #[error("Players data is too long")]
+    PlayersTooLong,
 }
 
+/// Max length of the data for one view in a single frame
+pub const FRAME_MAX_STREAM_LEN: usize = 256;
+/// How much time a Keepalive frame advances by
+pub const FRAME_KEEPALIVE_MS: u64 = 0x7FFF;
+
 /// Builder for a full MineWars file
 ///
 /// The general process of encoding a file is as follows (pseudocode):
 ///
-/// ```rust
-/// let (b_file, b_is) = MwFileBuilder::new(out)?.start_is()?;
+/// ```rust,ignore
+/// let (b_file, b_is) = MwFileBuilder::new(out, &mut buf)?.start_is()?;
 /// let is = b_is
+///   .with_max_plid(...)
 ///   .with_map(...)?
 ///   .with_cits(...)?
 ///   .with_rules(...)?
+///   .with_players(...)?
 ///   .finish()?;
-/// let mut b_file
+/// let mut b_file = b_file
 ///   .with_is(is)?;
+/// let (b_file2, mut b_frames) = b_file.start_frames()?;
 /// for game_update in game_updates {
-///   let (b_file2, b_frame) = b_file.start_frame(...)?;
-///   let frame = b_frame
-///     .with_msgs(game_update.msgs)?
-///     .finish()?;
-///   b_file = b_file2.with_frames(frame)?;
+///   b_frames.append_msgs(game_update.time_ms, game_update.plids, game_update.msgs)?;
 /// }
+/// b_file = b_file2.with_frames(b_frames.finish()?)?;
 /// b_file.finish()?;
 /// ```
 ///
@@ -52,6 +67,7 @@ pub struct MwFileBuilderReadyFrames<'b, W: Write + Seek> {
     writer: W,
     off_frames_start: u64,
     frames_hasher: SeaHasher,
+    frames_time_ms: u64,
     is_hash: u64,
     is_header: ISHeader,
 }
@@ -59,6 +75,7 @@ pub struct MwFileBuilderReadyCompressFrames<'b, 's, W: Write + Seek> {
     buf: &'b mut Vec<u8>,
     writer: W,
     scratch: Cursor<&'s mut Vec<u8>>,
+    frames_time_ms: u64,
     is_hash: u64,
     is_header: ISHeader,
 }
@@ -107,6 +124,13 @@ pub struct MwISBuilderWithRules<'b, W: Write + Seek> {
     hasher: Option<SeaHasher>,
     writer: W,
 }
+pub struct MwISBuilderWithPlayers<'b, W: Write + Seek> {
+    buf: &'b mut Vec<u8>,
+    off_header: u32,
+    header: ISHeader,
+    hasher: Option<SeaHasher>,
+    writer: W,
+}
 pub struct MwISComplete<'b, W: Write + Seek> {
     buf: &'b mut Vec<u8>,
     header: ISHeader,
@@ -114,17 +138,55 @@ pub struct MwISComplete<'b, W: Write + Seek> {
     writer: W,
 }
 
-pub struct MwFrameBuilder<'b, W: Write + Seek> {
+/// Builder for Frame data
+///
+/// Keeps track of the timestamp of the last frame written,
+/// so that frame time deltas can be computed.
+///
+/// Frames are only ever written forwards, so this does not need `Seek`.
+pub struct MwFrameBuilder<'b, W: Write> {
     buf: &'b mut Vec<u8>,
     hasher: Option<SeaHasher>,
     writer: W,
     is_header: ISHeader,
+    time_ms: u64,
 }
-pub struct MwFramesComplete<'b, W: Write + Seek> {
+pub struct MwFramesComplete<'b, W: Write> {
     buf: &'b mut Vec<u8>,
     hasher: Option<SeaHasher>,
     writer: W,
     is_header: ISHeader,
+    time_ms: u64,
+}
+
+/// Forward-only writer for the network stream format (IS + frames, no file header)
+///
+/// This never seeks, so it can write to a socket or pipe as the game progresses.
+/// The IS must be built in memory first (so that its header can contain all the
+/// lengths), and is written out in one go. Game outputs can then be appended
+/// one at a time, as they arrive:
+///
+/// ```rust,ignore
+/// let mut is_data = Vec::new();
+/// let is = MwISBuilder::new(Cursor::new(&mut is_data), &mut buf)?
+///   .with_max_plid(...)
+///   .with_map(...)?
+///   ...
+///   .finish()?;
+/// let mut w = MwStreamWriter::new(out, is)?;
+/// for (time_ms, output) in game_outputs {
+///   w.append_output(time_ms, &output)?;
+/// }
+/// w.flush()?;
+/// ```
+///
+/// Outputs with the same timestamp are encoded together into frames, once an
+/// output with a later timestamp is appended, or on `flush`.
+pub struct MwStreamWriter<'b, W: Write> {
+    frames: MwFrameBuilder<'b, W>,
+    pending_time_ms: u64,
+    pending_plids: Plids,
+    pending_msgs: Vec<Vec<MwEv>>,
 }
 
 impl<'b, W: Write + Seek> MwFileBuilder<'b, W> {
@@ -169,6 +231,7 @@ impl MwFileBuilderAwaitsIS {
             off_frames_start: is.writer.stream_position()?,
             writer: is.writer,
             frames_hasher: SeaHasher::default(),
+            frames_time_ms: 0,
             is_header: is.header,
             is_hash: is.hash.expect("File builder wants IS built with its MwISBuilder!"),
         })
@@ -182,6 +245,7 @@ impl MwFileBuilderAwaitsIS {
             buf: is.buf,
             writer: is.writer,
             scratch: Cursor::new(scratch),
+            frames_time_ms: 0,
             is_header: is.header,
             is_hash: is.hash.expect("File builder wants IS built with its MwISBuilder!"),
         })
@@ -224,6 +288,7 @@ impl<'b, W: Write + Seek> MwFileBuilderReadyFrames<'b, W> {
                 writer: self.writer,
                 is_header: self.is_header,
                 hasher: Some(self.frames_hasher),
+                time_ms: self.frames_time_ms,
             },
         ))
     }
@@ -235,6 +300,7 @@ impl MwFileBuilderAwaitsFrames {
             off_frames_start: self.off_frames_start,
             writer: frames.writer,
             frames_hasher: frames.hasher.expect("File builder wants Frames built with its MwFrameBuilder!"),
+            frames_time_ms: frames.time_ms,
             is_hash: self.is_hash,
             is_header: frames.is_header,
         })
@@ -290,6 +356,7 @@ impl<'b, 's, W: Write + Seek> MwFileBuilderReadyCompressFrames<'b, 's, W> {
                 writer: self.scratch,
                 hasher: None,
                 is_header: self.is_header,
+                time_ms: self.frames_time_ms,
             },
         ))
     }
@@ -303,6 +370,7 @@ impl<W: Write + Seek> MwFileBuilderAwaitsCompressFrames<W> {
             buf: frames.buf,
             writer: self.writer,
             scratch: frames.writer,
+            frames_time_ms: frames.time_ms,
             is_hash: self.is_hash,
             is_header: frames.is_header,
         })
@@ -328,11 +396,21 @@ impl<'b, W: Write + Seek> MwISBuilder<'b, W> {
             hasher: None,
         })
     }
+    /// Set the number of plids and sub-plids in the game
+    ///
+    /// Frames can only contain views for plids up to `max_plid`.
+    pub fn with_max_plid(mut self, max_plid: u8, max_sub_plid: u8) -> Self {
+        self.header.set_max_plid(max_plid);
+        self.header.set_max_sub_plid(max_sub_plid);
+        self
+    }
     pub fn finish(mut self) -> Result<MwISComplete<'b, W>, MwWriterError> {
+        let off_end = self.writer.stream_position()?;
         self.buf.clear();
         self.header.serialize(self.buf);
         self.writer.seek(SeekFrom::Start(self.off_header as u64))?;
         self.writer.write_all(self.buf)?;
+        self.writer.seek(SeekFrom::Start(off_end))?;
         Ok(MwISComplete {
             buf: self.buf,
             writer: self.writer,
@@ -340,6 +418,16 @@ impl<'b, W: Write + Seek> MwISBuilder<'b, W> {
             hash: self.hasher.map(|h| h.finish()),
         })
     }
+    /// Do not include any map data
+    pub fn without_map(self) -> MwISBuilderWithMap<'b, W> {
+        MwISBuilderWithMap {
+            buf: self.buf,
+            off_header: self.off_header,
+            header: self.header,
+            writer: self.writer,
+            hasher: self.hasher,
+        }
+    }
     pub fn with_map_uncompressed<C: Coord, D: MapTileDataOut, L: MapDataLayout<C>>(
         mut self,
         mapdata: &MapData<C, D, L>,
@@ -391,10 +479,12 @@ impl<'b, W: Write + Seek> MwISBuilderWithMap<'b, W> {
         self.writer
     }
     pub fn finish(mut self) -> Result<MwISComplete<'b, W>, MwWriterError> {
+        let off_end = self.writer.stream_position()?;
         self.buf.clear();
         self.header.serialize(self.buf);
         self.writer.seek(SeekFrom::Start(self.off_header as u64))?;
         self.writer.write_all(self.buf)?;
+        self.writer.seek(SeekFrom::Start(off_end))?;
         Ok(MwISComplete {
             buf: self.buf,
             writer: self.writer,
@@ -449,10 +539,12 @@ impl<'b, W: Write + Seek> MwISBuilderWithCits<'b, W> {
         self.writer
     }
     pub fn finish(mut self) -> Result<MwISComplete<'b, W>, MwWriterError> {
+        let off_end = self.writer.stream_position()?;
         self.buf.clear();
         self.header.serialize(self.buf);
         self.writer.seek(SeekFrom::Start(self.off_header as u64))?;
         self.writer.write_all(self.buf)?;
+        self.writer.seek(SeekFrom::Start(off_end))?;
         Ok(MwISComplete {
             buf: self.buf,
             writer: self.writer,
@@ -460,7 +552,30 @@ impl<'b, W: Write + Seek> MwISBuilderWithCits<'b, W> {
             hash: self.hasher.map(|h| h.finish()),
         })
     }
-    pub fn with_rules(self) -> Result<MwISBuilderWithRules<'b, W>, MwWriterError> {
+    /// Encode and include the game rules
+    pub fn with_rules(self, rules: &MwRules) -> Result<MwISBuilderWithRules<'b, W>, MwWriterError> {
+        let mut rules_data = Vec::new();
+        crate::rules::serialize_rules(rules, &mut rules_data);
+        self.with_rules_raw(&rules_data)
+    }
+    /// Do not include any rules data
+    pub fn without_rules(self) -> Result<MwISBuilderWithRules<'b, W>, MwWriterError> {
+        Ok(MwISBuilderWithRules {
+            buf: self.buf,
+            off_header: self.off_header,
+            header: self.header,
+            writer: self.writer,
+            hasher: self.hasher,
+        })
+    }
+    /// Include already-encoded rules data (as-is)
+    pub fn with_rules_raw(mut self, rules_data: &[u8]) -> Result<MwISBuilderWithRules<'b, W>, MwWriterError> {
+        self.header.len_rules = rules_data.len().try_into()
+            .map_err(|_| MwWriterError::RulesTooLong)?;
+        if let Some(ref mut h) = &mut self.hasher {
+            h.write(rules_data);
+        }
+        self.writer.write_all(rules_data)?;
         Ok(MwISBuilderWithRules {
             buf: self.buf,
             off_header: self.off_header,
@@ -475,10 +590,12 @@ impl<'b, W: Write + Seek> MwISBuilderWithRules<'b, W> {
         self.writer
     }
     pub fn finish(mut self) -> Result<MwISComplete<'b, W>, MwWriterError> {
+        let off_end = self.writer.stream_position()?;
         self.buf.clear();
         self.header.serialize(self.buf);
         self.writer.seek(SeekFrom::Start(self.off_header as u64))?;
         self.writer.write_all(self.buf)?;
+        self.writer.seek(SeekFrom::Start(off_end))?;
         Ok(MwISComplete {
             buf: self.buf,
             writer: self.writer,
@@ -486,8 +603,71 @@ impl<'b, W: Write + Seek> MwISBuilderWithRules<'b, W> {
             hash: self.hasher.map(|h| h.finish()),
         })
     }
+    /// Encode and include the players roster
+    ///
+    /// `players` is indexed by plid, starting with the spectator.
+    pub fn with_players(self, players: &[PlidInfo]) -> Result<MwISBuilderWithPlayers<'b, W>, MwWriterError> {
+        let mut players_data = Vec::new();
+        crate::players::serialize_players(players, &mut players_data);
+        self.with_players_raw(&players_data)
+    }
+    /// Do not include any players data
+    pub fn without_players(self) -> Result<MwISBuilderWithPlayers<'b, W>, MwWriterError> {
+        self.with_players_raw(&[])
+    }
+    /// Include already-encoded players data (as-is)
+    pub fn with_players_raw(mut self, players_data: &[u8]) -> Result<MwISBuilderWithPlayers<'b, W>, MwWriterError> {
+        self.header.len_players = players_data.len().try_into()
+            .map_err(|_| MwWriterError::PlayersTooLong)?;
+        if let Some(ref mut h) = &mut self.hasher {
+            h.write(players_data);
+        }
+        self.writer.write_all(players_data)?;
+        Ok(MwISBuilderWithPlayers {
+            buf: self.buf,
+            off_header: self.off_header,
+            header: self.header,
+            writer: self.writer,
+            hasher: self.hasher,
+        })
+    }
 }
-impl<'b, W: Write + Seek> MwFrameBuilder<'b, W> {
+impl<'b, W: Write + Seek> MwISBuilderWithPlayers<'b, W> {
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+    pub fn finish(mut self) -> Result<MwISComplete<'b, W>, MwWriterError> {
+        let off_end = self.writer.stream_position()?;
+        self.buf.clear();
+        self.header.serialize(self.buf);
+        self.writer.seek(SeekFrom::Start(self.off_header as u64))?;
+        self.writer.write_all(self.buf)?;
+        self.writer.seek(SeekFrom::Start(off_end))?;
+        Ok(MwISComplete {
+            buf: self.buf,
+            writer: self.writer,
+            header: self.header,
+            hash: self.hasher.map(|h| h.finish()),
+        })
+    }
+}
+impl<'b, W: Write> MwFrameBuilder<'b, W> {
+    /// Create a builder for bare frame data, without an IS or file header
+    ///
+    /// Useful for encoding game updates into memory, such as while
+    /// recording a game session. `max_plid` must match the IS that
+    /// the frames will later be stored with.
+    pub fn new(writer: W, buf: &'b mut Vec<u8>, max_plid: u8) -> Self {
+        let mut is_header = ISHeader::default();
+        is_header.set_max_plid(max_plid);
+        Self {
+            buf,
+            hasher: None,
+            writer,
+            is_header,
+            time_ms: 0,
+        }
+    }
     pub fn into_inner(self) -> W {
         self.writer
     }
@@ -497,10 +677,104 @@ impl<'b, W: Write + Seek> MwFrameBuilder<'b, W> {
             writer: self.writer,
             hasher: self.hasher,
             is_header: self.is_header,
+            time_ms: self.time_ms,
         })
     }
-    pub fn append_msgs(&mut self) -> Result<(), MwWriterError> {
-        todo!()
+    /// The timestamp of the last frame written
+    pub fn current_time_ms(&self) -> u64 {
+        self.time_ms
+    }
+    /// Encode game update messages as frames
+    ///
+    /// `msgs` is indexed by plid (index 0 is the spectator view). Only the views
+    /// selected by `plids` and with at least one message are encoded.
+    ///
+    /// If every view gets the same messages, Homogenous frames are written.
+    /// Otherwise, Heterogenous frames. If the data for any view exceeds the
+    /// max stream length, it is split into multiple frames. Keepalive frames
+    /// are inserted if needed to reach `time_ms`.
+    pub fn append_msgs(&mut self, time_ms: u64, plids: Plids, msgs: &[&[MwEv]]) -> Result<(), MwWriterError> {
+        if time_ms < self.time_ms {
+            return Err(MwWriterError::TimeTravel(time_ms));
+        }
+        let max_plid = self.is_header.max_plid();
+        let get_msgs = |plid: PlayerId| msgs.get(plid.i()).copied().unwrap_or(&[]);
+        let mut views = plids.iter(Some(max_plid)).filter(|plid| !get_msgs(*plid).is_empty());
+        let Some(first) = views.next() else {
+            return Ok(());
+        };
+        let homogenous = views.all(|plid| get_msgs(plid) == get_msgs(first));
+
+        // encode the data for all the views into `buf`,
+        // recording where each chunk begins and ends
+        self.buf.clear();
+        let mut chunks = Vec::new();
+        if homogenous {
+            encode_stream_chunks(self.buf, &mut chunks, first, get_msgs(first))?;
+        } else {
+            for plid in plids.iter(Some(max_plid)) {
+                encode_stream_chunks(self.buf, &mut chunks, plid, get_msgs(plid))?;
+            }
+        }
+        if chunks.is_empty() {
+            return Ok(());
+        }
+
+        let mut delta = time_ms - self.time_ms;
+        while delta >= FRAME_KEEPALIVE_MS {
+            self.write_hashed(&0xFFFFu16.to_be_bytes(), None)?;
+            delta -= FRAME_KEEPALIVE_MS;
+        }
+        self.time_ms = time_ms;
+
+        if homogenous {
+            let mut mask = 0u16;
+            for plid in plids.iter(Some(max_plid)).filter(|plid| !get_msgs(*plid).is_empty()) {
+                mask |= 1 << u8::from(plid);
+            }
+            for (_, start, end, _) in chunks {
+                let header = (delta as u16) | (1 << 15);
+                delta = 0;
+                self.write_hashed(&header.to_be_bytes(), None)?;
+                self.write_hashed(&mask.to_be_bytes()[(2 - len_plidsmask(max_plid))..], None)?;
+                self.write_hashed(&[(end - start - 1) as u8], Some((start, end)))?;
+            }
+        } else {
+            let n_frames = chunks.iter().map(|c| c.3 + 1).max().unwrap_or(0);
+            let mut lens = Vec::with_capacity(max_plid as usize + 1);
+            for i_frame in 0..n_frames {
+                let mut mask = 0u16;
+                lens.clear();
+                for (plid, start, end, _) in chunks.iter().filter(|c| c.3 == i_frame) {
+                    mask |= 1 << u8::from(*plid);
+                    lens.push((end - start - 1) as u8);
+                }
+                let header = delta as u16;
+                delta = 0;
+                self.write_hashed(&header.to_be_bytes(), None)?;
+                self.write_hashed(&mask.to_be_bytes()[(2 - len_plidsmask(max_plid))..], None)?;
+                self.write_hashed(&lens, None)?;
+                for (_, start, end, _) in chunks.iter().filter(|c| c.3 == i_frame) {
+                    self.write_hashed(&[], Some((*start, *end)))?;
+                }
+            }
+        }
+        self.buf.clear();
+        Ok(())
+    }
+    /// Write `data`, followed by the range `buf_range` of `self.buf`
+    fn write_hashed(&mut self, data: &[u8], buf_range: Option<(usize, usize)>) -> Result<(), MwWriterError> {
+        let buf_data = match buf_range {
+            Some((start, end)) => &self.buf[start..end],
+            None => &[],
+        };
+        if let Some(ref mut h) = &mut self.hasher {
+            h.write(data);
+            h.write(buf_data);
+        }
+        self.writer.write_all(data)?;
+        self.writer.write_all(buf_data)?;
+        Ok(())
     }
     pub fn append_raw_data(&mut self, raw_data: &[u8]) -> Result<(), MwWriterError> {
         if let Some(ref mut h) = &mut self.hasher {
@@ -510,3 +784,293 @@ impl<'b, W: Write + Seek> MwFrameBuilder<'b, W> {
         Ok(())
     }
 }
+
+impl<'b, W: Write> MwStreamWriter<'b, W> {
+    /// Write the IS, and prepare for writing frames after it
+    ///
+    /// `is` must have been built into an in-memory buffer.
+    pub fn new<V>(mut writer: W, is: MwISComplete<'b, Cursor<V>>) -> Result<Self, MwWriterError>
+    where
+        V: AsRef<[u8]>,
+        Cursor<V>: Write + Seek,
+    {
+        let off_end = is.writer.position() as usize;
+        let off_start = off_end - is.header.len_total_is();
+        writer.write_all(&is.writer.get_ref().as_ref()[off_start..off_end])?;
+        Ok(Self::with_frames(MwFrameBuilder {
+            buf: is.buf,
+            hasher: None,
+            writer,
+            is_header: is.header,
+            time_ms: 0,
+        }))
+    }
+    /// Only write frames, if the IS has already been sent some other way
+    pub fn new_frames_only(writer: W, buf: &'b mut Vec<u8>, max_plid: u8) -> Self {
+        Self::with_frames(MwFrameBuilder::new(writer, buf, max_plid))
+    }
+    fn with_frames(frames: MwFrameBuilder<'b, W>) -> Self {
+        let max_plid = frames.is_header.max_plid();
+        Self {
+            frames,
+            pending_time_ms: 0,
+            pending_plids: Plids::default(),
+            pending_msgs: vec![Vec::new(); max_plid as usize + 1],
+        }
+    }
+    /// Get the underlying writer
+    ///
+    /// Any outputs not yet encoded are lost. Call `flush` first.
+    pub fn into_inner(self) -> W {
+        self.frames.writer
+    }
+    /// The timestamp of the last output appended
+    pub fn current_time_ms(&self) -> u64 {
+        self.pending_time_ms
+    }
+    /// Append a game output that happened at `time_ms`
+    pub fn append_output<Io: GameIo<OutEvent = MwEv>>(&mut self, time_ms: u64, output: &GameOutput<Io>) -> Result<(), MwWriterError> {
+        self.append_msg(time_ms, output.plids, &output.output)
+    }
+    /// Append a message for the views of `plids`, that happened at `time_ms`
+    pub fn append_msg(&mut self, time_ms: u64, plids: Plids, msg: &MwEv) -> Result<(), MwWriterError> {
+        if time_ms < self.pending_time_ms {
+            return Err(MwWriterError::TimeTravel(time_ms));
+        }
+        if time_ms > self.pending_time_ms {
+            self.encode_pending()?;
+            self.pending_time_ms = time_ms;
+        }
+        let max_plid = self.frames.is_header.max_plid();
+        for plid in plids.iter(Some(max_plid)) {
+            self.pending_msgs[plid.i()].push(msg.clone());
+            self.pending_plids += plid;
+        }
+        Ok(())
+    }
+    /// Encode all outputs appended so far and flush the underlying writer
+    pub fn flush(&mut self) -> Result<(), MwWriterError> {
+        self.encode_pending()?;
+        self.frames.writer.flush()?;
+        Ok(())
+    }
+    fn encode_pending(&mut self) -> Result<(), MwWriterError> {
+        let msgs: Vec<&[MwEv]> = self.pending_msgs.iter().map(|v| v.as_slice()).collect();
+        self.frames.append_msgs(self.pending_time_ms, self.pending_plids, &msgs)?;
+        self.pending_msgs.iter_mut().for_each(|v| v.clear());
+        self.pending_plids = Plids::default();
+        Ok(())
+    }
+}
+
+fn len_plidsmask(max_plid: u8) -> usize {
+    if max_plid <= 7 { 1 } else { 2 }
+}
+
+/// Encode messages, splitting them into chunks of at most `FRAME_MAX_STREAM_LEN`
+///
+/// Appends the data to `buf` and a `(plid, start, end, index)` entry to `chunks`
+/// for each chunk.
+fn encode_stream_chunks(
+    buf: &mut Vec<u8>,
+    chunks: &mut Vec<(PlayerId, usize, usize, usize)>,
+    plid: PlayerId,
+    mut msgs: &[MwEv],
+) -> Result<(), MwWriterError> {
+    let mut msg_writer = MsgBinWrite::new();
+    let mut start = buf.len();
+    let mut index = 0;
+    while !msgs.is_empty() {
+        let len_prev = buf.len();
+        let max_bytes = FRAME_MAX_STREAM_LEN - (len_prev - start);
+        let (n_msgs, _) = msg_writer.write(buf, msgs, max_bytes)?;
+        // do not trust the byte count; check what was actually written
+        if n_msgs != 0 && buf.len() - start <= FRAME_MAX_STREAM_LEN {
+            msgs = &msgs[n_msgs..];
+            continue;
+        }
+        // did not fit; start a new chunk
+        buf.truncate(len_prev);
+        if len_prev == start {
+            return Err(MwWriterError::MsgTooLong);
+        }
+        chunks.push((plid, start, len_prev, index));
+        start = len_prev;
+        index += 1;
+    }
+    if buf.len() > start {
+        chunks.push((plid, start, buf.len(), index));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use mw_common::{game::{MapGenTileData, PlayerEv}, plid::PlayerId};
+
+    use crate::{msg::{bin::MsgBinRead, MsgReader}, players::SubPlidInfo, read::{FrameKind, MwFileReader, MwFrameDataReader, MwFrameReader, MwStreamDecoder, MwStreamItem}};
+
+    use super::*;
+
+    #[test]
+    fn is_data_then_frames() {
+        let map: MapDataC<Hex, MapGenTileData> = MapData::new(4, MapGenTileData::default());
+        let cit_name = [Ph::A];
+        let players = vec![
+            PlidInfo::default(),
+            PlidInfo {
+                color: [10, 20, 30],
+                subplids: vec![SubPlidInfo { display_name: "player".into() }],
+            },
+        ];
+        let mut buf = Vec::new();
+        let mut out = Cursor::new(Vec::new());
+        let (b_file, b_is) = MwFileBuilder::new(&mut out, &mut buf).unwrap()
+            .start_is().unwrap();
+        let is = b_is.with_max_plid(1, 0)
+            .with_map_uncompressed(&map, true).unwrap()
+            .with_cits([(Pos(1, 2), cit_name.as_slice())]).unwrap()
+            .without_rules().unwrap()
+            .with_players(&players).unwrap()
+            .finish().unwrap();
+        let (b_file, mut b_frames) = b_file.with_is(is).unwrap()
+            .start_frames().unwrap();
+        b_frames.append_msgs(100, Plids::all(true), &[&[MwEv::Tremor], &[MwEv::Tremor]]).unwrap();
+        b_file.with_frames(b_frames.finish().unwrap()).unwrap()
+            .finish().unwrap();
+
+        let mut mfr = MwFileReader::new(Cursor::new(out.into_inner()), &mut buf).unwrap();
+        mfr.verify_checksums().unwrap();
+        let (_, mut isr) = mfr.read_is().unwrap();
+        assert_eq!(isr.read_cits_pos().unwrap(), &[Pos(1, 2)]);
+        assert!(isr.read_rules().unwrap().is_none());
+        assert_eq!(isr.read_players().unwrap(), players);
+    }
+
+    #[test]
+    fn raw_frames_copy() {
+        let mut buf = Vec::new();
+        let mut orig = Vec::new();
+        let mut b_frames = MwFrameBuilder::new(Cursor::new(&mut orig), &mut buf, 2);
+        let smokes: Vec<_> = (0..50).map(|i| MwEv::Smoke { pos: Pos(i, i) }).collect();
+        b_frames.append_msgs(10, Plids::all(true), &[&[MwEv::Tremor], &[MwEv::Tremor]]).unwrap();
+        b_frames.append_msgs(50000, Plids::all(true), &[&smokes, &[], &[MwEv::Tremor]]).unwrap();
+        b_frames.finish().unwrap();
+
+        let mut copy = Vec::new();
+        let mut buf_r = Vec::new();
+        let mut frames = MwFrameDataReader::new(Cursor::new(&orig), &mut buf_r, 2).unwrap();
+        while frames.advance_next_frame().is_ok() {
+            frames.read_raw_frame(&mut copy).unwrap();
+        }
+        assert_eq!(copy, orig);
+    }
+
+    #[test]
+    fn frames_roundtrip() {
+        let mut buf = Vec::new();
+        let mut out = Cursor::new(Vec::new());
+        let (b_file, b_is) = MwFileBuilder::new(&mut out, &mut buf).unwrap()
+            .start_is().unwrap();
+        let is = b_is.with_max_plid(3, 0).finish().unwrap();
+        let (b_file, mut b_frames) = b_file.with_is(is).unwrap()
+            .start_frames().unwrap();
+
+        let all = [MwEv::Tremor];
+        b_frames.append_msgs(100, Plids::all(true), &[&all, &all, &all, &all]).unwrap();
+        let smokes: Vec<_> = (0..100).map(|i| MwEv::Smoke { pos: Pos(i, -i) }).collect();
+        let elim = [MwEv::Player { plid: PlayerId::from(2), subplid: None, ev: PlayerEv::Eliminated }];
+        b_frames.append_msgs(40100, Plids::all(true), &[&smokes, &[], &elim]).unwrap();
+        assert!(matches!(
+            b_frames.append_msgs(50, Plids::all(true), &[&all]),
+            Err(MwWriterError::TimeTravel(50))
+        ));
+        b_file.with_frames(b_frames.finish().unwrap()).unwrap()
+            .finish().unwrap();
+
+        let mut mfr = MwFileReader::new(Cursor::new(out.into_inner()), &mut buf).unwrap();
+        mfr.verify_checksums().unwrap();
+        let MwFrameReader::Uncompressed(mut frames) = mfr.read_frames(None).unwrap() else {
+            panic!("frames should not be compressed");
+        };
+        let mut kinds = vec![];
+        let mut decoded = vec![];
+        while frames.advance_next_frame().is_ok() {
+            kinds.push((frames.frame_kind(), frames.current_time_ms()));
+            let mut views = vec![];
+            for stream in frames.iter_streams() {
+                let mut msgs = vec![];
+                MsgBinRead::new().read_all(&mut stream.unwrap(), &mut msgs).unwrap();
+                views.push(msgs);
+            }
+            assert_eq!(views.len(), 4);
+            assert_eq!(frames.contains_view(PlayerId::from(0)), frames.frame_kind() != FrameKind::Keepalive);
+            decoded.push(views);
+        }
+        assert_eq!(kinds, vec![
+            (FrameKind::Homogenous, 100),
+            (FrameKind::Keepalive, 100 + 0x7FFF),
+            (FrameKind::Heterogenous, 40100),
+            (FrameKind::Heterogenous, 40100),
+        ]);
+        assert_eq!(decoded[0], vec![all.to_vec(), all.to_vec(), all.to_vec(), all.to_vec()]);
+        let smokes_decoded: Vec<_> = decoded[2][0].iter().chain(decoded[3][0].iter()).cloned().collect();
+        assert_eq!(smokes_decoded, smokes);
+        assert_eq!(decoded[2][2], elim.to_vec());
+        assert!(decoded[3][2].is_empty());
+    }
+
+    #[test]
+    fn stream_writer_forward_only() {
+        let map: MapDataC<Sq, MapGenTileData> = MapData::new(3, MapGenTileData::default());
+        let mut buf = Vec::new();
+        let mut is_data = Vec::new();
+        let is = MwISBuilder::new(Cursor::new(&mut is_data), &mut buf).unwrap()
+            .with_max_plid(2, 0)
+            .with_map_uncompressed(&map, false).unwrap()
+            .finish().unwrap();
+        // a plain `Vec` does not implement `Seek`
+        let mut out: Vec<u8> = Vec::new();
+        let mut w = MwStreamWriter::new(&mut out, is).unwrap();
+        let elim = MwEv::Player { plid: PlayerId::from(1), subplid: None, ev: PlayerEv::Eliminated };
+        w.append_msg(10, Plids::all(true), &MwEv::Tremor).unwrap();
+        w.append_msg(10, PlayerId::from(2).into(), &MwEv::Smoke { pos: Pos(1, 1) }).unwrap();
+        w.append_msg(500, Plids::all(true), &elim).unwrap();
+        assert!(matches!(
+            w.append_msg(20, Plids::all(true), &MwEv::Tremor),
+            Err(MwWriterError::TimeTravel(20))
+        ));
+        w.flush().unwrap();
+
+        let mut decoder = MwStreamDecoder::new();
+        decoder.push(&out);
+        let mut decoded = vec![];
+        while let Some(item) = decoder.decode_next().unwrap() {
+            match item {
+                MwStreamItem::ISHeader(header) => {
+                    assert_eq!(header.max_plid(), 2);
+                    assert_eq!(header.map_topology(), Topology::Sq);
+                }
+                MwStreamItem::Frame(frame) => {
+                    let mut views = vec![];
+                    for mut stream in frame.iter_streams() {
+                        let mut msgs = vec![];
+                        MsgBinRead::new().read_all(&mut stream, &mut msgs).unwrap();
+                        views.push(msgs);
+                    }
+                    decoded.push((frame.current_time_ms(), views));
+                }
+                _ => {}
+            }
+        }
+        assert_eq!(decoder.len_pending(), 0);
+        assert_eq!(decoded, vec![
+            (10, vec![
+                vec![MwEv::Tremor],
+                vec![MwEv::Tremor],
+                vec![MwEv::Tremor, MwEv::Smoke { pos: Pos(1, 1) }],
+            ]),
+            (500, vec![vec![elim.clone()], vec![elim.clone()], vec![elim.clone()]]),
+        ]);
+    }
+}
@@ -3,6 +3,7 @@ use super::*;
 pub struct GameMinesweeperBuilder {
     settings: MinesweeperSettings,
     playerdata: Vec<PlayerData>,
+    seed: Option<u64>,
 }
 
 impl GameMinesweeperBuilder {
@@ -11,10 +12,31 @@ impl GameMinesweeperBuilder {
             playerdata: vec![PlayerData {
                 n_owned: 0,
                 n_lives: settings.n_lives,
+                n_subplids: 1,
+                elim_order: 0,
+                elim_secs: 0,
             }; starting_plids as usize],
             settings,
+            seed: None,
         }
     }
+    /// Use a specific RNG seed, to make the game reproducible.
+    ///
+    /// If not set, a random seed is used. Either way, the seed
+    /// can be queried from the game afterwards.
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+    /// Allow multiple subplids to play as `plid`, sharing its territory and lives.
+    ///
+    /// This is how Co-op games are set up.
+    pub fn with_subplids(mut self, plid: PlayerId, n_subplids: u8) -> Self {
+        if let Some(playerdata) = self.playerdata.get_mut(plid.i().wrapping_sub(1)) {
+            playerdata.n_subplids = n_subplids;
+        }
+        self
+    }
     pub fn with_mapdata_hex(
         self,
         map_size: u8,
@@ -51,13 +73,19 @@ impl GameMinesweeperBuilder {
             }
             d.set_kind(kind);
         });
+        let seed = self.seed.unwrap_or_else(rand::random);
         GameMinesweeperTopo {
             settings: self.settings,
             mapdata,
             playerdata: self.playerdata,
             n_unexplored_tiles,
             floodq: Default::default(),
-            rng: MyRng::from_entropy(),
+            rng: MyRng::seed_from_u64(seed),
+            seed,
+            minegen: Default::default(),
+            started: std::time::Instant::now(),
+            n_eliminated: 0,
+            is_game_over: false,
         }
     }
 }
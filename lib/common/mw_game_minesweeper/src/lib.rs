@@ -5,8 +5,10 @@ use mw_common::driver::*;
 use modular_bitfield::prelude::*;
 
 pub mod minegen;
+pub mod solver;
 
 pub mod builder;
+pub mod snapshot;
 
 /// Settings that can be configured for a session of the Minesweeper game mode
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -17,6 +19,8 @@ pub struct MinesweeperSettings {
     pub n_lives: u8,
     /// If nonzero, limit the maximum time allowed for the game.
     pub time_limit_secs: u16,
+    /// Which flavor of Minesweeper to play.
+    pub rules: MinesweeperRules,
 }
 
 impl Default for MinesweeperSettings {
@@ -24,13 +28,31 @@ impl Default for MinesweeperSettings {
         Self {
             n_lives: 1,
             time_limit_secs: 0,
+            rules: MinesweeperRules::MineWars,
         }
     }
 }
 
+/// The flavor of Minesweeper rules to play by
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "bevy", derive(Reflect))]
+pub enum MinesweeperRules {
+    /// With the MineWars twists: decoys (shown as asterisk digits),
+    /// and exploded mines becoming captured territory.
+    #[default]
+    MineWars,
+    /// Plain old Minesweeper: no decoys, exploded mines stay mines,
+    /// and the first tile explored has no mines around it.
+    Classic,
+}
+
 #[derive(Debug, Clone)]
 pub struct MinesweeperInitData {
     pub minegen: minegen::MineGenSettings,
+    /// If set, re-seed the game's RNG before generating the mines.
+    ///
+    /// Use this to reproduce a specific board.
+    pub seed: Option<u64>,
 }
 
 #[derive(Debug, Clone)]
@@ -42,15 +64,28 @@ pub enum MinesweeperInputAction {
     ToggleFlag {
         pos: Pos,
     },
+    /// On one of our digits: if the number of flags around it
+    /// matches the digit, explore all the other tiles around it.
+    ChordTile {
+        pos: Pos,
+    },
 }
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Serialize, Deserialize)]
 pub struct PlayerData {
     n_owned: u16,
     n_lives: u8,
+    /// How many subplids act on behalf of this plid.
+    ///
+    /// More than one means Co-op: they share the territory and lives.
+    n_subplids: u8,
+    /// In what order the plid was eliminated (0 if still playing)
+    elim_order: u8,
+    /// When the plid was eliminated (seconds since the start of the game)
+    elim_secs: u16,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum MinesweeperSchedEvent {
     GameOverOutOfTime,
 }
@@ -70,6 +105,37 @@ pub enum GameMinesweeper {
     Sq(GameMinesweeperTopo<Sq>),
 }
 
+impl GameMinesweeper {
+    /// The seed that the game's RNG was initialized with
+    ///
+    /// Can be given to `GameMinesweeperBuilder::with_seed` or
+    /// `MinesweeperInitData::seed` to regenerate the same board.
+    pub fn seed(&self) -> u64 {
+        match self {
+            GameMinesweeper::Hex(game) => game.seed(),
+            GameMinesweeper::Sq(game) => game.seed(),
+        }
+    }
+    /// What a player can figure out about the map, from what they can see
+    ///
+    /// See `GameMinesweeperTopo::hint`.
+    pub fn hint(&self, plid: PlayerId) -> solver::Hints<Pos> {
+        fn to_pos<C: Coord>(hints: solver::Hints<C>) -> solver::Hints<Pos> {
+            solver::Hints {
+                safe: hints.safe.into_iter().map(Into::into).collect(),
+                hazard: hints.hazard.into_iter().map(Into::into).collect(),
+                probabilities: hints.probabilities.into_iter()
+                    .map(|(c, p)| (c.into(), p))
+                    .collect(),
+            }
+        }
+        match self {
+            GameMinesweeper::Hex(game) => to_pos(game.hint(plid)),
+            GameMinesweeper::Sq(game) => to_pos(game.hint(plid)),
+        }
+    }
+}
+
 impl Game for Box<GameMinesweeper> {
     type Io = MinesweeperIo;
     type InitData = MinesweeperInitData;
@@ -100,6 +166,9 @@ impl Game for Box<GameMinesweeper> {
     }
 }
 
+/// How many fresh layouts to try, when generating a no-guess board
+const NO_GUESS_ATTEMPTS: usize = 3;
+
 pub struct GameMinesweeperTopo<C: Coord> {
     settings: MinesweeperSettings,
     mapdata: MapDataC<C, TileData>,
@@ -107,6 +176,11 @@ pub struct GameMinesweeperTopo<C: Coord> {
     n_unexplored_tiles: u16,
     floodq: FloodQ,
     rng: MyRng,
+    seed: u64,
+    minegen: minegen::MineGenSettings,
+    started: std::time::Instant,
+    n_eliminated: u8,
+    is_game_over: bool,
 }
 
 #[bitfield]
@@ -116,7 +190,9 @@ struct TileData {
     flag: B4,
     item: ItemKind,
     kind: TileKind,
-    #[skip] __: B3,
+    /// A mine that has exploded, but remains a mine (in Classic rules)
+    exploded: bool,
+    #[skip] __: B2,
 }
 
 impl<C: Coord> Game for GameMinesweeperTopo<C> {
@@ -124,13 +200,23 @@ impl<C: Coord> Game for GameMinesweeperTopo<C> {
     type InitData = MinesweeperInitData;
 
     fn init<H: Host<MinesweeperIo>>(&mut self, host: &mut H, initdata: Box<MinesweeperInitData>) {
+        if let Some(seed) = initdata.seed {
+            self.seed = seed;
+            self.rng = MyRng::seed_from_u64(seed);
+        }
+        self.minegen = initdata.minegen.clone();
+        if self.settings.rules == MinesweeperRules::Classic {
+            self.minegen.prob_decoy = 0;
+        }
         minegen::gen_mines(
-            &initdata.minegen,
+            &self.minegen,
             &mut self.mapdata,
             &mut self.rng,
             |d| d.kind(),
             |d, i| d.set_item(i),
         );
+        self.count_unexplored_tiles();
+        self.started = host.now();
         // schedule an event for "game over by running out of time"
         if self.settings.time_limit_secs != 0 {
             host.msg((Plids::all(true), MwEv::Player {
@@ -141,7 +227,7 @@ impl<C: Coord> Game for GameMinesweeperTopo<C> {
                 },
             }).into());
             host.sched(
-                Instant::now() + Duration::from_secs(self.settings.time_limit_secs as u64),
+                host.now() + Duration::from_secs(self.settings.time_limit_secs as u64),
                 MinesweeperSchedEvent::GameOverOutOfTime
             );
         }
@@ -151,7 +237,7 @@ impl<C: Coord> Game for GameMinesweeperTopo<C> {
             return;
         }
         if let Some(playerdata) = self.playerdata.get(input.plid.i()-1) {
-            if playerdata.n_lives == 0 {
+            if playerdata.n_lives == 0 || input.subplid >= playerdata.n_subplids {
                 return;
             }
         } else {
@@ -159,11 +245,14 @@ impl<C: Coord> Game for GameMinesweeperTopo<C> {
         }
         match input.input {
             MinesweeperInputAction::ExploreTile { pos } => {
-                self.explore_tile(host, input.plid, pos.into());
+                self.explore_tile(host, input.plid, input.subplid, pos.into());
             }
             MinesweeperInputAction::ToggleFlag { pos } => {
                 self.flag(host, input.plid, pos.into());
             }
+            MinesweeperInputAction::ChordTile { pos } => {
+                self.chord_tile(host, input.plid, input.subplid, pos.into());
+            }
         }
     }
     fn unsched<H: Host<MinesweeperIo>>(&mut self, host: &mut H, event: MinesweeperSchedEvent) {
@@ -178,18 +267,49 @@ impl<C: Coord> Game for GameMinesweeperTopo<C> {
                         }).into());
                     }
                 }
-                host.game_over();
+                self.game_over(host);
             }
         }
     }
 }
 
 impl<C: Coord> GameMinesweeperTopo<C> {
+    /// The seed that the game's RNG was initialized with
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+    /// What a player can figure out about the map, from what they can see
+    ///
+    /// Uses the digits of the player's own tiles, and the fact that tiles
+    /// owned by other players are safe. Flags are not trusted, as they
+    /// could be wrong.
+    ///
+    /// Digits count decoys as well as mines, so the hints are about hazards
+    /// of either kind, and so are the odds assumed for tiles no digit
+    /// says anything about (see `MineGenSettings::hazard_density`).
+    pub fn hint(&self, plid: PlayerId) -> solver::Hints<C> {
+        let mut solver = solver::Solver::new(
+            self.mapdata.size(),
+            |c| self.mapdata[c].kind().is_land(),
+        );
+        for (c, d) in self.mapdata.iter() {
+            if d.owner() == u8::from(plid) {
+                solver.reveal(c, self.compute_digit(plid, c).0);
+            } else if d.owner() != 0 {
+                solver.mark_safe(c);
+            } else if d.exploded() {
+                solver.mark_hazard(c);
+            }
+        }
+        let mut hints = solver.hints(self.minegen.hazard_density());
+        hints.safe.retain(|c| self.mapdata[*c].owner() == 0);
+        hints
+    }
     fn flag<H: Host<MinesweeperIo>>(&mut self, host: &mut H, plid: PlayerId, c: C) {
         if c.ring() > self.mapdata.size() {
             return;
         }
-        if !self.mapdata[c].kind().is_land() || self.mapdata[c].owner() != 0 {
+        if !self.mapdata[c].kind().is_land() || self.mapdata[c].owner() != 0 || self.mapdata[c].exploded() {
             return;
         }
         if self.mapdata[c].flag() == 0 {
@@ -206,12 +326,12 @@ impl<C: Coord> GameMinesweeperTopo<C> {
             }).into());
         }
     }
-    fn explore_tile<H: Host<MinesweeperIo>>(&mut self, host: &mut H, plid: PlayerId, c: C) {
+    fn explore_tile<H: Host<MinesweeperIo>>(&mut self, host: &mut H, plid: PlayerId, subplid: u8, c: C) {
         if c.ring() > self.mapdata.size() {
             return;
         }
 
-        if !self.mapdata[c].kind().is_land() {
+        if !self.mapdata[c].kind().is_land() || self.mapdata[c].exploded() {
             return;
         }
 
@@ -219,7 +339,29 @@ impl<C: Coord> GameMinesweeperTopo<C> {
         // territory, guarantee it to be safe (forgive any mine)
         if let Some(playerdata) = self.playerdata.get(plid.i()-1) {
             if playerdata.n_owned == 0 {
-                if self.mapdata[c].item() == ItemKind::Mine {
+                // if nobody has explored anything yet, we can still
+                // rearrange the board to be solvable from here
+                if self.minegen.no_guess && self.playerdata.iter().all(|p| p.n_owned == 0) {
+                    // try a few fresh layouts; if none of them work out,
+                    // keep the last one (it may just require guessing)
+                    for _ in 0..NO_GUESS_ATTEMPTS {
+                        let solved = minegen::gen_mines_no_guess(
+                            &self.minegen,
+                            &mut self.mapdata,
+                            &mut self.rng,
+                            c,
+                            |d| d.kind(),
+                            |d, i| d.set_item(i),
+                        );
+                        if solved {
+                            break;
+                        }
+                    }
+                    self.count_unexplored_tiles();
+                }
+                if self.settings.rules == MinesweeperRules::Classic {
+                    self.clear_first_area(c);
+                } else if self.mapdata[c].item() == ItemKind::Mine {
                     if c.iter_n1().all(|c2| self.mapdata[c2].owner() == 0) {
                         self.mapdata[c].set_item(ItemKind::Safe);
                     }
@@ -246,7 +388,7 @@ impl<C: Coord> GameMinesweeperTopo<C> {
                                 self.capture_tile(host, plid, c2, false);
                             }
                             _ => {
-                                self.explode_player(host, plid, c2);
+                                self.explode_player(host, plid, subplid, c2);
                             }
                         }
                     }
@@ -258,11 +400,101 @@ impl<C: Coord> GameMinesweeperTopo<C> {
                     self.capture_tile(host, plid, c, true);
                 }
                 _ => {
-                    self.explode_player(host, plid, c);
+                    self.explode_player(host, plid, subplid, c);
+                }
+            }
+        }
+    }
+    fn chord_tile<H: Host<MinesweeperIo>>(&mut self, host: &mut H, plid: PlayerId, subplid: u8, c: C) {
+        if c.ring() > self.mapdata.size() {
+            return;
+        }
+        if self.mapdata[c].owner() != u8::from(plid) {
+            return;
+        }
+        let (digit, _) = self.compute_digit(plid, c);
+        if digit == 0 {
+            return;
+        }
+        // exploded mines are as good as flagged
+        let is_flagged = |d: &TileData| d.flag() == u8::from(plid) || d.exploded();
+        let n_flags = c.iter_n1()
+            .filter_map(|c2| self.mapdata.get(c2))
+            .filter(|d| d.owner() == 0 && is_flagged(d))
+            .count();
+        if n_flags != digit as usize {
+            return;
+        }
+        for c2 in c.iter_n1() {
+            let Some(d) = self.mapdata.get(c2) else {
+                continue;
+            };
+            if !d.kind().is_land() || d.owner() != 0 || is_flagged(d) {
+                continue;
+            }
+            // a wrong flag may have cost us the game
+            if self.is_game_over || self.playerdata[plid.i()-1].n_lives == 0 {
+                break;
+            }
+            match d.item() {
+                ItemKind::Safe => {
+                    self.capture_tile(host, plid, c2, true);
+                }
+                _ => {
+                    self.explode_player(host, plid, subplid, c2);
                 }
             }
         }
     }
+    /// Classic: count the tiles that remain to be explored (everything except mines)
+    ///
+    /// In MineWars, mines also count (stepping on one captures it),
+    /// so moving items around does not change the count from when
+    /// the map was built, and it is left as is.
+    fn count_unexplored_tiles(&mut self) {
+        if self.settings.rules != MinesweeperRules::Classic {
+            return;
+        }
+        let size = self.mapdata.size();
+        self.n_unexplored_tiles = self.mapdata.iter()
+            .filter(|(c, d)| {
+                c.ring() <= size && d.kind().is_land()
+                    && d.owner() == 0 && d.item() != ItemKind::Mine
+            })
+            .count() as u16;
+    }
+    /// Classic first click: move any mines away from the tile and its neighbors
+    ///
+    /// Tiles next to anyone's territory are left alone, both as sources and
+    /// destinations, so that digits other players can already see stay valid.
+    fn clear_first_area(&mut self, c: C) {
+        let size = self.mapdata.size();
+        let mapdata = &self.mapdata;
+        let is_free = |c: C, d: &TileData| {
+            c.ring() <= size && d.kind().is_land() && d.owner() == 0 && !d.exploded()
+                && c.iter_n1().all(|c2| mapdata.get(c2).map_or(true, |d2| d2.owner() == 0))
+        };
+        let area: Vec<C> = std::iter::once(c).chain(c.iter_n1())
+            .filter(|c2| self.mapdata.get(*c2).is_some_and(|d| is_free(*c2, d)))
+            .collect();
+        let mut spots: Vec<C> = self.mapdata.iter()
+            .filter(|(c2, d)| is_free(*c2, *d) && d.item() == ItemKind::Safe && !area.contains(c2))
+            .map(|(c2, _)| c2)
+            .collect();
+        spots.shuffle(&mut self.rng);
+        for c2 in area {
+            let item = self.mapdata[c2].item();
+            if item == ItemKind::Safe {
+                continue;
+            }
+            self.mapdata[c2].set_item(ItemKind::Safe);
+            if let Some(spot) = spots.pop() {
+                self.mapdata[spot].set_item(item);
+            } else if item == ItemKind::Mine {
+                self.n_unexplored_tiles += 1;
+            }
+        }
+    }
     fn capture_tile<H: Host<MinesweeperIo>>(&mut self, host: &mut H, plid: PlayerId, mut c: C, recurse: bool) {
         let mut q = vec![];
         loop {
@@ -285,7 +517,9 @@ impl<C: Coord> GameMinesweeperTopo<C> {
             }).into());
             let digit = self.compute_send_digit(host, plid, c);
             for c2 in c.iter_n1() {
-                let kind = self.mapdata[c2].kind();
+                let Some(kind) = self.mapdata.get(c2).map(|d| d.kind()) else {
+                    continue;
+                };
                 if kind.is_rescluster() {
                     self.mapdata[c2].set_owner(u8::from(plid));
                     host.msg((Plids::all(true), MwEv::TileOwner {
@@ -294,8 +528,11 @@ impl<C: Coord> GameMinesweeperTopo<C> {
                     self.floodq.clear();
                     self.floodq.push_back(c2.into());
                     flood(&mut self.floodq, |c3, _| {
-                        if self.mapdata[c3].kind() == kind && self.mapdata[c3].owner() != u8::from(plid) {
-                            self.mapdata[c3].set_owner(u8::from(plid));
+                        let Some(d3) = self.mapdata.get_mut(c3) else {
+                            return FloodSelect::No;
+                        };
+                        if d3.kind() == kind && d3.owner() != u8::from(plid) {
+                            d3.set_owner(u8::from(plid));
                             host.msg((Plids::all(true), MwEv::TileOwner {
                                 plid, pos: c3.into()
                             }).into());
@@ -309,6 +546,8 @@ impl<C: Coord> GameMinesweeperTopo<C> {
                     q.push(c2);
                 }
             }
+            // a tile may have been queued more than once
+            q.retain(|c2| self.mapdata[*c2].owner() == 0);
             if let Some(next_c) = q.pop() {
                 c = next_c;
             } else {
@@ -316,11 +555,12 @@ impl<C: Coord> GameMinesweeperTopo<C> {
             }
         }
         if self.n_unexplored_tiles == 0 {
-            host.game_over();
+            self.game_over(host);
         }
     }
-    fn explode_player<H: Host<MinesweeperIo>>(&mut self, host: &mut H, plid: PlayerId, c: C) {
+    fn explode_player<H: Host<MinesweeperIo>>(&mut self, host: &mut H, plid: PlayerId, subplid: u8, c: C) {
         let mut capture = true;
+        let classic = self.settings.rules == MinesweeperRules::Classic;
         match self.mapdata[c].item() {
             ItemKind::Safe => {
                 return;
@@ -341,8 +581,10 @@ impl<C: Coord> GameMinesweeperTopo<C> {
                 }).into());
             },
             ItemKind::Mine => {
-                // we now have an extra safe/explorable tile
-                self.n_unexplored_tiles += 1;
+                if !classic {
+                    // we now have an extra safe/explorable tile
+                    self.n_unexplored_tiles += 1;
+                }
                 if self.mapdata[c].flag() != 0 {
                     self.mapdata[c].set_flag(0);
                     host.msg((Plids::all(true), MwEv::Flag {
@@ -355,14 +597,31 @@ impl<C: Coord> GameMinesweeperTopo<C> {
                 host.msg((Plids::all(true), MwEv::Explode {
                     pos: c.into(),
                 }).into());
-                host.msg((Plids::all(true), MwEv::TileKind {
-                    kind: TileKind::Destroyed, pos: c.into(),
+                if !classic {
+                    self.mapdata[c].set_kind(TileKind::Destroyed);
+                    host.msg((Plids::all(true), MwEv::TileKind {
+                        kind: TileKind::Destroyed, pos: c.into(),
+                    }).into());
+                }
+                host.msg((Plids::all(true), MwEv::Player {
+                    plid,
+                    subplid: Some(subplid),
+                    ev: PlayerEv::Exploded {
+                        pos: c.into(),
+                        killer: PlayerId::Neutral,
+                    },
                 }).into());
                 if let Some(playerdata) = self.playerdata.get_mut(plid.i()-1) {
                     if playerdata.n_lives > 0 {
                         playerdata.n_lives -= 1;
                     }
                     if playerdata.n_lives == 0 {
+                        self.n_eliminated += 1;
+                        playerdata.elim_order = self.n_eliminated;
+                        playerdata.elim_secs = host.now()
+                            .saturating_duration_since(self.started)
+                            .as_secs()
+                            .min(u16::MAX as u64) as u16;
                         host.msg((Plids::all(true), MwEv::Player {
                             plid,
                             subplid: None,
@@ -378,12 +637,18 @@ impl<C: Coord> GameMinesweeperTopo<C> {
                         },
                     }).into());
                     if self.playerdata.iter().all(|p| p.n_lives == 0) {
-                        host.game_over();
+                        self.game_over(host);
                     }
                 }
             },
         }
 
+        if classic {
+            // the mine stays, but cannot be explored or flagged anymore
+            self.mapdata[c].set_exploded(true);
+            return;
+        }
+
         self.mapdata[c].set_item(ItemKind::Safe);
         for c2 in c.iter_n1() {
             if let Some(tile) = self.mapdata.get(c2) {
@@ -395,7 +660,41 @@ impl<C: Coord> GameMinesweeperTopo<C> {
             self.capture_tile(host, plid, c, false);
         }
     }
-    fn compute_digit(&mut self, plid: PlayerId, c: C) -> (u8, bool) {
+    /// Rank the plids and report everyone's final results, then end the game
+    ///
+    /// Plids are ranked by how many tiles they explored. Ties are broken in
+    /// favor of those who survived (or were eliminated later), and then by
+    /// how many lives they have left.
+    fn game_over<H: Host<MinesweeperIo>>(&mut self, host: &mut H) {
+        if self.is_game_over {
+            return;
+        }
+        self.is_game_over = true;
+        let key = |p: &PlayerData| (
+            p.n_owned,
+            p.elim_order == 0,
+            p.elim_order,
+            p.n_lives,
+        );
+        for (i, playerdata) in self.playerdata.iter().enumerate() {
+            let rank = 1 + self.playerdata.iter()
+                .filter(|p| key(p) > key(playerdata))
+                .count() as u8;
+            host.msg((Plids::all(true), MwEv::Player {
+                plid: PlayerId::from(i as u8 + 1),
+                subplid: None,
+                ev: PlayerEv::Standing {
+                    rank,
+                    tiles: playerdata.n_owned,
+                    lives: playerdata.n_lives,
+                    elim_order: playerdata.elim_order,
+                    elim_secs: playerdata.elim_secs,
+                },
+            }).into());
+        }
+        host.game_over();
+    }
+    fn compute_digit(&self, plid: PlayerId, c: C) -> (u8, bool) {
         let mut digit = 0;
         let mut asterisk = false;
         for c2 in c.iter_n1() {
@@ -409,7 +708,7 @@ impl<C: Coord> GameMinesweeperTopo<C> {
                 }
                 // minesweeper mode has no flashes, treat them as decoys
                 if tile.item() == ItemKind::Decoy || tile.item() == ItemKind::Trap {
-                    asterisk = true;
+                    asterisk = self.settings.rules != MinesweeperRules::Classic;
                 }
             }
         }
@@ -425,3 +724,464 @@ impl<C: Coord> GameMinesweeperTopo<C> {
         (digit, asterisk)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use mw_common::driver::headless::HeadlessSession;
+
+    use super::*;
+
+    fn new_session(settings: MinesweeperSettings, seed: u64) -> HeadlessSession<Box<GameMinesweeper>> {
+        let game = builder::GameMinesweeperBuilder::new(settings, 1)
+            .with_mapdata_sq(8, |_| TileKind::Regular);
+        HeadlessSession::new(game, Box::new(MinesweeperInitData {
+            minegen: Default::default(),
+            seed: Some(seed),
+        }))
+    }
+
+    fn explore(session: &mut HeadlessSession<Box<GameMinesweeper>>, pos: Pos) {
+        session.input(PlayerId::from(1), 0, MinesweeperInputAction::ExploreTile { pos });
+    }
+
+    #[test]
+    fn same_seed_same_game() {
+        let mut a = new_session(Default::default(), 1234);
+        let mut b = new_session(Default::default(), 1234);
+        assert_eq!(a.game.seed(), 1234);
+        for pos in [Pos(0, 0), Pos(3, -2), Pos(-5, 4)] {
+            explore(&mut a, pos);
+            explore(&mut b, pos);
+        }
+        let log_a: Vec<_> = a.output_log().iter().map(|(_, out)| out.output.clone()).collect();
+        let log_b: Vec<_> = b.output_log().iter().map(|(_, out)| out.output.clone()).collect();
+        assert!(!log_a.is_empty());
+        assert_eq!(log_a, log_b);
+    }
+
+    #[test]
+    fn time_limit_game_over() {
+        let settings = MinesweeperSettings {
+            time_limit_secs: 60,
+            ..Default::default()
+        };
+        let mut session = new_session(settings, 1);
+        session.advance(Duration::from_secs(59));
+        assert!(!session.is_game_over());
+        session.fast_forward();
+        assert!(session.is_game_over());
+        assert_eq!(session.elapsed(), Duration::from_secs(60));
+        let log = session.output_log();
+        let (_, eliminated) = &log[log.len() - 2];
+        assert_eq!(eliminated.output, MwEv::Player {
+            plid: PlayerId::from(1),
+            subplid: None,
+            ev: PlayerEv::Eliminated,
+        });
+        let (_, last) = log.last().unwrap();
+        assert_eq!(last.output, MwEv::Player {
+            plid: PlayerId::from(1),
+            subplid: None,
+            ev: PlayerEv::Standing {
+                rank: 1,
+                tiles: 0,
+                lives: 1,
+                elim_order: 0,
+                elim_secs: 0,
+            },
+        });
+    }
+
+    #[test]
+    fn pvp_standings() {
+        let settings = MinesweeperSettings {
+            time_limit_secs: 60,
+            ..Default::default()
+        };
+        let game = builder::GameMinesweeperBuilder::new(settings, 2)
+            .with_mapdata_sq(8, |_| TileKind::Regular);
+        let mut session = HeadlessSession::new(game, Box::new(MinesweeperInitData {
+            minegen: Default::default(),
+            seed: Some(1),
+        }));
+        session.advance(Duration::from_secs(10));
+        session.input(PlayerId::from(2), 0, MinesweeperInputAction::ExploreTile { pos: Pos(-6, -6) });
+        session.input(PlayerId::from(1), 0, MinesweeperInputAction::ExploreTile { pos: Pos(6, 6) });
+        let GameMinesweeper::Sq(game) = session.game.as_ref() else {
+            unreachable!();
+        };
+        let mine = game.mapdata.iter()
+            .find(|(_, d)| d.owner() == 0 && d.item() == ItemKind::Mine)
+            .map(|(c, _)| c)
+            .unwrap();
+        session.input(PlayerId::from(2), 0, MinesweeperInputAction::ExploreTile { pos: mine.into() });
+        session.fast_forward();
+        assert!(session.is_game_over());
+        let standings: Vec<_> = session.output_log().iter()
+            .filter_map(|(_, out)| match out.output {
+                MwEv::Player { plid, ev: PlayerEv::Standing { rank, tiles, lives, elim_order, elim_secs }, .. } =>
+                    Some((plid, rank, tiles, lives, elim_order, elim_secs)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(standings.len(), 2);
+        let (_, rank1, tiles1, _, elim_order1, _) = standings[0];
+        let (_, rank2, tiles2, lives2, elim_order2, elim_secs2) = standings[1];
+        assert_eq!(elim_order1, 0);
+        assert_eq!((lives2, elim_order2, elim_secs2), (0, 1, 10));
+        // survived, so wins ties
+        if tiles1 >= tiles2 {
+            assert_eq!((rank1, rank2), (1, 2));
+        } else {
+            assert_eq!((rank1, rank2), (2, 1));
+        }
+    }
+
+    #[test]
+    fn no_guess_board_solvable() {
+        for seed in 0..4 {
+            let game = builder::GameMinesweeperBuilder::new(Default::default(), 1)
+                .with_mapdata_sq(8, |_| TileKind::Regular);
+            let mut session = HeadlessSession::new(game, Box::new(MinesweeperInitData {
+                minegen: minegen::MineGenSettings {
+                    no_guess: true,
+                    ..Default::default()
+                },
+                seed: Some(seed),
+            }));
+            explore(&mut session, Pos(2, -3));
+            let GameMinesweeper::Sq(game) = session.game.as_ref() else {
+                unreachable!();
+            };
+            assert_eq!(game.mapdata[Sq(2, -3)].owner(), 1);
+            let solver = solver::simulate(
+                8, Sq(2, -3),
+                |c| game.mapdata[c].kind().is_land(),
+                |c| game.mapdata[c].item() != ItemKind::Safe,
+            );
+            for (c, k) in solver.iter_playable() {
+                if game.mapdata[c].item() == ItemKind::Safe {
+                    assert!(matches!(k, solver::TileKnowledge::Revealed(_)));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn hints_are_safe() {
+        for seed in 0..4 {
+            let mut session = new_session(Default::default(), seed);
+            explore(&mut session, Pos(0, 0));
+            // the first tile is a guess
+            session.take_output();
+            loop {
+                let hints = session.game.hint(PlayerId::from(1));
+                for pos in hints.hazard.iter() {
+                    let GameMinesweeper::Sq(game) = session.game.as_ref() else {
+                        unreachable!();
+                    };
+                    assert_ne!(game.mapdata[Sq::from(*pos)].item(), ItemKind::Safe);
+                }
+                let Some(pos) = hints.safe.first() else {
+                    break;
+                };
+                explore(&mut session, *pos);
+            }
+            assert!(!session.output_log().iter().any(|(_, out)| matches!(out.output, MwEv::Explode { .. })));
+        }
+    }
+
+    #[test]
+    fn hint_prior_includes_decoys() {
+        let minegen = minegen::MineGenSettings {
+            mine_density: 64,
+            prob_decoy: 128,
+            ..Default::default()
+        };
+        let game = builder::GameMinesweeperBuilder::new(Default::default(), 1)
+            .with_mapdata_sq(30, |_| TileKind::Regular);
+        let session = HeadlessSession::new(game, Box::new(MinesweeperInitData {
+            minegen: minegen.clone(),
+            seed: Some(1),
+        }));
+        let GameMinesweeper::Sq(game) = session.game.as_ref() else {
+            unreachable!();
+        };
+        let items: Vec<ItemKind> = game.mapdata.iter()
+            .filter(|(c, _)| c.ring() <= 30)
+            .map(|(_, d)| d.item())
+            .collect();
+        assert!(items.contains(&ItemKind::Decoy));
+        let n_hazards = items.iter().filter(|i| **i != ItemKind::Safe).count();
+        let density = n_hazards as f32 / items.len() as f32;
+        assert!((density - minegen.hazard_density()).abs() < 0.03);
+        // with nothing revealed, every tile gets the prior
+        let hints = session.game.hint(PlayerId::from(1));
+        assert!(!hints.probabilities.is_empty());
+        assert!(hints.probabilities.iter().all(|(_, p)| *p == minegen.hazard_density()));
+    }
+
+    #[test]
+    fn coop_shared_lives() {
+        let settings = MinesweeperSettings {
+            n_lives: 2,
+            ..Default::default()
+        };
+        let game = builder::GameMinesweeperBuilder::new(settings, 1)
+            .with_subplids(PlayerId::from(1), 2)
+            .with_mapdata_sq(8, |_| TileKind::Regular);
+        let mut session = HeadlessSession::new(game, Box::new(MinesweeperInitData {
+            minegen: Default::default(),
+            seed: Some(1),
+        }));
+        // only subplids 0 and 1 exist
+        session.input(PlayerId::from(1), 2, MinesweeperInputAction::ExploreTile { pos: Pos(0, 0) });
+        assert!(session.output_log().is_empty());
+        session.input(PlayerId::from(1), 0, MinesweeperInputAction::ExploreTile { pos: Pos(0, 0) });
+        session.take_output();
+        let GameMinesweeper::Sq(game) = session.game.as_ref() else {
+            unreachable!();
+        };
+        let mine = game.mapdata.iter()
+            .find(|(_, d)| d.owner() == 0 && d.item() == ItemKind::Mine)
+            .map(|(c, _)| c)
+            .unwrap();
+        session.input(PlayerId::from(1), 1, MinesweeperInputAction::ExploreTile { pos: mine.into() });
+        let log: Vec<_> = session.output_log().iter().map(|(_, out)| out.output.clone()).collect();
+        assert!(log.contains(&MwEv::Player {
+            plid: PlayerId::from(1),
+            subplid: Some(1),
+            ev: PlayerEv::Exploded {
+                pos: mine.into(),
+                killer: PlayerId::Neutral,
+            },
+        }));
+        assert!(log.contains(&MwEv::Player {
+            plid: PlayerId::from(1),
+            subplid: None,
+            ev: PlayerEv::LivesRemain { lives: 1 },
+        }));
+    }
+
+    #[test]
+    fn coop_shared_territory() {
+        let game = builder::GameMinesweeperBuilder::new(Default::default(), 1)
+            .with_subplids(PlayerId::from(1), 2)
+            .with_mapdata_sq(8, |_| TileKind::Regular);
+        let mut session = HeadlessSession::new(game, Box::new(MinesweeperInitData {
+            minegen: Default::default(),
+            seed: Some(1),
+        }));
+        let find_safe = |session: &HeadlessSession<Box<GameMinesweeper>>| {
+            let GameMinesweeper::Sq(game) = session.game.as_ref() else {
+                unreachable!();
+            };
+            game.mapdata.iter()
+                .filter(|(c, d)| c.ring() <= 8 && d.kind().is_land() && d.owner() == 0 && d.item() == ItemKind::Safe)
+                .map(|(c, _)| c)
+                .last()
+                .unwrap()
+        };
+        let safe0 = find_safe(&session);
+        session.input(PlayerId::from(1), 0, MinesweeperInputAction::ExploreTile { pos: safe0.into() });
+        let safe1 = find_safe(&session);
+        session.input(PlayerId::from(1), 1, MinesweeperInputAction::ExploreTile { pos: safe1.into() });
+        let GameMinesweeper::Sq(game) = session.game.as_ref() else {
+            unreachable!();
+        };
+        // both subplids capture territory for the same plid
+        assert_eq!(game.mapdata[safe0].owner(), 1);
+        assert_eq!(game.mapdata[safe1].owner(), 1);
+        let n_owned = game.mapdata.iter().filter(|(_, d)| d.owner() == 1).count();
+        assert_eq!(game.playerdata[0].n_owned as usize, n_owned);
+    }
+
+    #[test]
+    fn flood_captures_each_tile_once() {
+        let mut flooded = false;
+        for seed in 0..4 {
+            let game = builder::GameMinesweeperBuilder::new(Default::default(), 1)
+                .with_mapdata_sq(8, |_| TileKind::Regular);
+            let mut session = HeadlessSession::new(game, Box::new(MinesweeperInitData {
+                minegen: minegen::MineGenSettings {
+                    mine_density: 16,
+                    ..Default::default()
+                },
+                seed: Some(seed),
+            }));
+            explore(&mut session, Pos(0, 0));
+            let mut captured = HashSet::new();
+            for (_, out) in session.output_log() {
+                if let MwEv::TileOwner { pos, .. } = out.output {
+                    assert!(captured.insert(pos), "tile {:?} captured twice", pos);
+                }
+            }
+            let GameMinesweeper::Sq(game) = session.game.as_ref() else {
+                unreachable!();
+            };
+            let n_owned = game.mapdata.iter().filter(|(_, d)| d.owner() == 1).count();
+            assert_eq!(game.playerdata[0].n_owned as usize, n_owned);
+            flooded |= n_owned > 1;
+        }
+        assert!(flooded);
+    }
+
+    #[test]
+    fn classic_first_click() {
+        let settings = MinesweeperSettings {
+            rules: MinesweeperRules::Classic,
+            ..Default::default()
+        };
+        for seed in 0..4 {
+            let mut session = new_session(settings.clone(), seed);
+            let count_items = |session: &HeadlessSession<Box<GameMinesweeper>>| {
+                let GameMinesweeper::Sq(game) = session.game.as_ref() else {
+                    unreachable!();
+                };
+                assert!(game.mapdata.iter().all(|(_, d)| d.item() != ItemKind::Decoy));
+                game.mapdata.iter().filter(|(_, d)| d.item() == ItemKind::Mine).count()
+            };
+            let n_mines = count_items(&session);
+            explore(&mut session, Pos(0, 0));
+            assert_eq!(count_items(&session), n_mines);
+            let GameMinesweeper::Sq(game) = session.game.as_ref() else {
+                unreachable!();
+            };
+            for c in std::iter::once(Sq(0, 0)).chain(Sq(0, 0).iter_n1()) {
+                assert_eq!(game.mapdata[c].item(), ItemKind::Safe);
+            }
+            assert!(session.output_log().iter().all(|(_, out)| match out.output {
+                MwEv::DigitCapture { digit, .. } => !digit.asterisk,
+                MwEv::Explode { .. } => false,
+                _ => true,
+            }));
+        }
+    }
+
+    #[test]
+    fn classic_game_over_on_mine() {
+        let settings = MinesweeperSettings {
+            rules: MinesweeperRules::Classic,
+            ..Default::default()
+        };
+        let mut session = new_session(settings, 1);
+        explore(&mut session, Pos(0, 0));
+        let GameMinesweeper::Sq(game) = session.game.as_ref() else {
+            unreachable!();
+        };
+        let mine = game.mapdata.iter()
+            .find(|(_, d)| d.owner() == 0 && d.item() == ItemKind::Mine)
+            .map(|(c, _)| c)
+            .unwrap();
+        session.take_output();
+        explore(&mut session, mine.into());
+        assert!(session.is_game_over());
+        let GameMinesweeper::Sq(game) = session.game.as_ref() else {
+            unreachable!();
+        };
+        assert_eq!(game.mapdata[mine].item(), ItemKind::Mine);
+        assert!(game.mapdata[mine].exploded());
+        assert!(!session.output_log().iter().any(|(_, out)| matches!(out.output, MwEv::TileKind { .. })));
+    }
+
+    #[test]
+    fn classic_first_click_keeps_other_digits() {
+        let settings = MinesweeperSettings {
+            rules: MinesweeperRules::Classic,
+            ..Default::default()
+        };
+        // how many mines are around each of plid 1's tiles
+        let digits = |game: &GameMinesweeperTopo<Sq>| -> Vec<(Sq, usize)> {
+            game.mapdata.iter()
+                .filter(|(c, d)| c.ring() <= 8 && d.owner() == 1)
+                .map(|(c, _)| (c, c.iter_n1()
+                    .filter(|c2| game.mapdata.get(*c2).is_some_and(|d| d.item() == ItemKind::Mine))
+                    .count()))
+                .collect()
+        };
+        for seed in 0..4 {
+            let game = builder::GameMinesweeperBuilder::new(settings.clone(), 2)
+                .with_mapdata_sq(8, |_| TileKind::Regular);
+            let mut session = HeadlessSession::new(game, Box::new(MinesweeperInitData {
+                minegen: Default::default(),
+                seed: Some(seed),
+            }));
+            session.input(PlayerId::from(1), 0, MinesweeperInputAction::ExploreTile { pos: Pos(0, 0) });
+            let (before, target) = {
+                let GameMinesweeper::Sq(game) = session.game.as_ref() else {
+                    unreachable!();
+                };
+                let n_mines = |c: Sq| std::iter::once(c).chain(c.iter_n1())
+                    .filter(|c2| game.mapdata.get(*c2).is_some_and(|d| d.item() == ItemKind::Mine))
+                    .count();
+                // plid 2 starts right next to plid 1, preferably with mines around
+                let target = game.mapdata.iter()
+                    .filter(|(c, d)| c.ring() <= 8 && d.kind().is_land() && d.owner() == 0 && !d.exploded()
+                        && c.iter_n1().any(|c2| game.mapdata.get(c2).is_some_and(|d2| d2.owner() == 1)))
+                    .map(|(c, _)| c)
+                    .max_by_key(|c| n_mines(*c))
+                    .unwrap();
+                (digits(game), target)
+            };
+            session.input(PlayerId::from(2), 0, MinesweeperInputAction::ExploreTile { pos: target.into() });
+            let GameMinesweeper::Sq(game) = session.game.as_ref() else {
+                unreachable!();
+            };
+            assert_eq!(digits(game), before);
+        }
+    }
+
+    #[test]
+    fn chord_needs_matching_flags() {
+        let settings = MinesweeperSettings {
+            rules: MinesweeperRules::Classic,
+            ..Default::default()
+        };
+        let mut chorded = false;
+        for seed in 0..8 {
+            let mut session = new_session(settings.clone(), seed);
+            explore(&mut session, Pos(0, 0));
+            let GameMinesweeper::Sq(game) = session.game.as_ref() else {
+                unreachable!();
+            };
+            // one of our digits with both mines and safe tiles around it
+            let Some((c, mines)) = game.mapdata.iter()
+                .filter(|(c, d)| d.owner() == 1 && c.ring() < 8)
+                .map(|(c, _)| {
+                    let unowned: Vec<_> = c.iter_n1()
+                        .filter(|c2| game.mapdata[*c2].owner() == 0)
+                        .collect();
+                    (c, unowned)
+                })
+                .filter(|(_, unowned)| unowned.iter().any(|c2| game.mapdata[*c2].item() == ItemKind::Safe))
+                .find_map(|(c, unowned)| {
+                    let mines: Vec<_> = unowned.into_iter()
+                        .filter(|c2| game.mapdata[*c2].item() == ItemKind::Mine)
+                        .collect();
+                    (!mines.is_empty()).then_some((c, mines))
+                })
+            else {
+                continue;
+            };
+            session.take_output();
+            // not enough flags: nothing happens
+            session.input(PlayerId::from(1), 0, MinesweeperInputAction::ChordTile { pos: c.into() });
+            assert!(session.output_log().is_empty());
+            for mine in mines.iter() {
+                session.input(PlayerId::from(1), 0, MinesweeperInputAction::ToggleFlag { pos: (*mine).into() });
+            }
+            session.input(PlayerId::from(1), 0, MinesweeperInputAction::ChordTile { pos: c.into() });
+            assert!(!session.output_log().iter().any(|(_, out)| matches!(out.output, MwEv::Explode { .. })));
+            let GameMinesweeper::Sq(game) = session.game.as_ref() else {
+                unreachable!();
+            };
+            for c2 in c.iter_n1() {
+                if game.mapdata[c2].item() == ItemKind::Safe {
+                    assert_eq!(game.mapdata[c2].owner(), 1);
+                }
+            }
+            chorded = true;
+        }
+        assert!(chorded);
+    }
+}
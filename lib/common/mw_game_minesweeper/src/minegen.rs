@@ -1,16 +1,33 @@
 use mw_common::{prelude::*, game::ItemKind};
 
 use super::MyRng;
+use crate::solver::{simulate, TileKnowledge};
 
 #[derive(Debug, Clone)]
 #[cfg_attr(feature = "bevy", derive(Reflect))]
 #[derive(Serialize, Deserialize)]
 #[serde(default)]
 pub struct MineGenSettings {
-    /// Probability of an item appearing on a tile.
+    /// Probability of an item (a mine or a decoy) appearing on a tile.
     pub mine_density: u8,
     /// Probability a mine being replaced by a decoy instead.
     pub prob_decoy: u8,
+    /// Make sure the board can be cleared by pure logic, without guessing,
+    /// starting from the first tile explored in the game.
+    pub no_guess: bool,
+    /// Limit how many times the board may be re-checked by the solver
+    /// while making it solvable (see `no_guess`), to bound the time spent.
+    pub no_guess_max_passes: u16,
+}
+
+impl MineGenSettings {
+    /// Probability of any given land tile having a hazard (a mine or a decoy)
+    ///
+    /// Decoys replace some of the mines (see `prob_decoy`), rather than
+    /// being added on top of them, so this does not depend on `prob_decoy`.
+    pub fn hazard_density(&self) -> f32 {
+        self.mine_density as f32 / 255.0
+    }
 }
 
 impl Default for MineGenSettings {
@@ -18,6 +35,8 @@ impl Default for MineGenSettings {
         Self {
             mine_density: 96,
             prob_decoy: 64,
+            no_guess: false,
+            no_guess_max_passes: 256,
         }
     }
 }
@@ -43,3 +62,92 @@ pub fn gen_mines<C: Coord, D, L: MapDataLayout<C>>(
         }
     }
 }
+
+/// Generate mines so that the board can be cleared without guessing
+///
+/// The player is assumed to start at `start`, which (along with its
+/// neighbors) is kept safe. Starting from a random board (as `gen_mines`),
+/// hazards that block a logical solver are moved elsewhere, further away
+/// from what has been revealed, until the solver can reveal every safe tile.
+///
+/// The solver is run at most `no_guess_max_passes` times (and never more
+/// than once per tile), which bounds the time this can take.
+///
+/// Returns `false` if that could not be achieved within that many passes.
+/// The board is still valid, but may require guessing.
+pub fn gen_mines_no_guess<C: Coord, D, L: MapDataLayout<C>>(
+    settings: &MineGenSettings,
+    mapdata: &mut MapData<C, D, L>,
+    rng: &mut MyRng,
+    start: C,
+    f_get_kind: impl Fn(&D) -> TileKind,
+    f_set_item: impl Fn(&mut D, ItemKind),
+) -> bool {
+    let size = mapdata.size();
+    let playable: MapDataC<C, bool> = MapData::new_with(size, |c: C| {
+        c.ring() <= size && mapdata.get(c).map(&f_get_kind).is_some_and(TileKind::is_land)
+    });
+    let mut items: MapDataC<C, ItemKind> = MapData::new(size, ItemKind::Safe);
+    for c in items.iter_coords(None) {
+        if playable[c] && rng.gen_bool(settings.mine_density as f64 / 255.0) {
+            items[c] = if rng.gen_bool(settings.prob_decoy as f64 / 255.0) {
+                ItemKind::Decoy
+            } else {
+                ItemKind::Mine
+            };
+        }
+    }
+    items[start] = ItemKind::Safe;
+    for c in start.iter_n1() {
+        if c.ring() <= size {
+            items[c] = ItemKind::Safe;
+        }
+    }
+
+    let mut solved = false;
+    let max_passes = (settings.no_guess_max_passes as usize).min(C::map_area(size));
+    for _ in 0..max_passes {
+        let solver = simulate(size, start, |c| playable[c], |c| items[c] != ItemKind::Safe);
+        let is_revealed = |c: C| matches!(solver.knowledge(c), TileKnowledge::Revealed(_));
+        let unknown: Vec<C> = solver.iter_playable()
+            .filter(|(_, k)| *k == TileKnowledge::Unknown)
+            .map(|(c, _)| c)
+            .collect();
+        if unknown.iter().all(|c| items[*c] != ItemKind::Safe) {
+            solved = true;
+            break;
+        }
+        // hazards right at the edge of what the solver could reveal
+        let mut blocking: Vec<C> = unknown.iter().copied()
+            .filter(|c| items[*c] != ItemKind::Safe && c.iter_n1().any(is_revealed))
+            .collect();
+        if blocking.is_empty() {
+            // hazards that were deduced, but enclose unknown tiles
+            blocking = solver.iter_playable()
+                .filter(|(c, k)| *k == TileKnowledge::Hazard
+                    && c.iter_n1().any(|c2| solver.knowledge(c2) == TileKnowledge::Unknown))
+                .map(|(c, _)| c)
+                .collect();
+        }
+        let Some(&b) = blocking.choose(rng) else {
+            break;
+        };
+        let item = items[b];
+        items[b] = ItemKind::Safe;
+        let spots: Vec<C> = unknown.iter().copied()
+            .filter(|c| *c != b && items[*c] == ItemKind::Safe && !c.iter_n1().any(is_revealed))
+            .collect();
+        if let Some(&s) = spots.choose(rng) {
+            items[s] = item;
+        }
+    }
+
+    for c in mapdata.iter_coords(None) {
+        if playable[c] {
+            if let Some(d) = mapdata.get_mut(c) {
+                f_set_item(d, items[c]);
+            }
+        }
+    }
+    solved
+}
@@ -0,0 +1,305 @@
+//! Saving and restoring the full state of a game in progress
+
+use super::*;
+
+/// Bump this whenever the contents of `Snapshot` change
+const SNAPSHOT_VERSION: u8 = 1;
+
+#[derive(Serialize, Deserialize)]
+struct Snapshot {
+    version: u8,
+    topology: Topology,
+    map_size: u8,
+    /// The raw `TileData` of every tile, in `MapData` order
+    tiles: Vec<[u8; 2]>,
+    settings: MinesweeperSettings,
+    playerdata: Vec<PlayerData>,
+    n_unexplored_tiles: u16,
+    rng: MyRng,
+    seed: u64,
+    minegen: minegen::MineGenSettings,
+    /// Time since the game started
+    elapsed: Duration,
+    n_eliminated: u8,
+    is_game_over: bool,
+    /// Pending scheds, with how long until each one is due
+    scheds: Vec<(Duration, MinesweeperSchedEvent)>,
+}
+
+impl Snapshot {
+    fn decode(data: &[u8]) -> AnyResult<Self> {
+        let snapshot: Snapshot = bitcode::deserialize(data)
+            .context("Cannot decode minesweeper game snapshot")?;
+        ensure!(
+            snapshot.version == SNAPSHOT_VERSION,
+            "Unsupported minesweeper game snapshot version: {}", snapshot.version
+        );
+        Ok(snapshot)
+    }
+    fn mapdata<C: Coord>(&self) -> AnyResult<MapDataC<C, TileData>> {
+        let mut mapdata = MapDataC::<C, TileData>::new(self.map_size, TileData::default());
+        ensure!(
+            mapdata.data().len() == self.tiles.len(),
+            "Snapshot has {} tiles, expected {}", self.tiles.len(), mapdata.data().len()
+        );
+        for (d, bytes) in mapdata.data_mut().iter_mut().zip(self.tiles.iter()) {
+            *d = TileData::from_bytes(*bytes);
+        }
+        Ok(mapdata)
+    }
+}
+
+/// Read the map from a saved game, without restoring the game itself
+///
+/// Useful for setting up a session (map view, etc.) before restoring.
+/// Only the tile kinds are included: the items are hidden information
+/// (where the mines are), so they must not end up in a player's view.
+pub fn read_saved_map(data: &[u8]) -> AnyResult<(Topology, MapDataPos<MapGenTileData>)> {
+    fn convert<C: Coord>(snapshot: &Snapshot) -> AnyResult<MapDataC<C, MapGenTileData>> {
+        let mapdata = snapshot.mapdata::<C>()?;
+        Ok(mapdata.convert(|_, d| {
+            let mut tile = MapGenTileData::default();
+            tile.set_kind(d.kind());
+            tile
+        }))
+    }
+    let snapshot = Snapshot::decode(data)?;
+    let map: MapDataPos<MapGenTileData> = match snapshot.topology {
+        Topology::Hex => convert::<Hex>(&snapshot)?.rekey(),
+        Topology::Sq => convert::<Sq>(&snapshot)?.rekey(),
+    };
+    Ok((snapshot.topology, map))
+}
+
+impl GameSnapshot for Box<GameMinesweeper> {
+    fn save_state(
+        &self,
+        now: std::time::Instant,
+        scheds: &[(std::time::Instant, MinesweeperSchedEvent)],
+    ) -> AnyResult<Vec<u8>> {
+        let snapshot = match self.as_ref() {
+            GameMinesweeper::Hex(game) => game.snapshot(now, scheds),
+            GameMinesweeper::Sq(game) => game.snapshot(now, scheds),
+        };
+        bitcode::serialize(&snapshot)
+            .context("Cannot encode minesweeper game snapshot")
+    }
+    fn restore_state<H: Host<MinesweeperIo>>(host: &mut H, data: &[u8]) -> AnyResult<Self> {
+        let snapshot = Snapshot::decode(data)?;
+        let game = match snapshot.topology {
+            Topology::Hex => GameMinesweeper::Hex(GameMinesweeperTopo::restore(host, snapshot)?),
+            Topology::Sq => GameMinesweeper::Sq(GameMinesweeperTopo::restore(host, snapshot)?),
+        };
+        Ok(Box::new(game))
+    }
+}
+
+impl<C: Coord> GameMinesweeperTopo<C> {
+    fn snapshot(
+        &self,
+        now: std::time::Instant,
+        scheds: &[(std::time::Instant, MinesweeperSchedEvent)],
+    ) -> Snapshot {
+        Snapshot {
+            version: SNAPSHOT_VERSION,
+            topology: C::TOPOLOGY,
+            map_size: self.mapdata.size(),
+            tiles: self.mapdata.data().iter().map(|d| d.into_bytes()).collect(),
+            settings: self.settings.clone(),
+            playerdata: self.playerdata.clone(),
+            n_unexplored_tiles: self.n_unexplored_tiles,
+            rng: self.rng.clone(),
+            seed: self.seed,
+            minegen: self.minegen.clone(),
+            elapsed: now.saturating_duration_since(self.started),
+            n_eliminated: self.n_eliminated,
+            is_game_over: self.is_game_over,
+            scheds: scheds.iter()
+                .map(|(time, ev)| (time.saturating_duration_since(now), *ev))
+                .collect(),
+        }
+    }
+    fn restore<H: Host<MinesweeperIo>>(host: &mut H, snapshot: Snapshot) -> AnyResult<Self> {
+        let now = host.now();
+        let mut game = GameMinesweeperTopo {
+            mapdata: snapshot.mapdata()?,
+            settings: snapshot.settings,
+            playerdata: snapshot.playerdata,
+            n_unexplored_tiles: snapshot.n_unexplored_tiles,
+            floodq: Default::default(),
+            rng: snapshot.rng,
+            seed: snapshot.seed,
+            minegen: snapshot.minegen,
+            started: now.checked_sub(snapshot.elapsed).unwrap_or(now),
+            n_eliminated: snapshot.n_eliminated,
+            is_game_over: snapshot.is_game_over,
+        };
+        for (remain, ev) in snapshot.scheds {
+            host.sched(now + remain, ev);
+            match ev {
+                MinesweeperSchedEvent::GameOverOutOfTime => {
+                    host.msg((Plids::all(true), MwEv::Player {
+                        plid: PlayerId::Neutral,
+                        subplid: None,
+                        ev: PlayerEv::MatchTimeRemain {
+                            secs: remain.as_secs_f32().ceil() as u16,
+                        },
+                    }).into());
+                }
+            }
+        }
+        game.send_view(host);
+        Ok(game)
+    }
+    /// Send everything players need to know to rebuild their view of the game
+    fn send_view<H: Host<MinesweeperIo>>(&mut self, host: &mut H) {
+        let coords: Vec<C> = self.mapdata.iter().map(|(c, _)| c).collect();
+        for c in coords {
+            let d = self.mapdata[c];
+            if d.kind() == TileKind::Destroyed {
+                host.msg((Plids::all(true), MwEv::TileKind {
+                    kind: d.kind(), pos: c.into(),
+                }).into());
+            }
+            if d.owner() != 0 {
+                host.msg((Plids::all(true), MwEv::TileOwner {
+                    plid: d.owner().into(), pos: c.into(),
+                }).into());
+                self.compute_send_digit(host, d.owner().into(), c);
+            }
+            if d.flag() != 0 {
+                host.msg((Plids::all(true), MwEv::Flag {
+                    plid: d.flag().into(), pos: c.into(),
+                }).into());
+            }
+            if d.exploded() {
+                host.msg((Plids::all(true), MwEv::RevealItem {
+                    item: d.item(), pos: c.into(),
+                }).into());
+            }
+        }
+        for (i, playerdata) in self.playerdata.iter().enumerate() {
+            let plid = PlayerId::from(i as u8 + 1);
+            if playerdata.elim_order != 0 {
+                host.msg((Plids::all(true), MwEv::Player {
+                    plid,
+                    subplid: None,
+                    ev: PlayerEv::Eliminated,
+                }).into());
+            }
+            host.msg((Plids::all(true), MwEv::Player {
+                plid,
+                subplid: None,
+                ev: PlayerEv::LivesRemain {
+                    lives: playerdata.n_lives,
+                },
+            }).into());
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use mw_common::driver::headless::HeadlessSession;
+
+    use super::*;
+
+    #[test]
+    fn save_restore_continues_game() {
+        let settings = MinesweeperSettings {
+            time_limit_secs: 60,
+            ..Default::default()
+        };
+        let game = builder::GameMinesweeperBuilder::new(settings, 1)
+            .with_mapdata_sq(8, |_| TileKind::Regular);
+        let mut session = HeadlessSession::new(game, Box::new(MinesweeperInitData {
+            minegen: Default::default(),
+            seed: Some(1),
+        }));
+        session.advance(Duration::from_secs(20));
+        session.input(PlayerId::from(1), 0, MinesweeperInputAction::ExploreTile { pos: Pos(0, 0) });
+        let data = session.save().unwrap();
+
+        let mut restored = HeadlessSession::<Box<GameMinesweeper>>::restore(&data).unwrap();
+        let (GameMinesweeper::Sq(a), GameMinesweeper::Sq(b)) = (session.game.as_ref(), restored.game.as_ref()) else {
+            unreachable!();
+        };
+        assert_eq!(a.mapdata.data().len(), b.mapdata.data().len());
+        for (da, db) in a.mapdata.data().iter().zip(b.mapdata.data().iter()) {
+            assert_eq!(da.into_bytes(), db.into_bytes());
+        }
+        assert!(restored.output_log().iter().any(|(_, out)| out.output == MwEv::Player {
+            plid: PlayerId::Neutral,
+            subplid: None,
+            ev: PlayerEv::MatchTimeRemain { secs: 40 },
+        }));
+        assert!(restored.output_log().iter().any(|(_, out)| out.output == MwEv::TileOwner {
+            plid: PlayerId::from(1),
+            pos: Pos(0, 0),
+        }));
+
+        // both should play out the same from here
+        session.take_output();
+        restored.take_output();
+        for pos in [Pos(3, -2), Pos(-5, 4)] {
+            session.input(PlayerId::from(1), 0, MinesweeperInputAction::ExploreTile { pos });
+            restored.input(PlayerId::from(1), 0, MinesweeperInputAction::ExploreTile { pos });
+        }
+        session.fast_forward();
+        restored.fast_forward();
+        let log_a: Vec<_> = session.output_log().iter().map(|(_, out)| out.output.clone()).collect();
+        let log_b: Vec<_> = restored.output_log().iter().map(|(_, out)| out.output.clone()).collect();
+        assert_eq!(log_a, log_b);
+        assert_eq!(session.is_game_over(), restored.is_game_over());
+    }
+
+    #[test]
+    fn saved_map_matches() {
+        let game = builder::GameMinesweeperBuilder::new(Default::default(), 1)
+            .with_mapdata_hex(6, |c| if c.ring() > 4 { TileKind::Water } else { TileKind::Regular });
+        let session = HeadlessSession::new(game, Box::new(MinesweeperInitData {
+            minegen: Default::default(),
+            seed: Some(1),
+        }));
+        let (topology, map) = read_saved_map(&session.save().unwrap()).unwrap();
+        assert_eq!(topology, Topology::Hex);
+        assert_eq!(map.size(), 6);
+        let GameMinesweeper::Hex(game) = session.game.as_ref() else {
+            unreachable!();
+        };
+        for (c, d) in game.mapdata.iter() {
+            assert_eq!(map[c.into()].kind(), d.kind());
+        }
+        // the mines must not leak
+        assert!(game.mapdata.iter().any(|(_, d)| d.item() != ItemKind::Safe));
+        assert!(map.iter().all(|(_, d)| d.item() == ItemKind::Safe));
+    }
+
+    #[test]
+    fn restore_keeps_destroyed_tiles() {
+        let game = builder::GameMinesweeperBuilder::new(Default::default(), 1)
+            .with_mapdata_sq(8, |_| TileKind::Regular);
+        let mut session = HeadlessSession::new(game, Box::new(MinesweeperInitData {
+            minegen: Default::default(),
+            seed: Some(1),
+        }));
+        session.input(PlayerId::from(1), 0, MinesweeperInputAction::ExploreTile { pos: Pos(0, 0) });
+        let GameMinesweeper::Sq(game) = session.game.as_ref() else {
+            unreachable!();
+        };
+        let mine = game.mapdata.iter()
+            .find(|(c, d)| c.ring() <= 8 && d.owner() == 0 && d.item() == ItemKind::Mine)
+            .map(|(c, _)| c)
+            .unwrap();
+        session.input(PlayerId::from(1), 0, MinesweeperInputAction::ExploreTile { pos: mine.into() });
+        let data = session.save().unwrap();
+
+        let (_, map) = read_saved_map(&data).unwrap();
+        assert_eq!(map[mine.into()].kind(), TileKind::Destroyed);
+        let restored = HeadlessSession::<Box<GameMinesweeper>>::restore(&data).unwrap();
+        assert!(restored.output_log().iter().any(|(_, out)| out.output == MwEv::TileKind {
+            kind: TileKind::Destroyed,
+            pos: mine.into(),
+        }));
+    }
+}
@@ -0,0 +1,516 @@
+//! Logical Minesweeper Solver
+//!
+//! Deduces which tiles are safe and which are hazards (mines or decoys),
+//! from the digits of the revealed tiles, the way a player could, without
+//! guessing. Works with the `iter_n1` neighborhoods of any topology.
+//!
+//! Digits count all hazards among the tiles not owned by the player.
+//! The asterisk only says that some of those hazards are decoys, which
+//! does not change which tiles are safe, so the solver does not need it.
+
+use mw_common::prelude::*;
+
+/// What the solver knows about a tile
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TileKnowledge {
+    /// Not a tile that can have items (not land)
+    NotPlayable,
+    /// Could be anything
+    Unknown,
+    /// Deduced to be safe, but not yet revealed
+    Safe,
+    /// Deduced to contain a hazard (a mine or decoy)
+    Hazard,
+    /// Revealed, showing the given digit
+    Revealed(u8),
+}
+
+/// Constraint-propagation solver
+///
+/// Feed it revealed tiles with `reveal` and call `deduce` to find out
+/// which other tiles are certainly safe or hazardous.
+pub struct Solver<C: Coord> {
+    map: MapDataC<C, TileKnowledge>,
+    dirty: Vec<C>,
+}
+
+impl<C: Coord> Solver<C> {
+    /// Create a solver for a map where nothing has been revealed yet
+    ///
+    /// `f_playable` tells which tiles can contain items.
+    pub fn new(size: u8, f_playable: impl Fn(C) -> bool) -> Self {
+        Solver {
+            map: MapData::new_with(size, |c: C| {
+                if c.ring() <= size && f_playable(c) {
+                    TileKnowledge::Unknown
+                } else {
+                    TileKnowledge::NotPlayable
+                }
+            }),
+            dirty: vec![],
+        }
+    }
+
+    pub fn knowledge(&self, c: C) -> TileKnowledge {
+        if c.ring() > self.map.size() {
+            return TileKnowledge::NotPlayable;
+        }
+        self.map.get(c).copied().unwrap_or(TileKnowledge::NotPlayable)
+    }
+
+    /// Iterate over all tiles that can contain items
+    pub fn iter_playable(&self) -> impl Iterator<Item = (C, TileKnowledge)> + '_ {
+        self.map.iter_coords(None)
+            .map(|c| (c, self.map[c]))
+            .filter(|(_, k)| *k != TileKnowledge::NotPlayable)
+    }
+
+    /// Record that a tile has been revealed and what digit it shows
+    pub fn reveal(&mut self, c: C, digit: u8) {
+        if self.knowledge(c) == TileKnowledge::NotPlayable {
+            return;
+        }
+        self.map[c] = TileKnowledge::Revealed(digit);
+        self.dirty.push(c);
+        self.mark_dirty_around(c);
+    }
+
+    /// Record that a tile is known to contain a hazard
+    /// (such as when trusting the player's flags)
+    pub fn mark_hazard(&mut self, c: C) {
+        self.set(c, TileKnowledge::Hazard);
+    }
+
+    /// Record that a tile is known to be safe, without a digit
+    /// (such as tiles owned by other players)
+    pub fn mark_safe(&mut self, c: C) {
+        self.set(c, TileKnowledge::Safe);
+    }
+
+    /// Make all the deductions possible from the current knowledge
+    ///
+    /// Tiles newly deduced to be safe are appended to `out_safe`.
+    /// Returns `true` if anything new was deduced.
+    pub fn deduce(&mut self, out_safe: &mut Vec<C>) -> bool {
+        let mut progress = false;
+        let mut changes = vec![];
+        loop {
+            while let Some(c) = self.dirty.pop() {
+                self.rule_single(c, &mut changes);
+                progress |= self.apply(&mut changes, out_safe);
+            }
+            self.rule_subset(&mut changes);
+            if !self.apply(&mut changes, out_safe) {
+                break;
+            }
+            progress = true;
+        }
+        progress
+    }
+
+    /// Deduce everything possible and estimate the odds for the rest
+    ///
+    /// `density` is the probability of any given tile having a hazard,
+    /// used as the prior for tiles that no digit says anything about.
+    pub fn hints(&mut self, density: f32) -> Hints<C> {
+        let mut scratch = vec![];
+        self.deduce(&mut scratch);
+        let mut hints = Hints::default();
+        for (c, p) in self.probabilities(density) {
+            if p <= 0.0 {
+                hints.safe.push(c);
+            } else if p >= 1.0 {
+                hints.hazard.push(c);
+            } else {
+                hints.probabilities.push((c, p));
+            }
+        }
+        for (c, k) in self.iter_playable() {
+            match k {
+                TileKnowledge::Safe => hints.safe.push(c),
+                TileKnowledge::Hazard => hints.hazard.push(c),
+                _ => {}
+            }
+        }
+        hints
+    }
+
+    /// Estimate the probability of each `Unknown` tile containing a hazard
+    ///
+    /// Tiles next to revealed digits are grouped into independent clusters
+    /// and all arrangements of hazards consistent with the digits are
+    /// enumerated, weighted by `density`. A result of exactly `0.0` or `1.0`
+    /// means the tile is certainly safe / hazardous. Clusters too big to
+    /// enumerate only get a rough estimate from the digits.
+    pub fn probabilities(&self, density: f32) -> Vec<(C, f32)> {
+        let density = density.clamp(0.001, 0.999);
+        let mut result = vec![];
+        let mut visited: HashSet<C> = HashSet::default();
+        for (c, k) in self.iter_playable() {
+            if k != TileKnowledge::Unknown || visited.contains(&c) {
+                continue;
+            }
+            if !c.iter_n1().any(|c2| matches!(self.knowledge(c2), TileKnowledge::Revealed(_))) {
+                // nothing to go by
+                result.push((c, density));
+                continue;
+            }
+            let (tiles, constraints) = self.cluster(c, &mut visited);
+            let p = enumerate(tiles.len(), &constraints, density as f64)
+                .unwrap_or_else(|| estimate(tiles.len(), &constraints, density));
+            result.extend(tiles.into_iter().zip(p));
+        }
+        result
+    }
+
+    /// Collect the unknown tiles that are connected through shared digits,
+    /// along with all the digits that constrain them
+    fn cluster(&self, start: C, visited: &mut HashSet<C>) -> (Vec<C>, Vec<Constraint>) {
+        let mut tiles = vec![start];
+        let mut digits = vec![];
+        visited.insert(start);
+        let mut i = 0;
+        while i < tiles.len() {
+            for c2 in tiles[i].iter_n1() {
+                if !matches!(self.knowledge(c2), TileKnowledge::Revealed(_)) || digits.contains(&c2) {
+                    continue;
+                }
+                digits.push(c2);
+                for c3 in c2.iter_n1() {
+                    if self.knowledge(c3) == TileKnowledge::Unknown && visited.insert(c3) {
+                        tiles.push(c3);
+                    }
+                }
+            }
+            i += 1;
+        }
+        let constraints = digits.into_iter()
+            .filter_map(|c| self.constraint(c))
+            .map(|(unknown, remain)| {
+                let idxs = unknown.iter()
+                    .filter_map(|u| tiles.iter().position(|t| t == u))
+                    .collect();
+                (idxs, remain)
+            })
+            .collect();
+        (tiles, constraints)
+    }
+
+    fn set(&mut self, c: C, k: TileKnowledge) {
+        if self.knowledge(c) == TileKnowledge::NotPlayable {
+            return;
+        }
+        self.map[c] = k;
+        self.mark_dirty_around(c);
+    }
+
+    fn mark_dirty_around(&mut self, c: C) {
+        for c2 in c.iter_n1() {
+            if let TileKnowledge::Revealed(_) = self.knowledge(c2) {
+                self.dirty.push(c2);
+            }
+        }
+    }
+
+    fn apply(&mut self, changes: &mut Vec<(C, TileKnowledge)>, out_safe: &mut Vec<C>) -> bool {
+        let mut any = false;
+        for (c, k) in changes.drain(..) {
+            if self.knowledge(c) != TileKnowledge::Unknown {
+                continue;
+            }
+            self.set(c, k);
+            if k == TileKnowledge::Safe {
+                out_safe.push(c);
+            }
+            any = true;
+        }
+        any
+    }
+
+    /// The unknown neighbors of a revealed tile and
+    /// how many hazards remain to be found among them
+    fn constraint(&self, c: C) -> Option<(Vec<C>, i16)> {
+        let TileKnowledge::Revealed(digit) = self.knowledge(c) else {
+            return None;
+        };
+        let mut unknown = vec![];
+        let mut remain = digit as i16;
+        for c2 in c.iter_n1() {
+            match self.knowledge(c2) {
+                TileKnowledge::Unknown => unknown.push(c2),
+                TileKnowledge::Hazard => remain -= 1,
+                _ => {}
+            }
+        }
+        Some((unknown, remain))
+    }
+
+    /// If a digit is already satisfied, all the rest are safe.
+    /// If it needs all of the unknown tiles, they are all hazards.
+    fn rule_single(&self, c: C, changes: &mut Vec<(C, TileKnowledge)>) {
+        let Some((unknown, remain)) = self.constraint(c) else {
+            return;
+        };
+        if unknown.is_empty() {
+            return;
+        }
+        if remain == 0 {
+            changes.extend(unknown.into_iter().map(|c2| (c2, TileKnowledge::Safe)));
+        } else if remain == unknown.len() as i16 {
+            changes.extend(unknown.into_iter().map(|c2| (c2, TileKnowledge::Hazard)));
+        }
+    }
+
+    /// If the unknown tiles of one digit are a subset of those of another,
+    /// the difference must account for the difference in remaining hazards.
+    fn rule_subset(&self, changes: &mut Vec<(C, TileKnowledge)>) {
+        for a in self.map.iter_coords(None) {
+            let Some((unknown_a, remain_a)) = self.constraint(a) else {
+                continue;
+            };
+            if unknown_a.is_empty() {
+                continue;
+            }
+            let mut checked = vec![a];
+            for u in unknown_a.iter() {
+                for b in u.iter_n1() {
+                    if checked.contains(&b) {
+                        continue;
+                    }
+                    checked.push(b);
+                    let Some((unknown_b, remain_b)) = self.constraint(b) else {
+                        continue;
+                    };
+                    if !unknown_a.iter().all(|c| unknown_b.contains(c)) {
+                        continue;
+                    }
+                    let diff: Vec<C> = unknown_b.into_iter()
+                        .filter(|c| !unknown_a.contains(c))
+                        .collect();
+                    if diff.is_empty() {
+                        continue;
+                    }
+                    let remain_diff = remain_b - remain_a;
+                    if remain_diff == 0 {
+                        changes.extend(diff.into_iter().map(|c| (c, TileKnowledge::Safe)));
+                    } else if remain_diff == diff.len() as i16 {
+                        changes.extend(diff.into_iter().map(|c| (c, TileKnowledge::Hazard)));
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Indices of the unknown tiles of a cluster next to a digit,
+/// and how many hazards remain among them
+type Constraint = (Vec<usize>, i16);
+
+/// Clusters with more tiles than this are not enumerated exhaustively
+const MAX_ENUMERATE: usize = 24;
+
+/// Everything the solver can tell a player about the map
+#[derive(Debug, Clone, Default)]
+pub struct Hints<C> {
+    /// Tiles that are certainly safe to explore
+    pub safe: Vec<C>,
+    /// Tiles that certainly contain a hazard (mine or decoy)
+    pub hazard: Vec<C>,
+    /// Estimated probability of a hazard, for all the other tiles
+    pub probabilities: Vec<(C, f32)>,
+}
+
+/// Exact hazard probabilities of a cluster, by trying every arrangement
+///
+/// Returns `None` if the cluster is too big, or if no arrangement
+/// satisfies the digits (the knowledge given to the solver was wrong).
+fn enumerate(n_tiles: usize, constraints: &[Constraint], density: f64) -> Option<Vec<f32>> {
+    if n_tiles > MAX_ENUMERATE {
+        return None;
+    }
+    let mut tile_constraints = vec![vec![]; n_tiles];
+    for (k, (idxs, _)) in constraints.iter().enumerate() {
+        for &i in idxs {
+            tile_constraints[i].push(k);
+        }
+    }
+    let mut e = Enumeration {
+        tile_constraints: &tile_constraints,
+        remain: constraints.iter().map(|(_, remain)| *remain).collect(),
+        open: constraints.iter().map(|(idxs, _)| idxs.len() as i16).collect(),
+        assignment: vec![false; n_tiles],
+        ratio: density / (1.0 - density),
+        total: 0.0,
+        hazard: vec![0.0; n_tiles],
+    };
+    if e.remain.iter().zip(e.open.iter()).any(|(r, o)| *r < 0 || r > o) {
+        return None;
+    }
+    e.recurse(0, 1.0);
+    if e.total == 0.0 {
+        return None;
+    }
+    Some(e.hazard.iter().map(|w| (w / e.total) as f32).collect())
+}
+
+struct Enumeration<'a> {
+    /// Which constraints each tile is part of
+    tile_constraints: &'a [Vec<usize>],
+    /// Hazards yet to be placed, for each constraint
+    remain: Vec<i16>,
+    /// Tiles yet to be assigned, for each constraint
+    open: Vec<i16>,
+    assignment: Vec<bool>,
+    /// Relative weight of one more hazard in an arrangement
+    ratio: f64,
+    total: f64,
+    hazard: Vec<f64>,
+}
+
+impl Enumeration<'_> {
+    fn recurse(&mut self, i: usize, weight: f64) {
+        if i == self.assignment.len() {
+            self.total += weight;
+            for (w, is_hazard) in self.hazard.iter_mut().zip(self.assignment.iter()) {
+                if *is_hazard {
+                    *w += weight;
+                }
+            }
+            return;
+        }
+        let tile_constraints = self.tile_constraints;
+        for is_hazard in [false, true] {
+            let d = is_hazard as i16;
+            let fits = tile_constraints[i].iter()
+                .all(|&k| self.remain[k] >= d && self.remain[k] - d < self.open[k]);
+            if !fits {
+                continue;
+            }
+            for &k in tile_constraints[i].iter() {
+                self.remain[k] -= d;
+                self.open[k] -= 1;
+            }
+            self.assignment[i] = is_hazard;
+            self.recurse(i + 1, if is_hazard { weight * self.ratio } else { weight });
+            for &k in tile_constraints[i].iter() {
+                self.remain[k] += d;
+                self.open[k] += 1;
+            }
+        }
+    }
+}
+
+/// Rough hazard probabilities of a cluster, from the most
+/// pessimistic digit next to each tile
+///
+/// Never certain, because it is not a proof.
+fn estimate(n_tiles: usize, constraints: &[Constraint], density: f32) -> Vec<f32> {
+    let mut p = vec![None::<f32>; n_tiles];
+    for (idxs, remain) in constraints.iter() {
+        if idxs.is_empty() {
+            continue;
+        }
+        let p_k = *remain as f32 / idxs.len() as f32;
+        for &i in idxs {
+            p[i] = Some(p[i].map_or(p_k, |p_i| p_i.max(p_k)));
+        }
+    }
+    p.into_iter()
+        .map(|p_i| p_i.unwrap_or(density).clamp(0.001, 0.999))
+        .collect()
+}
+
+/// Simulate a player clearing the board by pure logic, starting from `start`
+///
+/// `f_hazard` tells the true contents of each tile. Digits are computed
+/// the same way as in the game: the number of hazards among `iter_n1`.
+///
+/// Returns the final state of the solver. The board is solvable
+/// without guessing if no `Unknown` tiles remain.
+pub fn simulate<C: Coord>(
+    size: u8,
+    start: C,
+    f_playable: impl Fn(C) -> bool,
+    f_hazard: impl Fn(C) -> bool,
+) -> Solver<C> {
+    let mut solver = Solver::new(size, &f_playable);
+    let digit = |c: C| c.iter_n1()
+        .filter(|c2| c2.ring() <= size && f_playable(*c2) && f_hazard(*c2))
+        .count() as u8;
+    if f_hazard(start) {
+        return solver;
+    }
+    solver.reveal(start, digit(start));
+    let mut safe = vec![];
+    while solver.deduce(&mut safe) {
+        for c in safe.drain(..) {
+            solver.reveal(c, digit(c));
+        }
+    }
+    solver
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn subset_deduction() {
+        // Sq board with 2 rows of 3 tiles, bottom row revealed:
+        //   * . *
+        //   1 2 1
+        let mut solver = Solver::<Sq>::new(3, |c: Sq| (-1..=0).contains(&c.y()) && (-1..=1).contains(&c.x()));
+        solver.reveal(Sq(0, -1), 1);
+        solver.reveal(Sq(0, 0), 2);
+        solver.reveal(Sq(0, 1), 1);
+        let mut safe = vec![];
+        assert!(solver.deduce(&mut safe));
+        assert_eq!(safe, vec![Sq(-1, 0)]);
+        assert_eq!(solver.knowledge(Sq(-1, -1)), TileKnowledge::Hazard);
+        assert_eq!(solver.knowledge(Sq(-1, 0)), TileKnowledge::Safe);
+        assert_eq!(solver.knowledge(Sq(-1, 1)), TileKnowledge::Hazard);
+    }
+
+    #[test]
+    fn probabilities_5050() {
+        // Sq board with 2 rows of 2 tiles, one revealed, one known safe:
+        //   . .
+        //   1 s
+        // The two remaining tiles are equally likely to be the hazard.
+        let mut solver = Solver::<Sq>::new(2, |c: Sq| (0..=1).contains(&c.y()) && (0..=1).contains(&c.x()));
+        solver.reveal(Sq(0, 0), 1);
+        solver.mark_safe(Sq(0, 1));
+        let hints = solver.hints(0.25);
+        assert!(hints.hazard.is_empty());
+        assert_eq!(hints.safe, vec![Sq(0, 1)]);
+        assert_eq!(hints.probabilities.len(), 2);
+        for (c, p) in hints.probabilities {
+            assert!(c == Sq(1, 0) || c == Sq(1, 1));
+            assert!((p - 0.5).abs() < 0.0001);
+        }
+    }
+
+    #[test]
+    fn enumeration_certainty() {
+        // Sq board with 2 rows of 3 tiles, bottom row revealed or known safe:
+        //   . . .
+        //   1 1 s
+        // The middle digit's hazard must be the left digit's hazard,
+        // so enumerating the arrangements finds the right tile to be safe.
+        let mut solver = Solver::<Sq>::new(3, |c: Sq| (0..=1).contains(&c.y()) && (-1..=1).contains(&c.x()));
+        solver.reveal(Sq(0, -1), 1);
+        solver.reveal(Sq(0, 0), 1);
+        solver.mark_safe(Sq(0, 1));
+        let p: HashMap<Sq, f32> = solver.probabilities(0.25).into_iter().collect();
+        assert_eq!(p.len(), 3);
+        assert_eq!(p[&Sq(1, 1)], 0.0);
+        assert!((p[&Sq(1, -1)] - 0.5).abs() < 0.0001);
+        assert!((p[&Sq(1, 0)] - 0.5).abs() < 0.0001);
+    }
+
+    #[test]
+    fn simulate_no_mines() {
+        let solver = simulate(4, Hex(0, 0), |_| true, |_| false);
+        assert!(solver.iter_playable().all(|(_, k)| matches!(k, TileKnowledge::Revealed(0))));
+    }
+}